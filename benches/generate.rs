@@ -0,0 +1,148 @@
+//! Times the generators over the test data, loaded several times to have some hundreds of
+//! structure definitions, and measures their peak resident memory on Unix. Run with
+//! `cargo bench`; the output of the generators is written to target/bench.
+//!
+//! With `FHIR_GENERATE_BASELINE` set to the binary of another revision, e.g. one built in a
+//! `git worktree`, each generator is run with both, their outputs are checked to be the same and
+//! the ratio of their median times and peak memory is shown.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+const RUNS: usize = 10;
+const COPIES: usize = 3;
+
+/// Median time and peak resident memory in KiB of the runs of a generator
+struct Measure {
+    median: Duration,
+    peak_kib: Option<u64>,
+}
+
+fn main() {
+    let mut files = Vec::<PathBuf>::new();
+    for dir in fs::read_dir("test_data").expect("test_data is readable").flatten() {
+        if dir.path().is_dir() {
+            files.extend(
+                fs::read_dir(dir.path())
+                    .expect("test_data is readable")
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|extension| extension == "json")),
+            );
+        }
+    }
+    files.sort();
+    let files = (0..COPIES)
+        .flat_map(|_| files.iter().map(|file| fs::canonicalize(file).expect("file exists")))
+        .collect::<Vec<_>>();
+    let baseline = env::var_os("FHIR_GENERATE_BASELINE").map(PathBuf::from);
+    let output_dir = Path::new("target/bench");
+
+    println!("{} structure definitions, {} runs", files.len(), RUNS);
+    for (generator, options) in [
+        ("paths", &["--output-file=paths.csv"][..]),
+        ("table", &[]),
+        ("plant-uml", &["--output-file=model.plantuml"]),
+        ("json", &["--output-file=model.json"]),
+    ] {
+        let mut binaries = vec![("current", PathBuf::from(env!("CARGO_BIN_EXE_fhir-generate")))];
+        binaries.extend(baseline.iter().map(|baseline| ("baseline", baseline.clone())));
+        let measures = measure(&binaries, generator, options, &files, output_dir);
+        print_measure(generator, &measures[0]);
+        if let [current, before] = &measures[..] {
+            print_measure("baseline", before);
+            assert!(
+                same_files(&output_dir.join("current").join(generator), &output_dir.join("baseline").join(generator)),
+                "{} writes other output than the baseline",
+                generator
+            );
+            print!("{:<10} time {:>5.2}x", "", ms(current.median) / ms(before.median));
+            match (current.peak_kib, before.peak_kib) {
+                (Some(kib), Some(baseline_kib)) => println!("  peak RSS {:>5.2}x", kib as f64 / baseline_kib as f64),
+                _ => println!(),
+            }
+        }
+    }
+}
+
+/// Runs a generator with each binary, taking turns so that both see the same load of the machine,
+/// each writing to its own folder under the output folder
+fn measure(binaries: &[(&str, PathBuf)], generator: &str, options: &[&str], files: &[PathBuf], output_dir: &Path) -> Vec<Measure> {
+    let mut times = vec![Vec::<Duration>::new(); binaries.len()];
+    let mut peak_kib = vec![None::<u64>; binaries.len()];
+    for _ in 0..RUNS {
+        for (i, (name, binary)) in binaries.iter().enumerate() {
+            let dir = output_dir.join(name).join(generator);
+            fs::create_dir_all(&dir).expect("the bench folder can be created");
+            let start = Instant::now();
+            let child = Command::new(binary)
+                .current_dir(&dir)
+                .args([generator, "--force"])
+                .args(options)
+                .args(files)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .expect("fhir-generate runs");
+            let (success, kib) = wait(child);
+            times[i].push(start.elapsed());
+            assert!(success, "{} failed with {}", generator, binary.display());
+            peak_kib[i] = peak_kib[i].max(kib);
+        }
+    }
+    times
+        .into_iter()
+        .zip(peak_kib)
+        .map(|(mut times, peak_kib)| {
+            times.sort();
+            Measure { median: times[RUNS / 2], peak_kib }
+        })
+        .collect()
+}
+
+fn print_measure(label: &str, measure: &Measure) {
+    print!("{:<10} median {:>8.2} ms", label, ms(measure.median));
+    match measure.peak_kib {
+        Some(kib) => println!("  peak RSS {:>7.1} MiB", kib as f64 / 1024.0),
+        None => println!(),
+    }
+}
+
+/// Whether two folders hold the same files with the same content
+fn same_files(dir: &Path, other: &Path) -> bool {
+    let contents = |dir: &Path| {
+        let mut contents = fs::read_dir(dir)
+            .expect("the bench folder is readable")
+            .flatten()
+            .map(|entry| (entry.file_name(), fs::read(entry.path()).unwrap_or_default()))
+            .collect::<Vec<_>>();
+        contents.sort();
+        contents
+    };
+    contents(dir) == contents(other)
+}
+
+/// Waits for a run, with whether it succeeded and its peak resident memory in KiB
+#[cfg(unix)]
+fn wait(child: Child) -> (bool, Option<u64>) {
+    let mut status = 0;
+    // SAFETY: rusage is plain data, and the child is waited for here only, by its pid
+    let mut usage = unsafe { std::mem::zeroed::<libc::rusage>() };
+    let pid = unsafe { libc::wait4(child.id() as libc::pid_t, &mut status, 0, &mut usage) };
+    let success = pid > 0 && libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0;
+    // bytes on macOS, kilobytes elsewhere
+    let max_rss = usage.ru_maxrss as u64;
+    (success, Some(if cfg!(target_os = "macos") { max_rss / 1024 } else { max_rss }))
+}
+
+#[cfg(not(unix))]
+fn wait(mut child: Child) -> (bool, Option<u64>) {
+    (child.wait().is_ok_and(|status| status.success()), None)
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
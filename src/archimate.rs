@@ -0,0 +1,123 @@
+use crate::doc_index::DocIndex;
+use crate::output::create_output_file;
+use crate::summary::status;
+use crate::utils::escape_xml;
+use crate::{ArchimateArgs, StructureDefTreeInfo};
+use std::io::{BufWriter, Write};
+
+/// A relationship between two elements of the model: its type, the source and target structure
+/// definitions and its name, e.g. ("Association", "EHDSPatient", "EHDSAddress", "address")
+type Relationship<'a> = (&'static str, &'a str, &'a str, String);
+
+/// The properties of the elements, defined once in the model
+const PROPERTIES: &[&str] = &["url", "version", "status"];
+
+/// Writes an ArchiMate model in the Open Exchange format, for import in e.g. Archi: an element per
+/// structure definition, a business object for logical models and a data object for the others,
+/// with the url, version and status as properties. An element typed by or referring to another
+/// structure definition becomes an association named by the element, and a profile on another one
+/// a realization of it, or a specialization when both are the same kind of object.
+pub fn generate_archimate(args: &ArchimateArgs, docs: &[StructureDefTreeInfo]) -> Result<(), Box<dyn std::error::Error>> {
+    let index = DocIndex::new(docs);
+    let mut relationships = Vec::<Relationship>::new();
+    for doc in docs.iter() {
+        status!("processing: {}", doc.id);
+        if let Some(base) = index.base_of(doc) {
+            let relationship = if object_type(base) == object_type(doc) { "Specialization" } else { "Realization" };
+            relationships.push((relationship, &doc.id, &base.id, String::new()));
+        }
+        for (_idx, element) in doc.element_tree.iter().filter(|(_, element)| element.depth > 0 && &*element.max != "0") {
+            let name = element.id.split_once('.').map(|(_, path)| path).unwrap_or_default();
+            for target in element.datatype.iter().filter_map(|datatype| index.get(datatype)) {
+                let relationship = ("Association", doc.id.as_str(), target.id.as_str(), name.to_string());
+                if !relationships.contains(&relationship) {
+                    relationships.push(relationship);
+                }
+            }
+        }
+    }
+
+    let mut writer = BufWriter::new(create_output_file(&args.output_file)?);
+    writeln!(writer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(
+        writer,
+        "<model xmlns=\"http://www.opengroup.org/xsd/archimate/3.0/\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:schemaLocation=\"http://www.opengroup.org/xsd/archimate/3.0/ http://www.opengroup.org/xsd/archimate/3.1/archimate3_Model.xsd\" identifier=\"id-model\">"
+    )?;
+    writeln!(writer, "  <name xml:lang=\"en\">{}</name>", escape_xml(&args.title))?;
+
+    writeln!(writer, "  <elements>")?;
+    for doc in docs.iter() {
+        writeln!(writer, "    <element identifier=\"{}\" xsi:type=\"{}\">", identifier(&doc.id), object_type(doc))?;
+        writeln!(writer, "      <name xml:lang=\"en\">{}</name>", escape_xml(&doc.id))?;
+        let documentation = if doc.description.is_empty() {
+            doc.element_tree.iter().next().map(|(_, root)| root.definition.as_ref()).unwrap_or_default()
+        } else {
+            &doc.description
+        };
+        if !documentation.is_empty() {
+            writeln!(writer, "      <documentation xml:lang=\"en\">{}</documentation>", escape_xml(documentation))?;
+        }
+        // in the order of PROPERTIES
+        let properties = [&doc.url, &doc.version, &doc.status];
+        if properties.iter().any(|value| !value.is_empty()) {
+            writeln!(writer, "      <properties>")?;
+            for (property_num, value) in properties.iter().enumerate().filter(|(_, value)| !value.is_empty()) {
+                writeln!(
+                    writer,
+                    "        <property propertyDefinitionRef=\"propid-{}\"><value xml:lang=\"en\">{}</value></property>",
+                    property_num + 1,
+                    escape_xml(value)
+                )?;
+            }
+            writeln!(writer, "      </properties>")?;
+        }
+        writeln!(writer, "    </element>")?;
+    }
+    writeln!(writer, "  </elements>")?;
+
+    if !relationships.is_empty() {
+        writeln!(writer, "  <relationships>")?;
+        for (relationship_num, (relationship, source, target, name)) in relationships.iter().enumerate() {
+            write!(
+                writer,
+                "    <relationship identifier=\"id-relationship-{}\" source=\"{}\" target=\"{}\" xsi:type=\"{}\"",
+                relationship_num + 1,
+                identifier(source),
+                identifier(target),
+                relationship
+            )?;
+            if name.is_empty() {
+                writeln!(writer, "/>")?;
+            } else {
+                writeln!(writer, ">\n      <name xml:lang=\"en\">{}</name>\n    </relationship>", escape_xml(name))?;
+            }
+        }
+        writeln!(writer, "  </relationships>")?;
+    }
+
+    // the properties are defined after the elements and relationships, as the schema orders them
+    writeln!(writer, "  <propertyDefinitions>")?;
+    for (property_num, name) in PROPERTIES.iter().enumerate() {
+        writeln!(
+            writer,
+            "    <propertyDefinition identifier=\"propid-{}\" type=\"string\">\n      <name xml:lang=\"en\">{}</name>\n    </propertyDefinition>",
+            property_num + 1,
+            name
+        )?;
+    }
+    writeln!(writer, "  </propertyDefinitions>")?;
+    writeln!(writer, "</model>")?;
+    Ok(())
+}
+
+/// Logical models are business objects, the information as the business sees it, and the other
+/// structure definitions the data objects representing it
+fn object_type(doc: &StructureDefTreeInfo) -> &'static str {
+    if doc.kind == "logical" { "BusinessObject" } else { "DataObject" }
+}
+
+/// Identifier of the element of a structure definition, an XML ID as the exchange format requires:
+/// ids of structure definitions are letters, digits, '-' and '.', which may not start one
+fn identifier(id: &str) -> String {
+    format!("id-{}", id)
+}
@@ -0,0 +1,184 @@
+use crate::output::{OutputFile, create_output_file};
+use crate::summary::status;
+use serde_json::json;
+use std::collections::HashSet;
+use std::fs::{read, remove_file};
+use std::io::{BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// Writes the files generated in a run into a zip archive with a manifest.json listing them, and
+/// removes them. The entries are named by their path below the working directory, and stored
+/// uncompressed with a fixed date, so that the same files give the same archive.
+pub fn write_archive(archive: &Path, files: &[PathBuf]) -> Result<(), Box<dyn std::error::Error>> {
+    // --output-dir is not given with --archive
+    let base = normalize(Path::new("."))?;
+    let archive_path = normalize(archive)?;
+    let mut files_to_archive = Vec::<PathBuf>::new();
+    for file in files.iter() {
+        let path = normalize(file)?;
+        // a file recorded under two spellings of its path is archived once
+        if path != archive_path && !files_to_archive.contains(&path) {
+            files_to_archive.push(path);
+        }
+    }
+
+    let mut names = HashSet::<String>::from(["manifest.json".to_string()]);
+    let mut entries = Vec::<(String, Vec<u8>)>::new();
+    for file in files_to_archive.iter() {
+        let name = entry_name(file, &base)?;
+        if !names.insert(name.clone()) {
+            return Err(format!("Two files would both be archived as '{}', the generated files are left in place", name).into());
+        }
+        entries.push((name, read(file)?));
+    }
+    let manifest = json!({
+        "generator": format!("fhir-generate {}", env!("CARGO_PKG_VERSION")),
+        "files": entries
+            .iter()
+            .map(|(name, data)| json!({ "path": name, "size": data.len(), "crc32": format!("{:08x}", crc32(data)) }))
+            .collect::<Vec<_>>(),
+    });
+    entries.push(("manifest.json".to_string(), serde_json::to_vec_pretty(&manifest)?));
+    check_limits(&entries)?;
+
+    status!("archiving: {} files into {}", entries.len() - 1, archive.display());
+    let output = create_output_file(archive)?;
+    if matches!(output, OutputFile::Skipped(_)) {
+        return Err(format!(
+            "Archive '{}' exists and is not overwritten without --force, the generated files are left in place",
+            archive.display()
+        )
+        .into());
+    }
+    let mut writer = BufWriter::new(output);
+    write_zip(&mut writer, &entries)?;
+    // the files are only removed once they are on disk in the archive
+    if let OutputFile::File(file) = writer.into_inner().map_err(|e| e.into_error())? {
+        file.sync_all()?;
+    }
+    for file in files_to_archive.iter() {
+        remove_file(file)?;
+    }
+    Ok(())
+}
+
+/// The absolute path of a file, with `.` and `..` resolved without following links, as the file
+/// may not exist
+fn normalize(file: &Path) -> std::io::Result<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in std::path::absolute(file)?.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                path.pop();
+            }
+            component => path.push(component),
+        }
+    }
+    Ok(path)
+}
+
+/// Name of a file in the archive, its path below the base folder with / as separator
+fn entry_name(file: &Path, base: &Path) -> Result<String, String> {
+    let relative = file.strip_prefix(base).map_err(|_| {
+        format!(
+            "'{}' is not below '{}' and cannot be archived, the generated files are left in place",
+            file.display(),
+            base.display()
+        )
+    })?;
+    Ok(relative.components().map(|component| component.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/"))
+}
+
+/// Size of the local header and of the central directory record of an entry, without its name
+const LOCAL_HEADER_LEN: u64 = 30;
+const CENTRAL_RECORD_LEN: u64 = 46;
+
+/// Checks that the entries fit in a zip file without the zip64 extensions: at most 65535 entries
+/// with names of at most 65535 bytes, and offsets and sizes below 4 GiB
+fn check_limits(entries: &[(String, Vec<u8>)]) -> Result<(), String> {
+    if entries.len() > u16::MAX as usize {
+        return Err(format!("{} files are more than a zip archive can hold, at most {}", entries.len(), u16::MAX));
+    }
+    if let Some((name, _)) = entries.iter().find(|(name, _)| name.len() > u16::MAX as usize) {
+        return Err(format!("The name '{}' is too long for a zip archive", name));
+    }
+    let local = entries.iter().map(|(name, data)| LOCAL_HEADER_LEN + (name.len() + data.len()) as u64).sum::<u64>();
+    let central = entries.iter().map(|(name, _)| CENTRAL_RECORD_LEN + name.len() as u64).sum::<u64>();
+    if local > u32::MAX as u64 || central > u32::MAX as u64 {
+        return Err("The generated files are too large for a zip archive, at most 4 GiB".to_string());
+    }
+    Ok(())
+}
+
+/// 1980-01-01 00:00, the earliest date of the zip format
+const DOS_DATE: u16 = (1 << 5) | 1;
+const DOS_TIME: u16 = 0;
+
+/// Writes a zip file with stored entries, followed by the central directory. The sizes and offsets
+/// fit in their fields, see `check_limits`.
+fn write_zip<W: Write>(writer: &mut W, entries: &[(String, Vec<u8>)]) -> std::io::Result<()> {
+    let mut central_directory = Vec::<u8>::new();
+    let mut offset = 0u32;
+    for (name, data) in entries.iter() {
+        let (crc, size, name_len) = (crc32(data), data.len() as u32, name.len() as u16);
+        let mut local_header = Vec::<u8>::new();
+        local_header.extend(0x04034b50u32.to_le_bytes());
+        // version needed, flags (UTF-8 names), method (stored), time, date
+        for value in [20u16, 1 << 11, 0, DOS_TIME, DOS_DATE] {
+            local_header.extend(value.to_le_bytes());
+        }
+        for value in [crc, size, size] {
+            local_header.extend(value.to_le_bytes());
+        }
+        local_header.extend(name_len.to_le_bytes());
+        local_header.extend(0u16.to_le_bytes());
+        local_header.extend(name.as_bytes());
+        writer.write_all(&local_header)?;
+        writer.write_all(data)?;
+
+        central_directory.extend(0x02014b50u32.to_le_bytes());
+        // version made by, version needed, flags, method, time, date
+        for value in [20u16, 20, 1 << 11, 0, DOS_TIME, DOS_DATE] {
+            central_directory.extend(value.to_le_bytes());
+        }
+        for value in [crc, size, size] {
+            central_directory.extend(value.to_le_bytes());
+        }
+        // name length, extra field length, comment length, disk number, internal attributes
+        for value in [name_len, 0, 0, 0, 0] {
+            central_directory.extend(value.to_le_bytes());
+        }
+        // external attributes, offset of the local header
+        for value in [0u32, offset] {
+            central_directory.extend(value.to_le_bytes());
+        }
+        central_directory.extend(name.as_bytes());
+        offset += local_header.len() as u32 + size;
+    }
+
+    writer.write_all(&central_directory)?;
+    let mut end = Vec::<u8>::new();
+    end.extend(0x06054b50u32.to_le_bytes());
+    // disk numbers, entries on this disk and in total
+    for value in [0u16, 0, entries.len() as u16, entries.len() as u16] {
+        end.extend(value.to_le_bytes());
+    }
+    for value in [central_directory.len() as u32, offset] {
+        end.extend(value.to_le_bytes());
+    }
+    end.extend(0u16.to_le_bytes());
+    writer.write_all(&end)
+}
+
+/// CRC-32 as used by zip (IEEE polynomial, reflected)
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,95 @@
+use crate::output::create_output_file;
+use crate::summary::status;
+use crate::utils::escape_csv;
+use crate::{AttributesArgs, AttributesFormat, ElementInfo, StructureDefTreeInfo};
+use serde_json::json;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Writes a row per structure definition, element and attribute with its value, e.g.
+/// `EHDSAddress,EHDSAddress.line,max,*`, the long format loaded into a database as is. Attributes
+/// with several values, like the types, have a row per value, and those without a value none.
+pub fn generate_attributes(args: &AttributesArgs, docs: &[StructureDefTreeInfo]) -> Result<(), Box<dyn std::error::Error>> {
+    let format = args.format.clone().unwrap_or_else(|| format_of(&args.output_file));
+    let mut writer = BufWriter::new(create_output_file(&args.output_file)?);
+    if format == AttributesFormat::Csv {
+        writeln!(writer, "structure definition,element,attribute,value")?;
+    }
+    for doc in docs.iter() {
+        status!("processing: {}", doc.id);
+        for (_idx, element) in doc.element_tree.iter() {
+            for (attribute, value) in attributes_of(element) {
+                match format {
+                    AttributesFormat::Csv => writeln!(
+                        writer,
+                        "{},{},{},{}",
+                        escape_csv(&doc.id),
+                        escape_csv(&element.id),
+                        attribute,
+                        escape_csv(&value)
+                    )?,
+                    AttributesFormat::Ndjson => writeln!(
+                        writer,
+                        "{}",
+                        json!({
+                            "structureDefinition": doc.id,
+                            "element": element.id,
+                            "attribute": attribute,
+                            "value": value,
+                        })
+                    )?,
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// NDJSON for files named .ndjson or .jsonl, otherwise CSV
+fn format_of(path: &Path) -> AttributesFormat {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("ndjson" | "jsonl") => AttributesFormat::Ndjson,
+        _ => AttributesFormat::Csv,
+    }
+}
+
+/// The attributes of an element with a value, named as in the element definition
+fn attributes_of(element: &ElementInfo) -> Vec<(&'static str, String)> {
+    let mut attributes = Vec::<(&'static str, String)>::new();
+    let mut add = |attribute: &'static str, value: &str| {
+        if !value.is_empty() {
+            attributes.push((attribute, value.to_string()));
+        }
+    };
+    add("short", &element.short);
+    add("definition", &element.definition);
+    add("requirements", element.requirements.as_deref().unwrap_or_default());
+    add("min", &element.min);
+    add("max", &element.max);
+    // including the cardinality of the ancestors
+    add("globalMin", &element.global_min);
+    add("globalMax", &element.global_max);
+    for datatype in element.datatype.iter().filter(|datatype| !element.reference.contains(datatype)) {
+        add("type", datatype);
+    }
+    if !element.reference.is_empty() {
+        add("type", "Reference");
+    }
+    for target in element.reference.iter() {
+        add("targetProfile", target);
+    }
+    add("binding.strength", element.binding_strength.as_deref().unwrap_or_default());
+    add("binding.valueSet", element.value_set.as_deref().unwrap_or_default());
+    add("binding.valueSetTitle", element.value_set_title.as_deref().unwrap_or_default());
+    add("binding.description", element.binding.as_deref().unwrap_or_default());
+    if let Some((datatype, value)) = &element.fixed_value {
+        add("fixed", &format!("{}: {}", datatype, value));
+    }
+    add("mustSupport", &element.must_support.to_string());
+    add("isModifier", &element.is_modifier.to_string());
+    add("isSummary", &element.is_summary.to_string());
+    for (actor, code, _documentation) in element.obligation.iter() {
+        add("obligation", &format!("{}: {}", actor, code));
+    }
+    attributes
+}
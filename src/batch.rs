@@ -0,0 +1,118 @@
+use crate::summary::status;
+use crate::{BatchArgs, Cli, Commands, run};
+use clap::{CommandFactory, Parser};
+use std::{fs::read_to_string, path::Path};
+use toml_edit::{DocumentMut, Item};
+
+/// The keys of a manifest, for the help of `batch`
+pub const MANIFEST_KEYS: &str = "Manifest keys:
+  [defaults]               options given to a generator for all inputs, e.g. table = [\"--flags\"]
+  [[input]]                an input, run through its generators on its own
+    files = [...]          input files, relative to the manifest
+    generators = [...]     generators run on the files, e.g. [\"table\", \"plant-uml\"]
+    prefix-code = \"...\"    --prefix-code of the generators with a code prefix, e.g. \"PAT\"
+    output = \"...\"         output file, without extension and relative to the manifest, of those writing one
+    options.<generator>    options given to a generator for this input, e.g. options.plant-uml = [\"--show-base\"]
+
+Example:
+  [defaults]
+  table = [\"--flags\"]
+
+  [[input]]
+  files = [\"StructureDefinition-EHDSPatient.json\"]
+  generators = [\"table\", \"plant-uml\"]
+  prefix-code = \"PAT\"
+  output = \"patient\"
+  options.plant-uml = [\"--show-base\"]";
+
+/// Runs the generators of each input of a manifest as if each was given on the command line, e.g.
+///
+/// ```toml
+/// # options given to a generator for all inputs
+/// [defaults]
+/// table = ["--flags"]
+///
+/// [[input]]
+/// files = ["StructureDefinition-EHDSPatient.json"]
+/// generators = ["table", "plant-uml"]
+/// prefix-code = "PAT"
+/// output = "patient"
+/// options.plant-uml = ["--show-base"]
+/// ```
+///
+/// Files and outputs are relative to the manifest, wherever it is run from. `prefix-code` is given to the generators with a code prefix,
+/// and `output` (without extension) to those writing a single output file.
+pub fn run_batch(args: &BatchArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = read_to_string(&args.manifest)?
+        .parse::<DocumentMut>()
+        .map_err(|e| format!("Error reading manifest '{}': {}", args.manifest.display(), e))?;
+    let dir = args.manifest.parent().unwrap_or(Path::new(""));
+    let defaults = manifest.get("defaults");
+    let inputs = manifest
+        .get("input")
+        .and_then(Item::as_array_of_tables)
+        .ok_or("The manifest must list its inputs as [[input]] tables")?;
+
+    for (input_num, input) in inputs.iter().enumerate() {
+        let context = format!("input {} of '{}'", input_num + 1, args.manifest.display());
+        let files = strings(input.get("files"), "files", &context)?;
+        if files.is_empty() {
+            return Err(format!("No files given for {}", context).into());
+        }
+        for generator in strings(input.get("generators"), "generators", &context)?.iter() {
+            let mut arguments = vec!["fhir-generate".to_string(), generator.clone()];
+            arguments.extend(strings(defaults.and_then(|d| d.get(generator)), generator, "defaults")?);
+            arguments.extend(strings(input.get("options").and_then(|o| o.get(generator)), generator, &context)?);
+            if let Some(prefix_code) = input.get("prefix-code").and_then(Item::as_str)
+                && default_value(generator, "prefix_code").is_some()
+            {
+                arguments.extend(["--prefix-code".to_string(), prefix_code.to_string()]);
+            }
+            if let Some(output) = input.get("output").and_then(Item::as_str)
+                && let Some(default_output) = default_value(generator, "output_file")
+            {
+                let output = dir.join(output).with_extension(Path::new(&default_output).extension().unwrap_or_default());
+                arguments.extend(["--output-file".to_string(), output.display().to_string()]);
+            }
+            arguments.extend(files.iter().map(|file| dir.join(file).display().to_string()));
+
+            status!("running: {}", arguments[1..].join(" "));
+            let command = Cli::try_parse_from(&arguments)
+                .map_err(|e| format!("Error in {}: {}", context, e))?
+                .command;
+            match command {
+                Some(Commands::Batch(_)) => return Err(format!("Error in {}: manifests cannot be nested", context).into()),
+                Some(command) => run(command)?,
+                None => return Err(format!("No generator given in {}", context).into()),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The strings of an array in the manifest, none if it is not given
+fn strings(item: Option<&Item>, key: &str, context: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let Some(item) = item else {
+        return Ok(Vec::new());
+    };
+    let error = || format!("'{}' in {} must be an array of strings", key, context);
+    item.as_array()
+        .ok_or_else(error)?
+        .iter()
+        .map(|value| value.as_str().map(str::to_string).ok_or_else(|| error().into()))
+        .collect()
+}
+
+/// Default value of an option of a generator, none if the generator does not have the option
+fn default_value(generator: &str, option: &str) -> Option<String> {
+    let command = Cli::command();
+    let argument = command
+        .find_subcommand(generator)?
+        .get_arguments()
+        .find(|argument| argument.get_id() == option)?;
+    argument
+        .get_default_values()
+        .first()
+        .map(|value| value.to_string_lossy().to_string())
+        .or(Some(String::new()))
+}
@@ -0,0 +1,540 @@
+//! The `browse` terminal browser: the loaded structure definitions in a list, the elements of the
+//! selected one as a tree to expand and collapse, and the details of the selected element. The
+//! terminal is put into raw mode once with termios, its size is read when it opens and when it is
+//! resized, and the screen is drawn with ANSI escape sequences.
+
+use crate::summary::status;
+use crate::utils::get_slice_after_last_occurrence;
+use crate::{BrowseArgs, Cli, ElementInfo, StructureDefTreeInfo, load_structure_definition_files, output, output_target, run};
+use clap::Parser;
+use std::collections::HashSet;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const HELP: &str = "↑↓ select  ←→ collapse/expand  Tab switch pane  e export  q quit";
+
+/// How long the rest of an escape sequence may take to arrive after the escape, in milliseconds
+const ESCAPE_TIMEOUT: i32 = 100;
+
+/// Set by SIGWINCH, when the terminal is resized
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, PartialEq)]
+enum Pane {
+    Profiles,
+    Elements,
+}
+
+enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Enter,
+    Tab,
+    Backspace,
+    Escape,
+    Char(char),
+    Interrupt,
+    /// the wait for a key was interrupted by a signal, e.g. the terminal was resized
+    Signal,
+    Other,
+}
+
+/// The terminal in raw mode on the alternate screen, restored when dropped
+struct Terminal {
+    /// the settings before
+    saved: libc::termios,
+}
+
+pub fn browse(args: &BrowseArgs) -> Result<(), Box<dyn std::error::Error>> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Err("browse needs a terminal".into());
+    }
+    let docs = load_structure_definition_files(&args.common)?;
+    if docs.is_empty() {
+        return Err("No structure definitions to browse".into());
+    }
+    let mut browser = Browser::new(&docs);
+    let terminal = Terminal::open()?;
+    let (mut rows, mut cols) = terminal_size();
+    loop {
+        if RESIZED.swap(false, Ordering::Relaxed) {
+            (rows, cols) = terminal_size();
+        }
+        browser.draw(rows, cols)?;
+        match read_key()? {
+            Key::Char('q') | Key::Interrupt => break,
+            Key::Char('e') => {
+                browser.message = String::new();
+                if let Some(command_line) = browser.prompt("export with: ", rows)? {
+                    browser.message = export(&terminal, args, browser.selected_element(), &command_line)?;
+                }
+            }
+            key => browser.handle(key, rows),
+        }
+    }
+    Ok(())
+}
+
+impl Terminal {
+    fn open() -> io::Result<Terminal> {
+        // SAFETY: tcgetattr fills in the termios it is given
+        let mut saved = unsafe { std::mem::zeroed::<libc::termios>() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut saved) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // without SA_RESTART, so that the wait for a key returns on a resize
+        let on_resize = on_resize as extern "C" fn(libc::c_int);
+        // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+        unsafe {
+            let mut action = std::mem::zeroed::<libc::sigaction>();
+            action.sa_sigaction = on_resize as libc::sighandler_t;
+            libc::sigaction(libc::SIGWINCH, &action, std::ptr::null_mut());
+        }
+        let terminal = Terminal { saved };
+        terminal.resume()?;
+        Ok(terminal)
+    }
+
+    /// Back to the screen and settings before, e.g. while a generator runs
+    fn suspend(&self) -> io::Result<()> {
+        print!("\x1b[?25h\x1b[?1049l");
+        io::stdout().flush()?;
+        set_attributes(&self.saved)
+    }
+
+    fn resume(&self) -> io::Result<()> {
+        let mut raw = self.saved;
+        // SAFETY: cfmakeraw only changes the flags of the termios it is given
+        unsafe { libc::cfmakeraw(&mut raw) };
+        set_attributes(&raw)?;
+        print!("\x1b[?1049h\x1b[?25l");
+        io::stdout().flush()
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.suspend();
+    }
+}
+
+extern "C" fn on_resize(_signal: libc::c_int) {
+    RESIZED.store(true, Ordering::Relaxed);
+}
+
+fn set_attributes(attributes: &libc::termios) -> io::Result<()> {
+    // SAFETY: the termios is one read by tcgetattr, or changed by cfmakeraw
+    if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, attributes) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Rows and columns of the terminal, 24 by 80 if it cannot tell
+fn terminal_size() -> (usize, usize) {
+    // SAFETY: TIOCGWINSZ fills in the winsize it is given
+    let mut size = unsafe { std::mem::zeroed::<libc::winsize>() };
+    let found = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    if found && size.ws_row > 0 && size.ws_col > 0 { (size.ws_row as usize, size.ws_col as usize) } else { (24, 80) }
+}
+
+/// The next byte typed, waiting for it at most `timeout` milliseconds, or without end for -1.
+/// None when none arrives in time or the wait is interrupted by a signal.
+fn read_byte(timeout: i32) -> io::Result<Option<u8>> {
+    let mut poll = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+    // SAFETY: one pollfd is given
+    match unsafe { libc::poll(&mut poll, 1, timeout) } {
+        0 => return Ok(None),
+        -1 => {
+            let error = io::Error::last_os_error();
+            return if error.kind() == io::ErrorKind::Interrupted { Ok(None) } else { Err(error) };
+        }
+        _ => {}
+    }
+    // read directly, as a buffered standard input would hold bytes the poll does not see
+    let mut byte = 0u8;
+    // SAFETY: one byte is read into one byte
+    match unsafe { libc::read(libc::STDIN_FILENO, (&mut byte as *mut u8).cast(), 1) } {
+        1 => Ok(Some(byte)),
+        0 => Err(io::ErrorKind::UnexpectedEof.into()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// A key press; the arrow and page keys arrive as escape sequences, e.g. ESC [ A for up
+fn read_key() -> io::Result<Key> {
+    let Some(byte) = read_byte(-1)? else {
+        return Ok(Key::Signal);
+    };
+    let next = || read_byte(ESCAPE_TIMEOUT);
+    let key = match byte {
+        0x1b => match next()? {
+            None => Key::Escape,
+            Some(b'[' | b'O') => match next()? {
+                Some(b'A') => Key::Up,
+                Some(b'B') => Key::Down,
+                Some(b'C') => Key::Right,
+                Some(b'D') => Key::Left,
+                Some(b'5') if next()? == Some(b'~') => Key::PageUp,
+                Some(b'6') if next()? == Some(b'~') => Key::PageDown,
+                _ => Key::Other,
+            },
+            Some(_) => Key::Other,
+        },
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        0x03 => Key::Interrupt,
+        byte if byte.is_ascii_graphic() || byte == b' ' => Key::Char(byte as char),
+        _ => Key::Other,
+    };
+    Ok(key)
+}
+
+/// Runs a generator on the subtree of the selected element, e.g. `table --force` as if given
+/// `fhir-generate table --force --subtree <element>` and the files and options browsed, on the
+/// screen before the browser. `--force`, `--output-dir` and `--stdout` apply to the export only.
+fn export(
+    terminal: &Terminal,
+    args: &BrowseArgs,
+    element: &ElementInfo,
+    command_line: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut arguments = vec!["fhir-generate".to_string()];
+    arguments.extend(command_line.split_whitespace().map(|argument| argument.to_string()));
+
+    terminal.suspend()?;
+    status!("running: {} --subtree {}", command_line, element.id);
+    let (target, force) = (output::target(), output::is_force());
+    let result = Cli::try_parse_from(&arguments)
+        .map_err(|e| e.to_string().into())
+        .and_then(|cli| export_with(cli, args, element));
+    output::set_target(target);
+    output::set_force(force);
+    let message = match result {
+        Ok(()) => format!("exported {} with: {}", element.id, command_line),
+        Err(e) => {
+            eprintln!("{}", e);
+            format!("export of {} failed", element.id)
+        }
+    };
+    status!("press a key to return to the browser");
+    terminal.resume()?;
+    while let Key::Signal = read_key()? {}
+    Ok(message)
+}
+
+fn export_with(mut cli: Cli, args: &BrowseArgs, element: &ElementInfo) -> Result<(), Box<dyn std::error::Error>> {
+    if cli.single_file.is_some() || cli.archive.is_some() || cli.summary_file.is_some() {
+        return Err("--single-file, --archive and --summary-file are not supported in an export".into());
+    }
+    let mut command = cli.command.take().ok_or("No generator given")?;
+    let common = command.common_mut().ok_or("Only generators of structure definitions can be exported to")?;
+    if let Some(option) = common.given_option() {
+        return Err(format!("Give {} to browse itself, the export is of the files browsed", option).into());
+    }
+    *common = args.common.clone();
+    common.subtree = Some(element.id.to_string());
+    output::set_force(output::is_force() || cli.force);
+    output::set_target(output_target(&cli));
+    run(command)
+}
+
+struct Browser<'a> {
+    docs: &'a [StructureDefTreeInfo],
+    focus: Pane,
+    profile: usize,
+    profile_scroll: usize,
+    /// the selected element of each structure definition, by node
+    selected: Vec<usize>,
+    /// the expanded elements of each structure definition, by node
+    expanded: Vec<HashSet<usize>>,
+    element_scroll: usize,
+    message: String,
+}
+
+impl<'a> Browser<'a> {
+    fn new(docs: &'a [StructureDefTreeInfo]) -> Browser<'a> {
+        // the roots start expanded, showing the top level elements
+        let roots = docs.iter().map(|doc| roots(doc).collect::<Vec<_>>()).collect::<Vec<_>>();
+        Browser {
+            docs,
+            focus: Pane::Profiles,
+            profile: 0,
+            profile_scroll: 0,
+            selected: roots.iter().map(|roots| roots.first().copied().unwrap_or_default()).collect(),
+            expanded: roots.into_iter().map(|roots| roots.into_iter().collect()).collect(),
+            element_scroll: 0,
+            message: String::new(),
+        }
+    }
+
+    fn doc(&self) -> &'a StructureDefTreeInfo {
+        &self.docs[self.profile]
+    }
+
+    fn selected_element(&self) -> &'a ElementInfo {
+        self.doc().element_tree.get_unchecked(self.selected[self.profile])
+    }
+
+    /// The elements shown in the tree, those below a collapsed element hidden
+    fn visible(&self) -> Vec<usize> {
+        let tree = &self.doc().element_tree;
+        let expanded = &self.expanded[self.profile];
+        let mut visible = Vec::<usize>::new();
+        let mut stack = roots(self.doc()).collect::<Vec<_>>();
+        stack.reverse();
+        while let Some(node) = stack.pop() {
+            visible.push(node);
+            if expanded.contains(&node) {
+                stack.extend(tree.children(node).iter().rev());
+            }
+        }
+        visible
+    }
+
+    fn handle(&mut self, key: Key, rows: usize) {
+        let page = rows.saturating_sub(2) * 2 / 3;
+        match (self.focus, key) {
+            (_, Key::Tab) => {
+                self.focus = if self.focus == Pane::Profiles { Pane::Elements } else { Pane::Profiles };
+            }
+            (Pane::Profiles, Key::Up | Key::Char('k')) => self.select_profile(self.profile.saturating_sub(1)),
+            (Pane::Profiles, Key::Down | Key::Char('j')) => self.select_profile(self.profile + 1),
+            (Pane::Profiles, Key::PageUp) => self.select_profile(self.profile.saturating_sub(page)),
+            (Pane::Profiles, Key::PageDown) => self.select_profile(self.profile + page),
+            (Pane::Profiles, Key::Right | Key::Enter) => self.focus = Pane::Elements,
+            (Pane::Elements, Key::Up | Key::Char('k')) => self.move_selection(-1),
+            (Pane::Elements, Key::Down | Key::Char('j')) => self.move_selection(1),
+            (Pane::Elements, Key::PageUp) => self.move_selection(-(page as isize)),
+            (Pane::Elements, Key::PageDown) => self.move_selection(page as isize),
+            (Pane::Elements, Key::Right) => {
+                let node = self.selected[self.profile];
+                let children = self.doc().element_tree.children(node);
+                if !self.expanded[self.profile].insert(node) && !children.is_empty() {
+                    self.selected[self.profile] = children[0];
+                }
+            }
+            (Pane::Elements, Key::Left) => {
+                let node = self.selected[self.profile];
+                if !self.expanded[self.profile].remove(&node) || self.doc().element_tree.children(node).is_empty() {
+                    match self.doc().element_tree.get_parent_of(Some(node)) {
+                        Some(parent) => self.selected[self.profile] = parent,
+                        // only the profile list is left of the root
+                        None => self.focus = Pane::Profiles,
+                    }
+                }
+            }
+            (Pane::Elements, Key::Enter) => {
+                let node = self.selected[self.profile];
+                if !self.expanded[self.profile].remove(&node) {
+                    self.expanded[self.profile].insert(node);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn select_profile(&mut self, profile: usize) {
+        self.profile = profile.min(self.docs.len() - 1);
+        self.element_scroll = 0;
+    }
+
+    fn move_selection(&mut self, by: isize) {
+        let visible = self.visible();
+        let row = visible.iter().position(|node| *node == self.selected[self.profile]).unwrap_or_default();
+        let row = row.saturating_add_signed(by).min(visible.len().saturating_sub(1));
+        if let Some(node) = visible.get(row) {
+            self.selected[self.profile] = *node;
+        }
+    }
+
+    /// Reads a line typed on the status line, none if it is left empty
+    fn prompt(&mut self, label: &str, rows: usize) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        loop {
+            print!("\x1b[{};1H\x1b[2K\x1b[?25h{}{}", rows, label, line);
+            io::stdout().flush()?;
+            match read_key()? {
+                Key::Enter => break,
+                Key::Interrupt | Key::Escape => {
+                    line.clear();
+                    break;
+                }
+                Key::Backspace => {
+                    line.pop();
+                }
+                Key::Char(c) => line.push(c),
+                _ => (),
+            }
+        }
+        print!("\x1b[?25l");
+        Ok(Some(line.trim().to_string()).filter(|line| !line.is_empty()))
+    }
+
+    fn draw(&mut self, rows: usize, cols: usize) -> io::Result<()> {
+        let height = rows.saturating_sub(2);
+        let left = (cols / 4).clamp(12, 40).min(cols / 2);
+        let right = cols.saturating_sub(left + 1);
+        let details_height = (height / 3).max(5).min(height.saturating_sub(2));
+        let tree_height = height.saturating_sub(details_height + 1);
+
+        let mut screen = String::new();
+        let title = format!(" fhir-generate browse: {} structure definitions", self.docs.len());
+        screen += &format!("\x1b[1;1H\x1b[7m{}\x1b[0m", fit(&title, cols));
+
+        self.profile_scroll = scroll(self.profile_scroll, self.profile, height);
+        let visible = self.visible();
+        let selected_row = visible.iter().position(|node| *node == self.selected[self.profile]).unwrap_or_default();
+        self.element_scroll = scroll(self.element_scroll, selected_row, tree_height);
+        let tree_lines = visible
+            .iter()
+            .enumerate()
+            .skip(self.element_scroll)
+            .take(tree_height)
+            .map(|(row, node)| {
+                let line = fit(&self.tree_line(*node), right);
+                highlight(line, row == selected_row, self.focus == Pane::Elements)
+            })
+            .collect::<Vec<_>>();
+        let details = self.details(right);
+
+        for row in 0..height {
+            let profile = self.docs.get(self.profile_scroll + row).map(|doc| {
+                let line = fit(&format!(" {}", doc.id), left);
+                highlight(line, self.profile_scroll + row == self.profile, self.focus == Pane::Profiles)
+            });
+            screen += &format!("\x1b[{};1H{}│", row + 2, profile.unwrap_or_else(|| fit("", left)));
+            let line = if row < tree_height {
+                tree_lines.get(row).cloned().unwrap_or_else(|| fit("", right))
+            } else if row == tree_height {
+                "─".repeat(right)
+            } else {
+                details.get(row - tree_height - 1).map(|line| fit(line, right)).unwrap_or_else(|| fit("", right))
+            };
+            screen += &line;
+        }
+        let status = if self.message.is_empty() { HELP } else { &self.message };
+        screen += &format!("\x1b[{};1H\x1b[2K{}", rows, fit(status, cols));
+        print!("{}", screen);
+        io::stdout().flush()
+    }
+
+    /// An element in the tree, indented by its depth, e.g. `  ▸ name 0..* HumanName`
+    fn tree_line(&self, node: usize) -> String {
+        let tree = &self.doc().element_tree;
+        let element = tree.get_unchecked(node);
+        let marker = if tree.children(node).is_empty() {
+            "  "
+        } else if self.expanded[self.profile].contains(&node) {
+            "▾ "
+        } else {
+            "▸ "
+        };
+        let name = get_slice_after_last_occurrence(&element.id, '.').unwrap_or(element.id.to_string());
+        let mut line = format!("{}{}{} {}..{}", "  ".repeat(element.depth), marker, name, element.min, element.max);
+        if !element.datatype.is_empty() {
+            line = format!("{} {}", line, element.datatype.join(" | "));
+        }
+        if element.must_support {
+            line += " (MS)";
+        }
+        line
+    }
+
+    /// The definition, cardinality, types, binding and constraints of the selected element, wrapped
+    fn details(&self, width: usize) -> Vec<String> {
+        let element = self.selected_element();
+        let mut lines = vec![element.id.to_string()];
+        let mut add = |label: &str, text: &str| {
+            if !text.is_empty() {
+                lines.extend(wrap(&format!("{}: {}", label, text), width));
+            }
+        };
+        add("Short", &element.short);
+        add("Cardinality", &format!("{}..{}", element.min, element.max));
+        add("Types", &element.datatype.join(", "));
+        add("Definition", &element.definition);
+        let flags = [(element.must_support, "must support"), (element.is_modifier, "modifier"), (element.is_summary, "summary")]
+            .iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, flag)| *flag)
+            .collect::<Vec<_>>();
+        add("Flags", &flags.join(", "));
+        if let Some(strength) = &element.binding_strength {
+            let value_set = match (&element.value_set_title, &element.value_set) {
+                (Some(title), Some(url)) => format!("{} ({})", title, url),
+                (None, Some(url)) => url.to_string(),
+                _ => String::new(),
+            };
+            let binding = [strength.as_ref(), &value_set, element.binding.as_deref().unwrap_or_default()]
+                .into_iter()
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>();
+            add("Binding", &binding.join(", "));
+        }
+        for (key, severity, human) in element.constraints.iter() {
+            add("Constraint", &format!("{} ({}) {}", key, severity, human));
+        }
+        lines
+    }
+}
+
+/// The elements without a parent, normally only the root
+fn roots(doc: &StructureDefTreeInfo) -> impl Iterator<Item = usize> + '_ {
+    let tree = &doc.element_tree;
+    tree.iter().map(|(node, _)| node).filter(|node| tree.get_parent_of(Some(*node)).is_none())
+}
+
+/// The first row shown of a list, so that the selected row is in view
+fn scroll(scroll: usize, selected: usize, height: usize) -> usize {
+    if selected < scroll {
+        selected
+    } else if height > 0 && selected >= scroll + height {
+        selected + 1 - height
+    } else {
+        scroll
+    }
+}
+
+/// A line cut or padded to the width, cut lines ending with an ellipsis
+fn fit(text: &str, width: usize) -> String {
+    let length = text.chars().count();
+    if length > width {
+        text.chars().take(width.saturating_sub(1)).collect::<String>() + "…"
+    } else {
+        format!("{}{}", text, " ".repeat(width - length))
+    }
+}
+
+/// The selected line in reverse video when its pane has the focus, and underlined otherwise
+fn highlight(line: String, selected: bool, focused: bool) -> String {
+    match (selected, focused) {
+        (true, true) => format!("\x1b[7m{}\x1b[0m", line),
+        (true, false) => format!("\x1b[4m{}\x1b[0m", line),
+        _ => line,
+    }
+}
+
+/// A text broken into lines of the width at spaces
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::<String>::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line += word;
+        }
+        lines.push(line);
+    }
+    lines
+}
@@ -0,0 +1,37 @@
+use crate::summary::status;
+use clap::Command;
+use std::{
+    fs::{File, create_dir_all},
+    io::BufWriter,
+    path::Path,
+};
+
+/// Writes a man page for the tool and for each visible subcommand, e.g. fhir-generate.1 and
+/// fhir-generate-table.1
+pub fn write_man_pages(mut command: Command, dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    create_dir_all(dir)?;
+    command.build();
+    let name = command.get_name().to_string();
+    status!("writing: {}.1", name);
+    let mut writer = BufWriter::new(File::create(dir.join(format!("{}.1", name)))?);
+    clap_mangen::Man::new(command.clone()).render(&mut writer)?;
+    for subcommand in command.get_subcommands().filter(|s| !s.is_hide_set() && s.get_name() != "help") {
+        let page = format!("{}-{}", name, subcommand.get_name());
+        status!("writing: {}.1", page);
+        let mut writer = BufWriter::new(File::create(dir.join(format!("{}.1", page)))?);
+        // the page name is also shown in the synopsis
+        clap_mangen::Man::new(subcommand.clone().name(page.clone()).bin_name(page)).render(&mut writer)?;
+    }
+    Ok(())
+}
+
+/// Prints the long help of the tool followed by that of each visible subcommand
+pub fn print_help_all(mut command: Command) {
+    command.build();
+    println!("{}", command.render_long_help());
+    for subcommand in command.get_subcommands().filter(|s| !s.is_hide_set() && s.get_name() != "help") {
+        let title = format!("{} {}", command.get_name(), subcommand.get_name());
+        println!("\n{}\n{}\n", title, "=".repeat(title.len()));
+        println!("{}", subcommand.clone().render_long_help());
+    }
+}
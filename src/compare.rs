@@ -0,0 +1,79 @@
+use crate::output::create_output_file;
+use crate::summary::status;
+use crate::utils::{TextMode, reduce_datatypes, sanitize_markdown};
+use crate::{CompareArgs, ElementInfo, StructureDefTreeInfo};
+use std::collections::HashMap;
+use std::io::{BufWriter, Write};
+
+/// Element path below the root, so that profiles with different ids line up, e.g. "name.given"
+fn relative_path<'a>(doc: &StructureDefTreeInfo, element: &'a ElementInfo) -> Option<&'a str> {
+    element.id.strip_prefix(&doc.id)?.strip_prefix('.')
+}
+
+fn compare_cell(element: &ElementInfo) -> String {
+    let mut cell = format!("{}..{} {}", element.min, element.max, reduce_datatypes(&element.datatype));
+    if let Some(binding) = element.value_set_title.as_ref().or(element.binding.as_ref()) {
+        cell.push_str(&format!(
+            "<br/>{} ({})",
+            sanitize_markdown(binding, TextMode::Escape, None),
+            element.binding_strength.as_deref().unwrap_or("unknown")
+        ));
+    }
+    cell
+}
+
+/// Writes a markdown matrix with a row per element path and a column per profile, with the rows
+/// where the profiles differ in bold
+pub fn generate_comparison(args: &CompareArgs, docs: &[StructureDefTreeInfo]) -> Result<(), Box<dyn std::error::Error>> {
+    // paths in order of appearance, paths only found in later profiles are placed after their predecessor
+    let mut paths = Vec::<String>::new();
+    let mut cells = HashMap::<(String, usize), String>::new();
+    for (doc_num, doc) in docs.iter().enumerate() {
+        status!("processing: {}", doc.id);
+        let mut position = 0;
+        for (_idx, element) in doc.element_tree.iter() {
+            let Some(path) = relative_path(doc, element) else {
+                continue;
+            };
+            if let Some(existing) = paths.iter().position(|p| p == path) {
+                position = existing + 1;
+            } else {
+                paths.insert(position, path.to_string());
+                position += 1;
+            }
+            cells.insert((path.to_string(), doc_num), compare_cell(element));
+        }
+    }
+
+    let mut writer = BufWriter::new(create_output_file(&args.output_file)?);
+    write!(writer, "| Element |")?;
+    for doc in docs.iter() {
+        write!(writer, " {} |", doc.id)?;
+    }
+    writeln!(writer)?;
+    writeln!(writer, "|---|{}", "---|".repeat(docs.len()))?;
+
+    let mut differences = 0;
+    for path in paths.iter() {
+        let row = (0..docs.len())
+            .map(|doc_num| cells.get(&(path.clone(), doc_num)))
+            .collect::<Vec<_>>();
+        let differs = row.iter().any(|cell| *cell != row[0]);
+        if differs {
+            differences += 1;
+        } else if args.differences_only {
+            continue;
+        }
+        let highlight = |text: &str| if differs { format!("**{}**", text) } else { text.to_string() };
+        write!(writer, "| {} |", highlight(path))?;
+        for cell in row.iter() {
+            match cell {
+                Some(cell) => write!(writer, " {} |", highlight(cell))?,
+                None => write!(writer, " — |")?,
+            }
+        }
+        writeln!(writer)?;
+    }
+    status!("{} of {} element paths differ", differences, paths.len());
+    Ok(())
+}
@@ -0,0 +1,115 @@
+use crate::output::create_output_file;
+use crate::summary::status;
+use crate::utils::{TextMode, escape_csv, items, sanitize_markdown, text};
+use crate::{ConceptMapArgs, ConceptMapFormat, load_resources};
+use serde_json::Value;
+use std::io::{BufWriter, Write};
+
+/// A row of a mapping table: the source code and display, the relationship (the equivalence before
+/// R5), the target code and display and the comment. Source codes without a target have a row with
+/// the relationship "not mapped".
+type Mapping<'a> = [&'a str; 6];
+
+/// Writes a table of the mappings of each ConceptMap, by default <id>_conceptmap.md with a section
+/// per source and target system, or <id>_conceptmap.csv with the systems as columns
+pub fn generate_concept_maps(args: &ConceptMapArgs) -> Result<(), Box<dyn std::error::Error>> {
+    for (_file, map) in load_resources(&args.common, &["ConceptMap"]).iter() {
+        let id = map["id"].as_str().or(map["name"].as_str()).unwrap_or("ConceptMap");
+        status!("processing: {}", id);
+        let name = args.output_pattern.replace("{id}", id);
+        match args.format {
+            ConceptMapFormat::Markdown => {
+                let mut writer = BufWriter::new(create_output_file(format!("{}.md", name))?);
+                write_markdown(&mut writer, map, id)?;
+            }
+            ConceptMapFormat::Csv => {
+                let mut writer = BufWriter::new(create_output_file(format!("{}.csv", name))?);
+                write_csv(&mut writer, map)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The first of the members given, e.g. sourceUri or sourceCanonical before R5 and sourceScopeUri
+/// or sourceScopeCanonical from R5
+fn first_of<'a>(value: &'a Value, keys: &[&str]) -> &'a str {
+    keys.iter().find_map(|key| value[*key].as_str()).unwrap_or_default()
+}
+
+/// The mappings of a group in the order of its elements
+fn mappings(group: &Value) -> Vec<Mapping<'_>> {
+    let mut rows = Vec::<Mapping>::new();
+    for element in items(&group["element"]) {
+        let (code, display) = (text(element, "code"), text(element, "display"));
+        let mut targets = items(&element["target"]).peekable();
+        if targets.peek().is_none() {
+            rows.push([code, display, "not mapped", "", "", ""]);
+        }
+        for target in targets {
+            let relationship = target["relationship"].as_str().or(target["equivalence"].as_str()).unwrap_or_default();
+            rows.push([code, display, relationship, text(target, "code"), text(target, "display"), text(target, "comment")]);
+        }
+    }
+    rows
+}
+
+/// What a group does with source codes that are not listed, e.g. "use the code as is"
+fn unmapped_text(group: &Value) -> Option<String> {
+    let unmapped = &group["unmapped"];
+    let text = match unmapped["mode"].as_str()? {
+        "provided" | "use-source-code" => "use the source code as is".to_string(),
+        "fixed" => format!("map to {} {}", text(unmapped, "code"), text(unmapped, "display")).trim_end().to_string(),
+        "other-map" => format!("use {}", first_of(unmapped, &["url", "otherMap"])),
+        mode => mode.to_string(),
+    };
+    Some(text)
+}
+
+fn write_markdown<W: Write>(writer: &mut W, map: &Value, id: &str) -> std::io::Result<()> {
+    let title = map["title"].as_str().or(map["name"].as_str()).unwrap_or(id);
+    writeln!(writer, "# {}\n", sanitize_markdown(title, TextMode::Escape, None))?;
+    if let Some(url) = map["url"].as_str() {
+        writeln!(writer, "{}\n", url)?;
+    }
+    if let Some(description) = map["description"].as_str() {
+        writeln!(writer, "{}\n", description.trim())?;
+    }
+    let source = first_of(map, &["sourceScopeUri", "sourceScopeCanonical", "sourceUri", "sourceCanonical"]);
+    let target = first_of(map, &["targetScopeUri", "targetScopeCanonical", "targetUri", "targetCanonical"]);
+    if !source.is_empty() || !target.is_empty() {
+        writeln!(writer, "From {} to {}\n", or_any(source), or_any(target))?;
+    }
+    for group in items(&map["group"]) {
+        writeln!(writer, "## {} → {}\n", or_any(text(group, "source")), or_any(text(group, "target")))?;
+        writeln!(
+            writer,
+            "| Source code | Source display | Relationship | Target code | Target display | Comment |\n|---|---|---|---|---|---|"
+        )?;
+        for row in mappings(group) {
+            let cells = row.map(|cell| sanitize_markdown(cell, TextMode::Escape, None));
+            writeln!(writer, "| {} |", cells.join(" | "))?;
+        }
+        if let Some(unmapped) = unmapped_text(group) {
+            writeln!(writer, "\nOther source codes: {}", unmapped)?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+fn write_csv<W: Write>(writer: &mut W, map: &Value) -> std::io::Result<()> {
+    writeln!(writer, "source system,source code,source display,relationship,target system,target code,target display,comment")?;
+    for group in items(&map["group"]) {
+        for [code, display, relationship, target_code, target_display, comment] in mappings(group) {
+            let cells = [text(group, "source"), code, display, relationship, text(group, "target"), target_code, target_display, comment];
+            writeln!(writer, "{}", cells.map(escape_csv).join(","))?;
+        }
+    }
+    Ok(())
+}
+
+/// A system or value set, or "(any)" when not given
+fn or_any(system: &str) -> &str {
+    if system.is_empty() { "(any)" } else { system }
+}
@@ -0,0 +1,96 @@
+use crate::StructureDefTreeInfo;
+use crate::diagnostics::{self, Diagnostic};
+use crate::doc_index::DocIndex;
+use std::collections::{HashMap, VecDeque, hash_map::Entry};
+use std::rc::Rc;
+
+/// Indices of the loaded structure definitions used as type or reference target by each one
+fn dependencies(docs: &[StructureDefTreeInfo]) -> Vec<Vec<usize>> {
+    let index = DocIndex::new(docs);
+    docs.iter()
+        .map(|doc| {
+            let mut targets = doc
+                .element_tree
+                .iter()
+                .flat_map(|(_idx, element)| element.datatype.iter())
+                .filter_map(|datatype| index.position(datatype))
+                .collect::<Vec<_>>();
+            targets.sort();
+            targets.dedup();
+            targets
+        })
+        .collect()
+}
+
+/// Shortest path from `start` back to itself, as indices starting and ending with `start`
+fn shortest_cycle(start: usize, edges: &[Vec<usize>]) -> Option<Vec<usize>> {
+    let mut previous = HashMap::<usize, usize>::new();
+    let mut queue = VecDeque::from([start]);
+    while let Some(node) = queue.pop_front() {
+        for &next in edges[node].iter() {
+            if next == start {
+                let mut path = vec![node];
+                while let Some(&p) = previous.get(&path[path.len() - 1]) {
+                    path.push(p);
+                }
+                path.reverse();
+                path.push(start);
+                return Some(path);
+            }
+            if let Entry::Vacant(entry) = previous.entry(next) {
+                entry.insert(node);
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+fn same_members(a: &[usize], b: &[usize]) -> bool {
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    a.sort();
+    a.dedup();
+    b.sort();
+    b.dedup();
+    a == b
+}
+
+/// Cycles among the structure definitions through element types and references, each given as the
+/// ids along the cycle with the first one repeated at the end, e.g. ["A", "B", "A"] or ["A", "A"]
+pub fn find_cycles(docs: &[StructureDefTreeInfo]) -> Vec<Vec<String>> {
+    let edges = dependencies(docs);
+    let mut cycles = Vec::<Vec<usize>>::new();
+    for start in 0..docs.len() {
+        // each cycle is reported once, from its first member
+        if let Some(cycle) = shortest_cycle(start, &edges)
+            && !cycles.iter().any(|c| same_members(c, &cycle))
+        {
+            cycles.push(cycle);
+        }
+    }
+    cycles
+        .iter()
+        .map(|cycle| cycle.iter().map(|&i| docs[i].id.clone()).collect())
+        .collect()
+}
+
+/// Reports the circular references among the structure definitions as warnings on the elements
+/// closing them
+pub fn report_cycles(docs: &[StructureDefTreeInfo]) {
+    let index = DocIndex::new(docs);
+    for cycle in find_cycles(docs).iter() {
+        for pair in cycle.windows(2) {
+            if let Some(doc) = index.get(&pair[0]) {
+                let closes = |datatype: &Rc<str>| index.get(datatype).is_some_and(|target| target.id == pair[1]);
+                for (_idx, element) in doc.element_tree.iter().filter(|(_, e)| e.datatype.iter().any(closes)) {
+                    diagnostics::emit(
+                        Diagnostic::warning("circular-reference", format!("Circular reference {}", cycle.join(" -> ")))
+                            .at(&element.id)
+                            .in_file(&doc.source),
+                    );
+                }
+            }
+        }
+    }
+}
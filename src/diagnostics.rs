@@ -0,0 +1,101 @@
+use crate::summary::{self, status};
+use clap::ValueEnum;
+use serde_json::json;
+use std::{fmt, path::Path, sync::OnceLock};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Default)]
+pub enum DiagnosticsFormat {
+    /// Messages on standard output
+    #[default]
+    Text,
+    /// One JSON object per line on standard error, with file, element id, severity and rule code
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    /// The file is skipped
+    Error,
+    Warning,
+}
+
+/// A finding about an input file, e.g. a missing short description, with a stable rule code
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub file: String,
+    pub element: Option<String>,
+    pub severity: Severity,
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(rule: &'static str, message: impl Into<String>) -> Self {
+        Diagnostic {
+            file: String::new(),
+            element: None,
+            severity: Severity::Error,
+            rule,
+            message: message.into(),
+        }
+    }
+
+    pub fn warning(rule: &'static str, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(rule, message)
+        }
+    }
+
+    pub fn at(mut self, element: &str) -> Self {
+        self.element = Some(element.to_string());
+        self
+    }
+
+    pub fn in_file(mut self, file: &Path) -> Self {
+        self.file = file.display().to_string();
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(element) = &self.element {
+            write!(f, " at {}", element)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+static FORMAT: OnceLock<DiagnosticsFormat> = OnceLock::new();
+
+pub fn set_format(format: DiagnosticsFormat) {
+    let _ = FORMAT.set(format);
+}
+
+/// Reports a diagnostic in the chosen format and counts it in the summary of the run
+pub fn emit(diagnostic: Diagnostic) {
+    match FORMAT.get().copied().unwrap_or_default() {
+        DiagnosticsFormat::Text => match diagnostic.severity {
+            Severity::Error => status!("Error reading file '{}': {}", diagnostic.file, diagnostic),
+            Severity::Warning => status!("Warning for '{}': {}", diagnostic.file, diagnostic),
+        },
+        DiagnosticsFormat::Json => eprintln!(
+            "{}",
+            json!({
+                "file": diagnostic.file,
+                "element": diagnostic.element,
+                "severity": if diagnostic.severity == Severity::Error { "error" } else { "warning" },
+                "rule": diagnostic.rule,
+                "message": diagnostic.message,
+            })
+        ),
+    }
+    match diagnostic.severity {
+        Severity::Error => summary::file_skipped(Path::new(&diagnostic.file), &diagnostic.to_string()),
+        Severity::Warning => summary::warning(),
+    }
+}
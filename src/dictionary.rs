@@ -0,0 +1,89 @@
+use crate::doc_index::DocIndex;
+use crate::site::class_diagram;
+use crate::output::create_output_file;
+use crate::summary::status;
+use crate::utils::{TextMode, escape_xml, get_slice_after_last_occurrence, reduce_datatypes, sanitize_markdown};
+use crate::{DictionaryArgs, PlantUmlArgs, StructureDefTreeInfo, TableArgs, TableContext, default_args, value_set_name, write_table};
+use std::collections::BTreeMap;
+use std::io::{BufWriter, Write};
+
+/// Writes one markdown document with an index of the structure definitions, a table and class
+/// diagram per structure definition, an appendix of the bindings and an alphabetical element index
+pub fn generate_dictionary(args: &DictionaryArgs, docs: &[StructureDefTreeInfo]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut table_args = default_args::<TableArgs>();
+    table_args.common.lang = args.common.lang.clone();
+    // datatypes link to the sections of this document
+    table_args.link_pattern = "#{id}".to_string();
+    table_args.toc = true;
+    let plantuml_args = default_args::<PlantUmlArgs>();
+    let ctx = TableContext::new(&table_args, docs)?;
+    let index = DocIndex::new(docs);
+
+    let mut writer = BufWriter::new(create_output_file(&args.output_file)?);
+    writeln!(writer, "# {}\n", args.title)?;
+
+    writeln!(writer, "## Contents\n")?;
+    for doc in docs.iter() {
+        let short = doc.element_tree.iter().next().map(|(_, root)| root.short.as_ref()).unwrap_or_default();
+        writeln!(writer, "- [{}](#{}) {}", doc.id, doc.id, sanitize_markdown(short, TextMode::Escape, None))?;
+    }
+    writeln!(writer, "- [Bindings](#bindings)\n- [Element index](#element-index)\n")?;
+
+    for (doc_num, doc) in docs.iter().enumerate() {
+        status!("processing: {}", doc.id);
+        writeln!(writer, "<a id=\"{}\"></a>\n", escape_xml(&doc.id))?;
+        let diagram = class_diagram(doc, &index, &plantuml_args)?;
+        if args.link_diagrams {
+            let diagram_file = format!("{}.plantuml", doc.id);
+            let mut diagram_writer = BufWriter::new(create_output_file(args.output_file.with_file_name(&diagram_file))?);
+            write!(diagram_writer, "{}", diagram)?;
+            writeln!(writer, "[Class diagram]({})\n", diagram_file)?;
+        } else {
+            writeln!(writer, "```plantuml\n{}```\n", diagram)?;
+        }
+        write_table(&mut writer, doc, doc_num, &ctx)?;
+        writeln!(writer)?;
+    }
+
+    writeln!(writer, "<a id=\"bindings\"></a>\n\n## Bindings\n")?;
+    writeln!(writer, "| Element | Data type | Value set | Strength |\n|---|---|---|---|")?;
+    for doc in docs.iter() {
+        for (_idx, element) in doc.element_tree.iter().filter(|(_, e)| &*e.max != "0") {
+            if let Some(value_set) = value_set_name(element) {
+                writeln!(
+                    writer,
+                    "| [{}](#{}) | {} | {} | {} |",
+                    element.id,
+                    doc.id,
+                    reduce_datatypes(&element.datatype),
+                    sanitize_markdown(&value_set, TextMode::Escape, None),
+                    element.binding_strength.as_deref().unwrap_or("unknown")
+                )?;
+            }
+        }
+    }
+
+    // element name to the structure definitions using it, sorted case-insensitively
+    let mut index = BTreeMap::<String, BTreeMap<String, Vec<String>>>::new();
+    for doc in docs.iter() {
+        for (_idx, element) in doc.element_tree.iter().filter(|(_, e)| e.id.contains('.') && &*e.max != "0") {
+            let name = get_slice_after_last_occurrence(&element.id, '.').unwrap_or_default();
+            index
+                .entry(name.to_lowercase())
+                .or_default()
+                .entry(name)
+                .or_default()
+                .push(doc.id.clone());
+        }
+    }
+    writeln!(writer, "\n<a id=\"element-index\"></a>\n\n## Element index\n")?;
+    for names in index.values() {
+        for (name, users) in names.iter() {
+            let mut users = users.clone();
+            users.dedup();
+            let links = users.iter().map(|id| format!("[{}](#{})", id, id)).collect::<Vec<_>>();
+            writeln!(writer, "- **{}**: {}", name, links.join(", "))?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,247 @@
+use crate::diagnostics::{self, Diagnostic};
+use crate::output::create_output_file;
+use crate::fsh;
+use crate::resource_reader::{read_resource, read_resource_from_slice};
+use crate::summary::status;
+use crate::utils::{TextMode, sanitize_markdown};
+use crate::{CommonArgs, DiffArgs, ElementInfo, StructureDefTreeInfo, load_structure_definition_files, structure_definitions_of};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{create_dir_all, write};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A change of an element: what changed, and the value before and after, e.g. ("cardinality",
+/// "0..1", "1..1")
+type Change = (&'static str, String, String);
+
+/// Writes a markdown report of the changes in the model between a git revision and the working
+/// tree: the structure definitions added and removed, and per changed one a table of the elements
+/// added, removed or changed in cardinality, types, binding, flags, fixed value or descriptions.
+/// The structure definitions at the revision are the input files as they were, and those removed
+/// since from the folders of the input files, with the FSH files and SUSHI projects among them
+/// built as they were.
+pub fn generate_diff(args: &DiffArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let docs = load_structure_definition_files(&args.common)?;
+    let (files, resources): (Vec<_>, Vec<_>) = resources_at(&args.git, &args.common)?.into_iter().unzip();
+    let old_docs = structure_definitions_of(&args.common, &files.iter().zip(resources).collect::<Vec<_>>())?;
+
+    let mut writer = BufWriter::new(create_output_file(&args.output_file)?);
+    writeln!(writer, "# Model changes since {}\n", args.git)?;
+    let added = docs.iter().filter(|doc| counterpart(doc, &old_docs).is_none()).collect::<Vec<_>>();
+    let removed = old_docs.iter().filter(|doc| counterpart(doc, &docs).is_none()).collect::<Vec<_>>();
+    write_list(&mut writer, "Added structure definitions", &added)?;
+    write_list(&mut writer, "Removed structure definitions", &removed)?;
+
+    let mut changed = added.len() + removed.len();
+    for doc in docs.iter() {
+        let Some(old) = counterpart(doc, &old_docs) else {
+            continue;
+        };
+        status!("processing: {}", doc.id);
+        let changes = structure_changes(old, doc);
+        if changes.is_empty() {
+            continue;
+        }
+        changed += 1;
+        writeln!(writer, "## {}\n\n| Element | Change | Before | After |\n|---|---|---|---|", doc.id)?;
+        for (element, (change, before, after)) in changes {
+            writeln!(
+                writer,
+                "| {} | {} | {} | {} |",
+                element,
+                change,
+                sanitize_markdown(&before, TextMode::Escape, None),
+                sanitize_markdown(&after, TextMode::Escape, None)
+            )?;
+        }
+        writeln!(writer)?;
+    }
+    if changed == 0 {
+        writeln!(writer, "No changes in the model.")?;
+    }
+    Ok(())
+}
+
+/// The same structure definition among others, by canonical URL or else by id
+fn counterpart<'a>(doc: &StructureDefTreeInfo, docs: &'a [StructureDefTreeInfo]) -> Option<&'a StructureDefTreeInfo> {
+    docs.iter()
+        .find(|other| !doc.url.is_empty() && other.url == doc.url)
+        .or_else(|| docs.iter().find(|other| other.id == doc.id))
+}
+
+fn write_list<W: Write>(writer: &mut W, title: &str, docs: &[&StructureDefTreeInfo]) -> std::io::Result<()> {
+    if docs.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer, "## {}\n", title)?;
+    for doc in docs {
+        writeln!(writer, "- {}", doc.id)?;
+    }
+    writeln!(writer)
+}
+
+/// The changes of a structure definition by element id, in the order of the elements now and then
+/// of those removed
+fn structure_changes<'a>(old: &'a StructureDefTreeInfo, new: &'a StructureDefTreeInfo) -> Vec<(String, Change)> {
+    let mut changes = Vec::<(String, Change)>::new();
+    if old.base != new.base {
+        changes.push((new.id.clone(), ("base", old.base.clone(), new.base.clone())));
+    }
+    let by_id = |doc: &'a StructureDefTreeInfo| {
+        doc.element_tree.iter().map(|(_, element)| (element.id.as_ref(), element)).collect::<HashMap<_, _>>()
+    };
+    let (old_elements, new_elements) = (by_id(old), by_id(new));
+    for (_idx, element) in new.element_tree.iter() {
+        match old_elements.get(element.id.as_ref()) {
+            Some(before) => {
+                changes.extend(element_changes(before, element).into_iter().map(|change| (element.id.to_string(), change)));
+            }
+            None => changes.push((element.id.to_string(), ("added", String::new(), summary(element)))),
+        }
+    }
+    for (_idx, element) in old.element_tree.iter() {
+        if !new_elements.contains_key(element.id.as_ref()) {
+            changes.push((element.id.to_string(), ("removed", summary(element), String::new())));
+        }
+    }
+    changes
+}
+
+/// Cardinality and types of an element, e.g. `0..* HumanName`
+fn summary(element: &ElementInfo) -> String {
+    format!("{}..{} {}", element.min, element.max, element.datatype.join(", ")).trim_end().to_string()
+}
+
+fn element_changes(old: &ElementInfo, new: &ElementInfo) -> Vec<Change> {
+    let mut changes = Vec::<Change>::new();
+    let mut compare = |change: &'static str, before: String, after: String| {
+        if before != after {
+            changes.push((change, before, after));
+        }
+    };
+    compare("cardinality", format!("{}..{}", old.min, old.max), format!("{}..{}", new.min, new.max));
+    compare("types", old.datatype.join(", "), new.datatype.join(", "));
+    let binding = |element: &ElementInfo| {
+        [element.binding_strength.as_deref(), element.value_set.as_deref()].into_iter().flatten().collect::<Vec<_>>().join(" ")
+    };
+    compare("binding", binding(old), binding(new));
+    let fixed = |element: &ElementInfo| element.fixed_value.as_ref().map(|(_, value)| value.clone()).unwrap_or_default();
+    compare("fixed value", fixed(old), fixed(new));
+    compare("must support", old.must_support.to_string(), new.must_support.to_string());
+    compare("modifier", old.is_modifier.to_string(), new.is_modifier.to_string());
+    compare("summary", old.is_summary.to_string(), new.is_summary.to_string());
+    compare("short description", old.short.to_string(), new.short.to_string());
+    compare("definition", old.definition.to_string(), new.definition.to_string());
+    changes
+}
+
+/// The folder to run git in for a file
+fn dir_of(file: &Path) -> PathBuf {
+    match file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Error running git: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(output.stdout)
+}
+
+/// The JSON and FSH input files as they were at the revision, and those removed since from their
+/// folders, named `<rev>:<file>`, with the FSH files and SUSHI project folders built. Files and
+/// projects added since are not among them.
+fn resources_at(rev: &str, common: &CommonArgs) -> Result<Vec<(PathBuf, Value)>, Box<dyn std::error::Error>> {
+    let files = &common.files;
+    let is_input = |file: &Path| file.extension().is_some_and(|extension| extension == "json" || extension == "fsh");
+    let mut files_at_rev = files.iter().filter(|file| is_input(file)).cloned().collect::<Vec<_>>();
+    for dir in files.iter().map(|file| dir_of(file)).collect::<BTreeSet<_>>() {
+        git(&dir, &["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", rev)]).map_err(|e| {
+            let reason = if e.is_empty() { "unknown revision".to_string() } else { e };
+            format!("Error finding git revision '{}' in '{}': {}", rev, dir.display(), reason)
+        })?;
+        let names = git(&dir, &["ls-tree", "--name-only", rev, "--", "."])?;
+        for name in String::from_utf8_lossy(&names).lines() {
+            let file = dir.join(name);
+            if is_input(&file) && !file.exists() {
+                files_at_rev.push(file);
+            }
+        }
+    }
+
+    // the FSH files and projects are copied out of the revision and built from the copies
+    let temp_dir = fsh::temp_dir();
+    let mut copies = Vec::<(PathBuf, PathBuf)>::new();
+    let mut resources = Vec::<(PathBuf, Value)>::new();
+    for file in files_at_rev {
+        let name = file.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        // files added since are not at the revision
+        let Ok(bytes) = git(&dir_of(&file), &["show", &format!("{}:./{}", rev, name)]) else {
+            continue;
+        };
+        let file = PathBuf::from(format!("{}:{}", rev, file.display()));
+        if name.ends_with(".fsh") {
+            // files of the same name in different folders are kept apart
+            let copy = temp_dir.0.join(format!("rev-{}", copies.len())).join(&name);
+            copy_out(&copy, &bytes)?;
+            copies.push((copy, file));
+            continue;
+        }
+        match read_resource_from_slice(&bytes) {
+            Ok(resource) => resources.push((file, resource)),
+            Err(e) => diagnostics::emit(Diagnostic::error("unreadable-file", e.to_string()).in_file(&file)),
+        }
+    }
+    for project in files.iter().filter(|file| file.is_dir()) {
+        let names = git(project, &["ls-tree", "-r", "--name-only", rev, "--", "sushi-config.yaml", "input"])?;
+        let names = String::from_utf8_lossy(&names);
+        // projects added since are not at the revision
+        if !names.lines().any(|name| name == "sushi-config.yaml") {
+            continue;
+        }
+        let copy = temp_dir.0.join(format!("rev-{}", copies.len()));
+        for name in names.lines() {
+            copy_out(&copy.join(name), &git(project, &["show", &format!("{}:./{}", rev, name)])?)?;
+        }
+        copies.push((copy, PathBuf::from(format!("{}:{}", rev, project.display()))));
+    }
+
+    // a copy, or a file in a copied project, is named after the file at the revision
+    let rename = |file: &Path| {
+        copies
+            .iter()
+            .find_map(|(copy, original)| match file.strip_prefix(copy) {
+                Ok(rest) if rest.as_os_str().is_empty() => Some(original.clone()),
+                Ok(rest) => Some(original.join(rest)),
+                Err(_) => None,
+            })
+            .unwrap_or_else(|| file.to_path_buf())
+    };
+    let inputs = fsh::expand(copies.iter().map(|(copy, _)| copy).collect(), &common.sushi, common.fhir_version, rename);
+    for built in inputs.files.iter() {
+        let file = inputs.origin(built);
+        match read_resource(built) {
+            Ok(resource) => resources.push((file.clone(), resource)),
+            Err(e) => diagnostics::emit(Diagnostic::error("unreadable-file", e.to_string()).in_file(file)),
+        }
+    }
+    Ok(resources)
+}
+
+/// Writes a file copied out of the revision, with the folders it is in
+fn copy_out(file: &Path, bytes: &[u8]) -> Result<(), String> {
+    file.parent()
+        .map_or(Ok(()), create_dir_all)
+        .and_then(|_| write(file, bytes))
+        .map_err(|e| format!("Error writing '{}': {}", file.display(), e))
+}
@@ -0,0 +1,58 @@
+use crate::StructureDefTreeInfo;
+use crate::utils::{get_slice_after_last_occurrence, get_slice_before_first_occurrence};
+use std::collections::HashMap;
+
+/// The loaded structure definitions by id and by canonical URL, built once per run so that
+/// looking up the datatype of each element does not scan all of them. Datatypes and bases are
+/// named by the last segment of a canonical, which is also a key, so that a structure definition
+/// whose id differs from its URL is found.
+pub struct DocIndex<'a> {
+    pub docs: &'a [StructureDefTreeInfo],
+    keys: HashMap<String, usize>,
+}
+
+impl<'a> DocIndex<'a> {
+    pub fn new(docs: &'a [StructureDefTreeInfo]) -> Self {
+        let mut keys = HashMap::<String, usize>::new();
+        // ids take precedence over URLs, and the first of several with the same key is kept
+        for (doc_num, doc) in docs.iter().enumerate() {
+            keys.entry(doc.id.clone()).or_insert(doc_num);
+        }
+        for (doc_num, doc) in docs.iter().enumerate().filter(|(_, doc)| !doc.url.is_empty()) {
+            keys.entry(doc.url.clone()).or_insert(doc_num);
+            if let Some(name) = get_slice_after_last_occurrence(&doc.url, '/') {
+                keys.entry(name).or_insert(doc_num);
+            }
+        }
+        DocIndex { docs, keys }
+    }
+
+    /// The structure definition with an id, a canonical URL (with or without a version) or the
+    /// last segment of one
+    pub fn get(&self, key: &str) -> Option<&'a StructureDefTreeInfo> {
+        self.position(key).map(|doc_num| &self.docs[doc_num])
+    }
+
+    /// Position of the structure definition among the loaded ones
+    pub fn position(&self, key: &str) -> Option<usize> {
+        self.keys
+            .get(key)
+            .or_else(|| self.keys.get(&get_slice_before_first_occurrence(key, '|')?))
+            .copied()
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// The loaded structure definition another one is based on, by its id or canonical URL, none
+    /// for one based on itself
+    pub fn base_of(&self, doc: &StructureDefTreeInfo) -> Option<&'a StructureDefTreeInfo> {
+        self.get(&doc.base).filter(|base| base.id != doc.id)
+    }
+
+    /// The loaded structure definitions based on one, as `base_of` finds their bases
+    pub fn derived_from(&self, doc: &'a StructureDefTreeInfo) -> impl Iterator<Item = &'a StructureDefTreeInfo> {
+        self.docs.iter().filter(move |derived| self.base_of(derived).is_some_and(|base| base.id == doc.id))
+    }
+}
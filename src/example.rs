@@ -0,0 +1,283 @@
+use crate::diagnostics::{self, Diagnostic};
+use crate::doc_index::DocIndex;
+use crate::output::create_output_file;
+use crate::summary::status;
+use crate::utils::get_slice_after_last_occurrence;
+use crate::{ElementInfo, ExampleArgs, StructureDefTreeInfo, TestDataArgs};
+use serde_json::{Map, Value, json};
+use std::collections::HashSet;
+use std::io::BufWriter;
+
+/// Writes a JSON instance skeleton per structure definition, by default <id>-example.json, with the
+/// required elements, the fixed and pattern values and a placeholder value of the datatype of the others
+pub fn generate_examples(args: &ExampleArgs, docs: &[StructureDefTreeInfo]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut generator = InstanceGenerator { index: DocIndex::new(docs), rng: None, reported: HashSet::new() };
+    for doc in docs.iter() {
+        status!("processing: {}", doc.id);
+        let file = args.output_pattern.replace("{id}", &doc.id);
+        serde_json::to_writer_pretty(BufWriter::new(create_output_file(file)?), &generator.instance(doc))?;
+    }
+    Ok(())
+}
+
+/// Writes pseudo-random instances per structure definition, by default <id>-testdata.json: a
+/// transaction bundle for resources, an array of instances for logical models
+pub fn generate_test_data(args: &TestDataArgs, docs: &[StructureDefTreeInfo]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut generator = InstanceGenerator {
+        index: DocIndex::new(docs),
+        rng: Some(Rng::new(args.seed)),
+        reported: HashSet::new(),
+    };
+    for doc in docs.iter() {
+        status!("processing: {}", doc.id);
+        let instances = (0..args.count).map(|_| generator.instance(doc)).collect::<Vec<_>>();
+        let output = if doc.kind == "resource" {
+            let entries = instances
+                .into_iter()
+                .map(|instance| json!({ "resource": instance, "request": { "method": "POST", "url": doc.sd_type } }))
+                .collect::<Vec<_>>();
+            json!({ "resourceType": "Bundle", "type": "transaction", "entry": entries })
+        } else {
+            Value::Array(instances)
+        };
+        let file = args.output_pattern.replace("{id}", &doc.id);
+        serde_json::to_writer_pretty(BufWriter::new(create_output_file(file)?), &output)?;
+    }
+    Ok(())
+}
+
+/// Small seedable pseudo-random number generator (SplitMix64), giving the same values for a seed
+/// on every platform
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number from 0 up to, not including, `n`
+    fn below(&mut self, n: u64) -> u64 {
+        if n == 0 { 0 } else { self.next() % n }
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len() as u64) as usize]
+    }
+}
+
+const FAMILY_NAMES: &[&str] = &["Andersson", "Berg", "Costa", "Dubois", "Eriksson", "Fischer", "García", "Horvat"];
+const GIVEN_NAMES: &[&str] = &["Anna", "Björn", "Chiara", "David", "Elin", "Felix", "Greta", "Hugo"];
+const WORDS: &[&str] = &["alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel"];
+
+/// Builds instances of structure definitions, with placeholder values when there is no random
+/// number generator, otherwise with random optional elements, repetitions and values
+struct InstanceGenerator<'a> {
+    index: DocIndex<'a>,
+    rng: Option<Rng>,
+    /// the elements reported with a maximum below the minimum, reported once for all instances
+    reported: HashSet<(&'a str, &'a str)>,
+}
+
+impl<'a> InstanceGenerator<'a> {
+    fn instance(&mut self, doc: &'a StructureDefTreeInfo) -> Value {
+        let mut instance = self.skeleton(doc, &mut vec![doc.id.as_str()]);
+        if doc.kind == "resource" {
+            instance.insert("resourceType".to_string(), json!(doc.sd_type));
+            if doc.derivation == "constraint" && !doc.url.is_empty() {
+                instance.insert("meta".to_string(), json!({ "profile": [doc.url] }));
+            }
+        }
+        Value::Object(instance)
+    }
+
+    /// The elements of an instance of a structure definition, `stack` holding the structure
+    /// definitions being expanded to stop at circular references
+    fn skeleton(&mut self, doc: &'a StructureDefTreeInfo, stack: &mut Vec<&'a str>) -> Map<String, Value> {
+        match doc.element_tree.iter().next() {
+            Some((root, _)) => self.children_object(doc, root, stack),
+            None => Map::new(),
+        }
+    }
+
+    fn children_object(&mut self, doc: &'a StructureDefTreeInfo, idx: usize, stack: &mut Vec<&'a str>) -> Map<String, Value> {
+        let mut object = Map::new();
+        for &child_idx in doc.element_tree.children(idx) {
+            let Some(element) = doc.element_tree.get(child_idx) else {
+                continue;
+            };
+            let name = get_slice_after_last_occurrence(&element.id, '.').unwrap_or(element.id.to_string());
+            // slices are left out, the sliced element is present when required
+            if &*element.max == "0" || name.contains(':') {
+                continue;
+            }
+            let min = element.min.parse::<u64>().unwrap_or(0);
+            let count = match &mut self.rng {
+                None if min == 0 && !has_fixed_value(doc, child_idx) => 0,
+                None => min.max(1),
+                Some(_) if has_fixed_value(doc, child_idx) => min.max(1),
+                // up to two more repetitions than required, within the maximum
+                Some(rng) => {
+                    let max = element.max.parse::<u64>().unwrap_or(u64::MAX);
+                    if max < min && self.reported.insert((&doc.id, &element.id)) {
+                        diagnostics::emit(
+                            Diagnostic::warning("max-below-min", format!("Maximum {} is below the minimum {}, {} are generated", max, min, min))
+                                .at(&element.id)
+                                .in_file(&doc.source),
+                        );
+                    }
+                    let max = max.clamp(min, min + 2);
+                    min + rng.below(max - min + 1)
+                }
+            };
+            if count == 0 {
+                continue;
+            }
+
+            let datatype = match &element.fixed_value {
+                Some((datatype, _)) => datatype.clone(),
+                None if element.datatype.is_empty() => String::new(),
+                // the first type of a choice is picked for a skeleton
+                None => match &mut self.rng {
+                    Some(rng) => rng.pick(&element.datatype).to_string(),
+                    None => element.datatype[0].to_string(),
+                },
+            };
+            let is_reference = element.reference.iter().any(|reference| **reference == *datatype);
+            let key = match name.strip_suffix("[x]") {
+                Some(choice) if is_reference => format!("{}Reference", choice),
+                Some(choice) => format!("{}{}", choice, capitalize(&datatype)),
+                None => name,
+            };
+
+            let mut values = (0..count)
+                .map(|_| self.element_value(doc, child_idx, &datatype, is_reference, stack))
+                .collect::<Vec<_>>();
+            let value = if &*element.max == "1" { values.remove(0) } else { Value::Array(values) };
+            object.insert(key, value);
+        }
+        object
+    }
+
+    /// Value of an element: its fixed or pattern value, its children, the instance of the loaded
+    /// structure definition it has as datatype or a value of the datatype
+    fn element_value(
+        &mut self,
+        doc: &'a StructureDefTreeInfo,
+        idx: usize,
+        datatype: &str,
+        is_reference: bool,
+        stack: &mut Vec<&'a str>,
+    ) -> Value {
+        let Some(element) = doc.element_tree.get(idx) else {
+            return Value::Null;
+        };
+        if let Some((_, fixed)) = &element.fixed_value {
+            return serde_json::from_str(fixed).unwrap_or_default();
+        }
+        if is_reference {
+            return json!({ "reference": format!("{}/{}", datatype, self.word()) });
+        }
+        let children = self.children_object(doc, idx, stack);
+        if !children.is_empty() {
+            return Value::Object(children);
+        }
+        if let Some(type_doc) = self.index.get(datatype)
+            && !stack.contains(&type_doc.id.as_str())
+        {
+            stack.push(&type_doc.id);
+            let value = Value::Object(self.skeleton(type_doc, stack));
+            stack.pop();
+            return value;
+        }
+        self.value_of(datatype, element)
+    }
+
+    fn word(&mut self) -> String {
+        match &mut self.rng {
+            Some(rng) => format!("{}-{}", rng.pick(WORDS), rng.below(10000)),
+            None => "example".to_string(),
+        }
+    }
+
+    /// A number from 0 up to, not including, `below`, or 1 for a skeleton
+    fn number(&mut self, below: u64) -> u64 {
+        self.rng.as_mut().map(|rng| rng.below(below)).unwrap_or(1)
+    }
+
+    /// A concept of the bound value set when its codes are among the loaded files
+    fn concept(&mut self, element: &ElementInfo) -> (String, String) {
+        if element.value_set_codes.is_empty() {
+            return ("http://example.org".to_string(), self.word());
+        }
+        let (system, code, _) = match &mut self.rng {
+            Some(rng) => rng.pick(&element.value_set_codes),
+            None => &element.value_set_codes[0],
+        };
+        (system.clone(), code.clone())
+    }
+
+    /// Value of a datatype used where the profile gives none
+    fn value_of(&mut self, datatype: &str, element: &ElementInfo) -> Value {
+        let date = match self.rng.as_mut() {
+            Some(rng) => format!("{}-{:02}-{:02}", 1950 + rng.below(75), 1 + rng.below(12), 1 + rng.below(28)),
+            None => "2000-01-01".to_string(),
+        };
+        match datatype {
+            "boolean" => json!(self.number(2) == 1),
+            "integer" | "positiveInt" | "unsignedInt" => json!(self.number(100).max(1)),
+            "integer64" => json!(self.number(100).max(1).to_string()),
+            "decimal" => json!(self.number(10000).max(100) as f64 / 100.0),
+            "date" => json!(date),
+            "dateTime" | "instant" => json!(format!("{}T{:02}:00:00Z", date, self.number(24))),
+            "time" => json!(format!("{:02}:00:00", self.number(24))),
+            "code" => json!(self.concept(element).1),
+            "uri" | "url" | "canonical" => json!(format!("http://example.org/{}", self.word())),
+            "oid" => json!(format!("urn:oid:1.2.3.{}", self.number(1000))),
+            "uuid" => json!(format!("urn:uuid:00000000-0000-0000-0000-{:012}", self.number(1_000_000))),
+            "base64Binary" => json!("ZXhhbXBsZQ=="),
+            "Coding" => {
+                let (system, code) = self.concept(element);
+                json!({ "system": system, "code": code })
+            }
+            "CodeableConcept" => {
+                let (system, code) = self.concept(element);
+                json!({ "coding": [{ "system": system, "code": code }] })
+            }
+            "Identifier" => json!({ "system": "http://example.org", "value": self.word() }),
+            "Quantity" => json!({ "value": self.number(100).max(1), "unit": "1" }),
+            "Period" => json!({ "start": date }),
+            "HumanName" => match &mut self.rng {
+                Some(rng) => json!({ "family": rng.pick(FAMILY_NAMES), "given": [rng.pick(GIVEN_NAMES)] }),
+                None => json!({ "family": "Example" }),
+            },
+            "Address" => json!({ "text": self.word() }),
+            "ContactPoint" => json!({ "system": "phone", "value": self.word() }),
+            "Attachment" => json!({ "contentType": "text/plain", "data": "ZXhhbXBsZQ==" }),
+            "Reference" => json!({ "reference": format!("Resource/{}", self.word()) }),
+            "string" | "markdown" | "id" | "xhtml" => json!(self.word()),
+            _ => json!({}),
+        }
+    }
+}
+
+/// Whether an element, or one below it, has a fixed or pattern value
+fn has_fixed_value(doc: &StructureDefTreeInfo, idx: usize) -> bool {
+    doc.element_tree.get(idx).is_some_and(|e| e.fixed_value.is_some())
+        || doc.element_tree.children(idx).iter().any(|&child| has_fixed_value(doc, child))
+}
+
+fn capitalize(datatype: &str) -> String {
+    let mut chars = datatype.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
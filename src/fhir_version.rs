@@ -0,0 +1,80 @@
+use clap::ValueEnum;
+use serde_json::Value;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FhirVersion {
+    /// FHIR R4 (4.0)
+    R4,
+    /// FHIR R4B (4.3)
+    #[value(name = "r4b")]
+    R4B,
+    /// FHIR R5 (5.0) and later
+    R5,
+}
+
+impl FhirVersion {
+    /// Version given by the `fhirVersion` of a structure definition, e.g. "4.0.1"
+    pub fn from_fhir_version(version: &str) -> Option<FhirVersion> {
+        let mut parts = version.split('.');
+        match (parts.next()?, parts.next()) {
+            ("4", Some("3")) => Some(FhirVersion::R4B),
+            ("4", _) => Some(FhirVersion::R4),
+            (major, _) if major.parse::<u32>().is_ok_and(|major| major >= 5) => Some(FhirVersion::R5),
+            _ => None,
+        }
+    }
+
+    /// Version of a structure definition, unless overridden; documents without a known
+    /// `fhirVersion` are handled as R5
+    pub fn detect(doc: &Value, version_override: Option<FhirVersion>) -> FhirVersion {
+        version_override
+            .or_else(|| doc["fhirVersion"].as_str().and_then(FhirVersion::from_fhir_version))
+            .unwrap_or(FhirVersion::R5)
+    }
+
+    /// Name of the root type that logical models specialize, "Element" before R5 and "Base" since
+    pub fn logical_root(&self) -> &'static str {
+        match self {
+            FhirVersion::R4 | FhirVersion::R4B => "Element",
+            FhirVersion::R5 => "Base",
+        }
+    }
+
+    /// Obligation extensions, the R5 one and the tools IG one used with R4 and R4B
+    pub fn obligation_urls(&self) -> &'static [&'static str] {
+        match self {
+            FhirVersion::R4 | FhirVersion::R4B => &[
+                "http://hl7.org/fhir/tools/StructureDefinition/obligation",
+                "http://hl7.org/fhir/StructureDefinition/obligation",
+            ],
+            FhirVersion::R5 => &["http://hl7.org/fhir/StructureDefinition/obligation"],
+        }
+    }
+}
+
+/// FHIR primitive type of a FHIRPath system type code, e.g. "http://hl7.org/fhirpath/System.String",
+/// as used for the `id` and `value` elements of primitives
+pub fn primitive_of_system_type(dt: &Value) -> Option<&str> {
+    // the type extension gives the FHIR type in both R4 and R5
+    if let Some(ext_array) = dt["extension"].as_array() {
+        for ext in ext_array {
+            if ext["url"] == "http://hl7.org/fhir/StructureDefinition/structuredefinition-fhir-type"
+                && let Some(value) = ext["valueUrl"].as_str().or(ext["valueUri"].as_str())
+            {
+                return Some(value);
+            }
+        }
+    }
+    let system_type = dt["code"].as_str()?.strip_prefix("http://hl7.org/fhirpath/System.")?;
+    let primitive = match system_type {
+        "String" => "string",
+        "Boolean" => "boolean",
+        "Integer" => "integer",
+        "Decimal" => "decimal",
+        "Date" => "date",
+        "DateTime" => "dateTime",
+        "Time" => "time",
+        other => other,
+    };
+    Some(primitive)
+}
@@ -0,0 +1,61 @@
+use crate::output::create_output_file;
+use crate::summary::status;
+use crate::{FhirPathArgs, StructureDefTreeInfo};
+use std::io::{BufWriter, Write};
+
+/// Writes, for each structure definition, the FHIRPath expression selecting each of its leaf
+/// elements, a choice element giving an expression per type, e.g. `Observation.value.ofType(Quantity)`
+pub fn generate_fhirpath(args: &FhirPathArgs, docs: &[StructureDefTreeInfo]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = BufWriter::new(create_output_file(&args.output_file)?);
+    for (doc_num, doc) in docs.iter().enumerate() {
+        status!("processing: {}", doc.id);
+        if doc_num > 0 {
+            writeln!(writer)?;
+        }
+        writeln!(writer, "// {}", if doc.url.is_empty() { &doc.id } else { &doc.url })?;
+        let Some((root, _)) = doc.element_tree.iter().next() else {
+            continue;
+        };
+        for expression in leaf_expressions(doc, root) {
+            writeln!(writer, "{}", expression)?;
+        }
+    }
+    Ok(())
+}
+
+/// Expressions of the leaf elements below an element, leaving out slices and elements that are
+/// not allowed, i.e. those with max 0 and the elements below them
+fn leaf_expressions(doc: &StructureDefTreeInfo, idx: usize) -> Vec<String> {
+    let mut expressions = Vec::new();
+    for &child_idx in doc.element_tree.children(idx) {
+        let Some(element) = doc.element_tree.get(child_idx) else {
+            continue;
+        };
+        if &*element.max == "0" || element.id.contains(':') {
+            continue;
+        }
+        if !doc.element_tree.children(child_idx).is_empty() {
+            expressions.extend(leaf_expressions(doc, child_idx));
+            continue;
+        }
+
+        let path = element.id.replace("[x]", "");
+        if !element.id.ends_with("[x]") {
+            expressions.push(path);
+            continue;
+        }
+        // the targets of a reference are all selected by ofType(Reference)
+        let mut types = Vec::<&str>::new();
+        for datatype in element.datatype.iter() {
+            let datatype = if element.reference.contains(datatype) { "Reference" } else { datatype };
+            if !types.contains(&datatype) {
+                types.push(datatype);
+            }
+        }
+        if types.is_empty() {
+            expressions.push(path.clone());
+        }
+        expressions.extend(types.iter().map(|datatype| format!("{}.ofType({})", path, datatype)));
+    }
+    expressions
+}
@@ -0,0 +1,224 @@
+//! FSH input. FSH files and SUSHI project folders among the input files are built with SUSHI into
+//! a temporary folder first, and the resources it generates are read in their place, so that a
+//! command goes from FSH to its documents in one step. The resources are named after the FSH file
+//! declaring them, or the project folder, so that diagnostics and sources do not point into the
+//! temporary folder.
+
+use crate::diagnostics::{self, Diagnostic};
+use crate::fhir_version::FhirVersion;
+use crate::summary::status;
+use std::collections::HashMap;
+use std::fs::{copy, create_dir_all, read_dir, read_to_string, remove_dir_all, write};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Temporary folders created in the run, numbered so that batch runs do not share one
+static BUILDS: AtomicUsize = AtomicUsize::new(0);
+
+/// Declarations of FSH naming a resource, by its id or by the name it takes as id without one
+const DECLARATIONS: &[&str] = &["Id", "Profile", "Logical", "Resource", "Extension", "ValueSet", "CodeSystem", "Instance"];
+
+/// A temporary folder of the run, removed when dropped
+pub struct TempDir(pub PathBuf);
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.0);
+    }
+}
+
+/// A new folder in the temporary folder of the system, not created yet
+pub fn temp_dir() -> TempDir {
+    TempDir(std::env::temp_dir().join(format!(
+        "fhir-generate-{}-{}",
+        std::process::id(),
+        BUILDS.fetch_add(1, Ordering::Relaxed)
+    )))
+}
+
+/// The files to read, with the folder of the resources built by SUSHI and the FSH file or project
+/// folder each of them was built from
+pub struct Inputs {
+    pub files: Vec<PathBuf>,
+    origins: HashMap<PathBuf, PathBuf>,
+    // only kept for the folder to be removed with the inputs
+    _temp_dir: Option<TempDir>,
+}
+
+impl Inputs {
+    /// The file to name a resource after: the FSH file or project folder it was built from, or the
+    /// file itself when given
+    pub fn origin<'a>(&'a self, file: &'a PathBuf) -> &'a PathBuf {
+        self.origins.get(file).unwrap_or(file)
+    }
+
+    /// Adds the resources built from FSH files, each named after the file among the candidates
+    /// declaring it, or else after `fallback`
+    fn add_built<F>(&mut self, resources: Vec<PathBuf>, candidates: &[PathBuf], fallback: &Path, rename: &F)
+    where
+        F: Fn(&Path) -> PathBuf,
+    {
+        let sources = candidates
+            .iter()
+            .filter_map(|file| read_to_string(file).ok().map(|fsh| (file, fsh)))
+            .collect::<Vec<_>>();
+        for resource in resources {
+            // SUSHI names the resources <type>-<id>.json
+            let id = resource
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.split_once('-'))
+                .map(|(_, id)| id)
+                .unwrap_or_default();
+            let origin = match sources.iter().find(|(_, fsh)| declares(fsh, id)) {
+                Some((file, _)) => rename(file),
+                None => fallback.to_path_buf(),
+            };
+            self.origins.insert(resource.clone(), origin);
+            self.files.push(resource);
+        }
+    }
+}
+
+fn is_fsh(file: &Path) -> bool {
+    file.extension().is_some_and(|extension| extension == "fsh")
+}
+
+/// The input files with the FSH files and SUSHI projects replaced by the resources SUSHI builds
+/// from them. The FSH files are built together as one project, so that they can refer to each
+/// other, and each project folder on its own. `rename` gives the name shown for an input file or a
+/// file below a project folder, the file itself unless it was copied from elsewhere.
+pub fn expand<F>(files: Vec<&PathBuf>, sushi: &Path, fhir_version: Option<FhirVersion>, rename: F) -> Inputs
+where
+    F: Fn(&Path) -> PathBuf,
+{
+    let fsh_files = files.iter().filter(|file| is_fsh(file)).collect::<Vec<_>>();
+    let projects = files.iter().filter(|file| file.is_dir()).collect::<Vec<_>>();
+    let mut inputs = Inputs {
+        files: files.iter().filter(|file| !is_fsh(file) && !file.is_dir()).map(|file| file.to_path_buf()).collect(),
+        origins: HashMap::new(),
+        _temp_dir: None,
+    };
+    if fsh_files.is_empty() && projects.is_empty() {
+        return inputs;
+    }
+
+    let temp_dir = temp_dir();
+    if !fsh_files.is_empty() {
+        let project = temp_dir.0.join("fsh");
+        let shown = fsh_files.iter().map(|file| rename(file).display().to_string()).collect::<Vec<_>>();
+        let built = write_project(&project, &fsh_files, fhir_version)
+            .map_err(|e| format!("Error writing a SUSHI project for the FSH files: {}", e))
+            .and_then(|_| build(sushi, &project, &temp_dir.0.join("fsh-out"), &shown.join(", ")));
+        match built {
+            Ok(resources) => {
+                let candidates = fsh_files.iter().map(|file| file.to_path_buf()).collect::<Vec<_>>();
+                inputs.add_built(resources, &candidates, &rename(fsh_files[0]), &rename);
+            }
+            Err(e) => diagnostics::emit(Diagnostic::error("sushi-failed", e).in_file(&rename(fsh_files[0]))),
+        }
+    }
+    for (i, project) in projects.into_iter().enumerate() {
+        let shown = rename(project);
+        match build(sushi, project, &temp_dir.0.join(format!("project-{}", i)), &shown.display().to_string()) {
+            Ok(resources) => {
+                let mut candidates = Vec::<PathBuf>::new();
+                files_below(&project.join("input"), &mut candidates);
+                candidates.retain(|file| is_fsh(file));
+                inputs.add_built(resources, &candidates, &shown, &rename);
+            }
+            Err(e) => diagnostics::emit(Diagnostic::error("sushi-failed", e).in_file(&shown)),
+        }
+    }
+    inputs._temp_dir = Some(temp_dir);
+    inputs
+}
+
+/// Whether FSH declares a resource of the id, e.g. by `Id: EHDSAddress` or `Logical: EHDSAddress`
+fn declares(fsh: &str, id: &str) -> bool {
+    fsh.lines().any(|line| {
+        line.split_once(':')
+            .is_some_and(|(keyword, name)| DECLARATIONS.contains(&keyword.trim()) && name.trim() == id)
+    })
+}
+
+/// The files the inputs are read or built from, for noticing changes to them: the input files, and
+/// for a SUSHI project folder its configuration and the files below its input folder
+pub fn watched(files: &[PathBuf]) -> Vec<PathBuf> {
+    let mut watched = Vec::<PathBuf>::new();
+    for file in files.iter() {
+        if file.is_dir() {
+            watched.push(file.join("sushi-config.yaml"));
+            files_below(&file.join("input"), &mut watched);
+        } else {
+            watched.push(file.clone());
+        }
+    }
+    watched
+}
+
+fn files_below(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = read_dir(dir) else {
+        return;
+    };
+    let mut entries = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect::<Vec<_>>();
+    // in the same order each time, for the modification times to compare
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            files_below(&entry, files);
+        } else {
+            files.push(entry);
+        }
+    }
+}
+
+/// A SUSHI project of FSH files only, with the least configuration SUSHI needs
+fn write_project(project: &Path, fsh_files: &[&&PathBuf], fhir_version: Option<FhirVersion>) -> io::Result<()> {
+    let fsh_dir = project.join("input").join("fsh");
+    create_dir_all(&fsh_dir)?;
+    let fhir_version = match fhir_version {
+        Some(FhirVersion::R4B) => "4.3.0",
+        Some(FhirVersion::R5) => "5.0.0",
+        _ => "4.0.1",
+    };
+    write(
+        project.join("sushi-config.yaml"),
+        format!("canonical: http://example.org/fhir\nfhirVersion: {}\nFSHOnly: true\n", fhir_version),
+    )?;
+    for (i, file) in fsh_files.iter().enumerate() {
+        // files of the same name in different folders are kept apart
+        let name = file.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        copy(file, fsh_dir.join(format!("{}-{}", i, name)))?;
+    }
+    Ok(())
+}
+
+/// Runs SUSHI on a project with snapshots, which the generators need, and lists the resources it
+/// generates. Its messages go to standard error, as standard output may carry the documents.
+/// `shown` names the input in the messages.
+fn build(sushi: &Path, project: &Path, out: &Path, shown: &str) -> Result<Vec<PathBuf>, String> {
+    status!("running sushi: {}", shown);
+    let result = Command::new(sushi)
+        .arg("build")
+        .arg(project)
+        .arg("--out")
+        .arg(out)
+        .arg("--snapshot")
+        .stdout(io::stderr())
+        .status()
+        .map_err(|e| format!("Error running '{}', install SUSHI or give its path with --sushi: {}", sushi.display(), e))?;
+    if !result.success() {
+        return Err(format!("Error building '{}' with SUSHI: sushi exited with {}", shown, result));
+    }
+    let resources_dir = out.join("fsh-generated").join("resources");
+    let mut resources = read_dir(&resources_dir)
+        .map_err(|e| format!("Error reading the resources SUSHI generated in '{}': {}", resources_dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|file| file.extension().is_some_and(|extension| extension == "json"))
+        .collect::<Vec<_>>();
+    resources.sort();
+    Ok(resources)
+}
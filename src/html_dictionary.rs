@@ -0,0 +1,111 @@
+use crate::numbering::number_elements;
+use crate::output::create_output_file;
+use crate::summary::status;
+use crate::utils::{escape_xml, get_slice_after_last_occurrence};
+use crate::{HtmlDictionaryArgs, StructureDefTreeInfo, TableArgs, TableContext, default_args, extension_rows, is_extension};
+use serde_json::{Value, json};
+use std::io::{BufWriter, Write};
+
+const PAGE_START: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body { font-family: sans-serif; margin: 1em; }
+#filters > * { margin-right: 1em; }
+table { border-collapse: collapse; margin-top: 1em; width: 100%; }
+th, td { border: 1px solid #ccc; padding: 4px; text-align: left; vertical-align: top; }
+th { background: #eee; }
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<div id="filters">
+<input id="query" type="search" placeholder="Search elements, descriptions and codes" size="50">
+<select id="profile"><option value="">All profiles</option></select>
+<select id="datatype"><option value="">All datatypes</option></select>
+<label><input id="mustSupport" type="checkbox"> Must support only</label>
+<span id="count"></span>
+</div>
+<table>
+<thead><tr><th>Code</th><th>Profile</th><th>Path</th><th>Description</th><th>Datatype</th><th>Cardinality</th></tr></thead>
+<tbody id="rows"></tbody>
+</table>
+<script>
+const DATA = "#;
+
+const PAGE_END: &str = r#";
+const $ = (id) => document.getElementById(id);
+const escape = (s) => s.replace(/[&<>"]/g, (c) => ({ "&": "&amp;", "<": "&lt;", ">": "&gt;", '"': "&quot;" })[c]);
+const fill = (select, values) => {
+  [...new Set(values)].sort().forEach((v) => select.add(new Option(v, v)));
+};
+fill($("profile"), DATA.map((e) => e.profile));
+fill($("datatype"), DATA.flatMap((e) => e.datatype));
+const render = () => {
+  const query = $("query").value.toLowerCase();
+  const rows = DATA.filter((e) =>
+    (!query || [e.name, e.path, e.short, e.definition, e.code].some((t) => t.toLowerCase().includes(query))) &&
+    (!$("profile").value || e.profile === $("profile").value) &&
+    (!$("datatype").value || e.datatype.includes($("datatype").value)) &&
+    (!$("mustSupport").checked || e.mustSupport));
+  $("rows").innerHTML = rows.map((e) =>
+    `<tr><td>${escape(e.code)}</td><td>${escape(e.profile)}</td><td>${escape(e.path)}</td>` +
+    `<td><b>${escape(e.short)}</b><br>${escape(e.definition)}</td><td>${escape(e.datatype.join(", "))}</td>` +
+    `<td>${escape(e.cardinality)}</td></tr>`).join("");
+  $("count").textContent = `${rows.length} of ${DATA.length} elements`;
+};
+["query", "profile", "datatype", "mustSupport"].forEach((id) => $(id).addEventListener("input", render));
+render();
+</script>
+</body>
+</html>
+"#;
+
+/// Writes a single HTML file with all elements of all structure definitions and a client-side search
+pub fn generate_html_dictionary(
+    args: &HtmlDictionaryArgs,
+    docs: &[StructureDefTreeInfo],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut table_args = default_args::<TableArgs>();
+    table_args.common.lang = args.common.lang.clone();
+    let ctx = TableContext::new(&table_args, docs)?;
+
+    let mut index = Vec::<Value>::new();
+    for (doc_num, doc) in docs.iter().enumerate() {
+        status!("processing: {}", doc.id);
+        let codes = number_elements(&doc.element_tree, &ctx.prefix_of(doc_num, doc), &table_args.numbering, &ctx.stable_codes);
+        // an extension by its sub-extensions, typed by their values, as in its table
+        let extension = is_extension(doc).then(|| extension_rows(doc));
+        let elements = match &extension {
+            Some(rows) => rows.iter().map(|(idx, name, row)| (*idx, name.as_str(), row)).collect::<Vec<_>>(),
+            None => doc.element_tree.iter().map(|(idx, element)| (idx, "", element)).collect(),
+        };
+        for (idx, name, element) in elements {
+            let name = if name.is_empty() {
+                get_slice_after_last_occurrence(&element.id, '.').unwrap_or(element.id.to_string())
+            } else {
+                name.to_string()
+            };
+            index.push(json!({
+                "profile": doc.id,
+                "path": element.id,
+                "name": name,
+                "short": element.short,
+                "definition": element.definition,
+                "datatype": element.datatype,
+                "cardinality": format!("{}..{}", element.min, element.max),
+                "mustSupport": element.must_support,
+                "code": codes.get(&idx).cloned().unwrap_or_default(),
+            }));
+        }
+    }
+
+    let mut writer = BufWriter::new(create_output_file(&args.output_file)?);
+    write!(writer, "{}", PAGE_START.replace("{title}", &escape_xml(&args.title)))?;
+    // "</" would end the script element early
+    write!(writer, "{}", serde_json::to_string(&index)?.replace("</", "<\\/"))?;
+    write!(writer, "{}", PAGE_END)?;
+    Ok(())
+}
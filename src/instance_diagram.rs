@@ -0,0 +1,236 @@
+use crate::diagnostics::{self, Diagnostic};
+use crate::doc_index::DocIndex;
+use crate::output::{self, create_output_file};
+use crate::summary::{self, status};
+use crate::utils::{get_slice_after_last_occurrence, load_json_from_file};
+use crate::{InstanceDiagramArgs, StructureDefTreeInfo, load_structure_definition_files};
+use serde_json::Value;
+use std::io::{BufWriter, Write};
+
+/// Members of a resource that are not shown as fields
+const HIDDEN_MEMBERS: &[&str] = &["resourceType", "id", "meta", "text", "contained", "extension", "modifierExtension"];
+
+/// Longest field value shown, longer ones are cut with an ellipsis
+const MAX_VALUE_LENGTH: usize = 60;
+
+/// An instance drawn as an object
+struct Instance {
+    alias: String,
+    /// e.g. Patient/p1
+    name: String,
+    /// fullUrl in a bundle
+    full_url: Option<String>,
+    resource: Value,
+}
+
+/// Writes a PlantUML object diagram of the instances, an object per resource with the values of
+/// its key fields, and a link per reference between them. The key fields are the required,
+/// must-support and summary elements of the profile of the instance (its meta.profile, or else its
+/// resource type) among the structure definitions, or all when there is none.
+pub fn generate_instance_diagram(args: &InstanceDiagramArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let docs = load_structure_definition_files(&args.common)?;
+    let index = DocIndex::new(&docs);
+    output::protect_inputs(&args.instance);
+
+    let mut instances = Vec::<Instance>::new();
+    for file in args.instance.iter() {
+        let value = match load_json_from_file(file) {
+            Ok(value) => value,
+            Err(e) => {
+                diagnostics::emit(Diagnostic::error("unreadable-file", e.to_string()).in_file(file));
+                continue;
+            }
+        };
+        summary::file_read();
+        if value["resourceType"] == "Bundle" {
+            for entry in value["entry"].as_array().into_iter().flatten() {
+                let full_url = entry["fullUrl"].as_str().map(|url| url.to_string());
+                add_instance(&mut instances, entry["resource"].clone(), full_url);
+            }
+        } else {
+            add_instance(&mut instances, value, None);
+        }
+    }
+
+    let mut writer = BufWriter::new(create_output_file(&args.output_file)?);
+    writeln!(writer, "@startuml\nhide circle\n")?;
+    let mut links = String::new();
+    let mut unresolved = Vec::<String>::new();
+    for instance in instances.iter() {
+        status!("processing: {}", instance.name);
+        let profile = profile_of(&instance.resource, &index);
+        match profile {
+            Some(profile) => writeln!(writer, "object \"{}\" as {} <<{}>> {{", instance.name, instance.alias, profile.id)?,
+            None => writeln!(writer, "object \"{}\" as {} {{", instance.name, instance.alias)?,
+        }
+        for (field, value) in key_fields(&instance.resource, profile, args.all_fields) {
+            writeln!(writer, "  {} = {}", field, value)?;
+        }
+        writeln!(writer, "}}")?;
+
+        let mut references = Vec::<(String, String)>::new();
+        collect_references(&instance.resource, "", &mut references);
+        for (path, reference) in references {
+            let target = if let Some(target) = instances.iter().find(|target| refers_to(&reference, target)) {
+                target.alias.clone()
+            } else if let Some(position) = unresolved.iter().position(|target| *target == reference) {
+                format!("ref{}", position + 1)
+            } else {
+                // targets that are not among the instances are drawn dashed, without fields
+                unresolved.push(reference.clone());
+                let alias = format!("ref{}", unresolved.len());
+                writeln!(writer, "object \"{}\" as {} #line.dashed", reference, alias)?;
+                alias
+            };
+            links += &format!("{} --> {} : {}\n", instance.alias, target, path);
+        }
+    }
+    write!(writer, "\n{}", links)?;
+    writeln!(writer, "@enduml")?;
+    Ok(())
+}
+
+fn add_instance(instances: &mut Vec<Instance>, resource: Value, full_url: Option<String>) {
+    let Some(resource_type) = resource["resourceType"].as_str() else {
+        return;
+    };
+    // logical models are named by the URL of their type
+    let resource_type = get_slice_after_last_occurrence(resource_type, '/').unwrap_or(resource_type.to_string());
+    let name = match resource["id"].as_str() {
+        Some(id) => format!("{}/{}", resource_type, id),
+        None => resource_type,
+    };
+    let alias = format!("o{}", instances.len() + 1);
+    instances.push(Instance { alias, name, full_url, resource });
+}
+
+/// The profile of an instance among the structure definitions
+fn profile_of<'a>(resource: &Value, index: &DocIndex<'a>) -> Option<&'a StructureDefTreeInfo> {
+    resource["meta"]["profile"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|profile| profile.as_str())
+        .chain(resource["resourceType"].as_str())
+        .find_map(|key| index.get(key))
+}
+
+/// Whether a reference, relative, absolute or to the fullUrl of a bundle entry, is to an instance
+fn refers_to(reference: &str, instance: &Instance) -> bool {
+    instance.full_url.as_deref() == Some(reference) || reference == instance.name || reference.ends_with(&format!("/{}", instance.name))
+}
+
+/// The fields shown of an instance with their values, in the order of the elements of the profile
+fn key_fields(resource: &Value, profile: Option<&StructureDefTreeInfo>, all_fields: bool) -> Vec<(String, String)> {
+    let Some(members) = resource.as_object() else {
+        return Vec::new();
+    };
+    let shown = members
+        .iter()
+        .filter(|(name, value)| !HIDDEN_MEMBERS.contains(&name.as_str()) && !name.starts_with('_') && !is_reference(value))
+        .collect::<Vec<_>>();
+    let Some(profile) = profile else {
+        return shown.iter().map(|(name, value)| (name.to_string(), value_text(value))).collect();
+    };
+
+    let tree = &profile.element_tree;
+    let elements = tree
+        .iter()
+        .next()
+        .map(|(root, _)| tree.children(root).iter().filter_map(|&child| tree.get(child)).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let mut fields = Vec::<(String, String, bool)>::new();
+    for element in elements {
+        let element_name = get_slice_after_last_occurrence(&element.id, '.').unwrap_or_default();
+        let is_key = &*element.min != "0" || element.must_support || element.is_summary;
+        for (name, value) in shown.iter().filter(|(name, _)| member_of(name, &element_name)) {
+            fields.push((name.to_string(), value_text(value), is_key));
+        }
+    }
+    if !all_fields && fields.iter().any(|(_, _, is_key)| *is_key) {
+        fields.retain(|(_, _, is_key)| *is_key);
+    }
+    fields.into_iter().map(|(name, value, _)| (name, value)).collect()
+}
+
+/// Whether a member of an instance is the element of a profile, e.g. valueQuantity of value[x]
+fn member_of(member: &str, element_name: &str) -> bool {
+    match element_name.strip_suffix("[x]") {
+        Some(stem) => member.strip_prefix(stem).is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_uppercase())),
+        None => member == element_name,
+    }
+}
+
+fn is_reference(value: &Value) -> bool {
+    match value {
+        Value::Array(items) => !items.is_empty() && items.iter().all(is_reference),
+        Value::Object(members) => members.contains_key("reference"),
+        _ => false,
+    }
+}
+
+/// The references in an instance with the path of the element, e.g. ("participant.individual",
+/// "Practitioner/p1"), left out of contained resources
+fn collect_references(value: &Value, path: &str, references: &mut Vec<(String, String)>) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                collect_references(item, path, references);
+            }
+        }
+        Value::Object(members) => {
+            if let Some(reference) = members.get("reference").and_then(|reference| reference.as_str()) {
+                references.push((path.to_string(), reference.to_string()));
+                return;
+            }
+            for (name, member) in members.iter().filter(|(name, _)| name.as_str() != "contained") {
+                let path = if path.is_empty() { name.clone() } else { format!("{}.{}", path, name) };
+                collect_references(member, &path, references);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// A field value as shown in an object: primitives as is, common datatypes by their text, display
+/// or code, and other objects by their primitive values
+fn value_text(value: &Value) -> String {
+    let text = match value {
+        Value::Array(items) => items.iter().map(value_text).collect::<Vec<_>>().join(", "),
+        _ => datatype_text(value),
+    };
+    let text = text.replace(['\n', '\r'], " ");
+    if text.chars().count() > MAX_VALUE_LENGTH {
+        text.chars().take(MAX_VALUE_LENGTH).collect::<String>().trim_end().to_string() + "…"
+    } else {
+        text
+    }
+}
+
+fn datatype_text(value: &Value) -> String {
+    let Value::Object(members) = value else {
+        return match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+    };
+    let member = |name: &str| members.get(name).map(value_text).filter(|text| !text.is_empty());
+    if let Some(text) = member("text") {
+        // HumanName, Address, CodeableConcept
+        text
+    } else if let Some(coding) = members.get("coding").and_then(|coding| coding.get(0)) {
+        value_text(coding)
+    } else if let Some(display) = member("display") {
+        display
+    } else if members.contains_key("family") || members.contains_key("given") {
+        [member("given"), member("family")].into_iter().flatten().collect::<Vec<_>>().join(" ")
+    } else if let (Some(quantity), Some(unit)) = (member("value"), member("unit").or(member("code"))) {
+        format!("{} {}", quantity, unit)
+    } else if let Some(code) = member("code") {
+        code
+    } else if let Some(identifier) = member("value") {
+        identifier
+    } else {
+        members.values().map(value_text).filter(|text| !text.is_empty()).collect::<Vec<_>>().join(" ")
+    }
+}
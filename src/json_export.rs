@@ -0,0 +1,120 @@
+//! JSON export of the loaded structure definitions, after parsing and type resolution.
+//!
+//! The document has the form
+//!
+//! ```json
+//! {
+//!   "formatVersion": 2,
+//!   "structureDefinitions": [
+//!     {
+//!       "id": "EHDSPatient", "url": "...", "kind": "logical", "derivation": "specialization",
+//!       "type": "...", "base": "Base", "abstract": false, "interface": false, "fhirVersion": "R5",
+//!       "source": "path/to/StructureDefinition-EHDSPatient.json",
+//!       "roots": [ <element> ]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! where `roots` are the elements without a parent, the root element of the structure definition
+//! and any element whose parent is not defined, and each element has `id`, `short`, `definition`, `datatypes` (type codes and target profile
+//! names, reduced to the last URL segment), `references` (the subset of `datatypes` reached through
+//! a Reference), `min`, `max`, `globalMin`, `globalMax` (cardinality including the ancestors),
+//! `binding` (`description`, `strength`, `valueSet`, `valueSetTitle`, or null), `mustSupport`, `isModifier`,
+//! `isSummary`, `obligations` (`actor`, `code`, `documentation`), `requirements` (or null) and
+//! `children` (the nested child elements).
+
+use crate::output::create_output_file;
+use crate::summary::status;
+use crate::{ElementInfo, JsonArgs, StructureDefTreeInfo};
+use serde_json::{Value, json};
+use std::io::BufWriter;
+
+pub const FORMAT_VERSION: u32 = 2;
+
+pub fn element_to_json(element: &ElementInfo) -> Value {
+    let binding = if element.binding.is_some() || element.binding_strength.is_some() || element.value_set.is_some() {
+        json!({
+            "description": element.binding,
+            "strength": element.binding_strength,
+            "valueSet": element.value_set,
+            "valueSetTitle": element.value_set_title,
+        })
+    } else {
+        Value::Null
+    };
+    json!({
+        "id": element.id,
+        "short": element.short,
+        "definition": element.definition,
+        "datatypes": element.datatype,
+        "references": element.reference,
+        "min": element.min,
+        "max": element.max,
+        "globalMin": element.global_min,
+        "globalMax": element.global_max,
+        "binding": binding,
+        "mustSupport": element.must_support,
+        "isModifier": element.is_modifier,
+        "isSummary": element.is_summary,
+        "obligations": element
+            .obligation
+            .iter()
+            .map(|(actor, code, documentation)| json!({
+                "actor": actor,
+                "code": code,
+                "documentation": documentation,
+            }))
+            .collect::<Vec<_>>(),
+        "requirements": element.requirements,
+        "children": [],
+    })
+}
+
+pub fn structure_definition_to_json(doc: &StructureDefTreeInfo) -> Value {
+    // the tree traversal only walks the first root, the others are elements whose parent is missing
+    let tree = &doc.element_tree;
+    let roots = tree
+        .iter()
+        .filter(|(idx, _)| tree.get_parent_of(Some(*idx)).is_none())
+        .map(|(idx, _)| subtree_to_json(doc, idx))
+        .collect::<Vec<_>>();
+
+    json!({
+        "id": doc.id,
+        "url": doc.url,
+        "kind": doc.kind,
+        "derivation": doc.derivation,
+        "type": doc.sd_type,
+        "base": doc.base,
+        "abstract": doc.is_abstract,
+        "interface": doc.is_interface,
+        "fhirVersion": format!("{:?}", doc.fhir_version),
+        "source": doc.source.display().to_string(),
+        "roots": roots,
+    })
+}
+
+/// An element with its descendants as nested `children`
+fn subtree_to_json(doc: &StructureDefTreeInfo, idx: usize) -> Value {
+    let mut element = element_to_json(doc.element_tree.get_unchecked(idx));
+    element["children"] = doc.element_tree.children(idx).iter().map(|&child| subtree_to_json(doc, child)).collect();
+    element
+}
+
+pub fn export_json(args: &JsonArgs, docs: &[StructureDefTreeInfo]) -> Result<(), Box<dyn std::error::Error>> {
+    let structure_definitions = docs
+        .iter()
+        .map(|doc| {
+            status!("processing: {}", doc.id);
+            structure_definition_to_json(doc)
+        })
+        .collect::<Vec<_>>();
+    let output = json!({
+        "formatVersion": FORMAT_VERSION,
+        "structureDefinitions": structure_definitions,
+    });
+    let writer = BufWriter::new(create_output_file(&args.output_file)?);
+    serde_json::to_writer_pretty(writer, &output)?;
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use crate::utils::load_json_from_file;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+const EN: &[(&str, &str)] = &[
+    ("code", "Code"),
+    ("level", "Level"),
+    ("element_name", "Element Name"),
+    ("flags", "Flags"),
+    ("element_description", "Element Description"),
+    ("data_type", "Data type"),
+    ("cardinality", "Cardinality"),
+    ("binding_requirements", "Binding requirements"),
+    ("relevance_full", "Relevance for support level \"full\""),
+    ("relevance_basic", "Relevance for support level \"basic\""),
+    ("derived_from", "Derived from parent data type:"),
+    ("part_of", "Part of"),
+    ("source", "Source"),
+    ("obligations", "Obligations"),
+    ("contents", "Contents"),
+    ("context_of_use", "Context of use"),
+    ("version", "version"),
+    ("derived_profiles", "Derived profiles"),
+    ("cardinality_format", "{min}..{max}"),
+];
+
+const SV: &[(&str, &str)] = &[
+    ("code", "Kod"),
+    ("level", "Nivå"),
+    ("element_name", "Elementnamn"),
+    ("flags", "Flaggor"),
+    ("element_description", "Elementbeskrivning"),
+    ("data_type", "Datatyp"),
+    ("cardinality", "Kardinalitet"),
+    ("binding_requirements", "Bindningskrav"),
+    ("relevance_full", "Relevans för stödnivå \"full\""),
+    ("relevance_basic", "Relevans för stödnivå \"basic\""),
+    ("derived_from", "Härledd från överordnad datatyp:"),
+    ("part_of", "Del av"),
+    ("source", "Källa"),
+    ("obligations", "Förpliktelser"),
+    ("contents", "Innehåll"),
+    ("context_of_use", "Användningskontext"),
+    ("version", "version"),
+    ("derived_profiles", "Härledda profiler"),
+    ("cardinality_format", "{min}..{max}"),
+];
+
+/// Generated labels in the selected language
+pub struct Labels {
+    map: HashMap<String, String>,
+}
+
+impl Labels {
+    /// Loads the embedded labels for a language, overridden by the entries of an optional
+    /// translation file (a JSON object from label key to text). Missing keys fall back to English.
+    pub fn load(lang: &str, translation_file: Option<&PathBuf>) -> Result<Labels, Box<dyn std::error::Error>> {
+        let mut map: HashMap<String, String> = EN.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        match lang {
+            "en" => (),
+            "sv" => map.extend(SV.iter().map(|(k, v)| (k.to_string(), v.to_string()))),
+            _ if translation_file.is_some() => (),
+            _ => return Err(format!("Unsupported language '{}', supply a translation file", lang).into()),
+        }
+        if let Some(file) = translation_file {
+            let doc = load_json_from_file(file)?;
+            for (key, value) in doc.as_object().ok_or("Translation file must be a JSON object")? {
+                let value = value.as_str().ok_or("Translations must be strings")?;
+                map.insert(key.clone(), value.to_string());
+            }
+        }
+        Ok(Labels { map })
+    }
+
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.map.get(key).map(|s| s.as_str()).unwrap_or(key)
+    }
+
+    pub fn cardinality(&self, min: &str, max: &str) -> String {
+        self.get("cardinality_format")
+            .replace("{min}", min)
+            .replace("{max}", max)
+    }
+}
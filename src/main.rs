@@ -1,33 +1,201 @@
+mod archimate;
+mod archive;
+mod attributes;
+mod batch;
+#[cfg(unix)]
+mod browse;
+mod cli_reference;
+mod compare;
+mod concept_map;
+mod cycles;
+mod diagnostics;
+mod diff;
+mod dictionary;
+mod doc_index;
+mod example;
+mod fhir_version;
+mod fhirpath;
+mod fsh;
+mod html_dictionary;
+mod instance_diagram;
+mod json_export;
+mod labels;
+mod metrics;
+mod naming;
+mod numbering;
+mod openehr;
+mod org;
+mod output;
+mod paths;
+mod pdf;
+mod pipeline;
+mod provenance;
+mod resource_reader;
+mod search_parameter;
+mod serve;
+mod site;
+mod structure_map;
+mod summary;
 mod utils;
+mod where_used;
 
 use crate::utils::{
-    count_char_occurrences, generate_code, get_slice_after_last_occurrence, get_slice_before_first_occurrence, load_json_from_file,
+    generate_code, get_slice_after_last_occurrence, get_slice_before_first_occurrence, load_json_from_file, Interner,
 };
-use clap::{Args, Parser, Subcommand};
+use crate::diagnostics::{Diagnostic, DiagnosticsFormat};
+use crate::doc_index::DocIndex;
+use crate::fhir_version::{FhirVersion, primitive_of_system_type};
+use crate::labels::Labels;
+use crate::naming::{NamingArgs, display_name};
+use crate::numbering::{NumberingScheme, number_elements};
+use crate::output::{OutputTarget, create_output_file};
+use crate::summary::status;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum, ValueHint};
 use easy_tree::Tree;
 use fmt_derive::Display;
 use std::{
     //    collections::{HashMap, HashSet},
-    collections::{HashMap, HashSet},
-    fs::File,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     io::{BufWriter, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Instant,
 };
-use utils::{camel_to_spaced_pascal, reduce_datatypes};
+use utils::{
+    TextMode, escape_xml, reduce_datatypes, sanitize_markdown, slugify,
+};
+
+const EXAMPLES: &str = "Examples:
+  fhir-generate table *.json
+  fhir-generate plant-uml -e StructureDefinition-EHDSMedicationDispense.json StructureDefinition-EHDSMedication.json
+  fhir-generate plant-uml --split --include-style style.iuml *.json
+  fhir-generate mindmap --format freemind --combined Dataset *.json
+  fhir-generate site --output-dir book --title \"EHDS data model\" *.json
+  fhir-generate batch manifest.toml
+  fhir-generate all --with plant-uml,table,mindmap --output-dir docs *.json";
 
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, after_long_help = EXAMPLES)]
 #[command(propagate_version = true)]
+#[command(arg_required_else_help = true)]
 struct Cli {
     /// Command
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
+
+    /// Write man pages for the tool and each subcommand into a folder
+    #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath)]
+    generate_man: Option<PathBuf>,
+
+    /// Print the help of all subcommands
+    #[arg(long)]
+    help_all: bool,
+
+    /// Also write the summary printed at the end of the run to a file
+    #[arg(long, global = true, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    summary_file: Option<PathBuf>,
+
+    /// Overwrite existing output files instead of skipping them (input files are never overwritten)
+    #[arg(long, global = true)]
+    force: bool,
+
+    /// Format of the warnings and errors about the input files
+    #[arg(long, global = true, value_enum, default_value_t = DiagnosticsFormat::Text)]
+    diagnostics_format: DiagnosticsFormat,
+
+    /// Write the files generated in the run into a folder instead of the working directory
+    #[arg(long, global = true, value_name = "DIR", value_hint = ValueHint::DirPath)]
+    output_dir: Option<PathBuf>,
+
+    /// Write all documents generated in the run one after the other into a single file
+    #[arg(long, global = true, value_name = "FILE", value_hint = ValueHint::FilePath, conflicts_with = "output_dir")]
+    single_file: Option<PathBuf>,
+
+    /// Write all documents generated in the run to standard output, one after the other
+    #[arg(long, global = true, conflicts_with_all = ["output_dir", "single_file"])]
+    stdout: bool,
+
+    /// Collect the files generated in the run into a zip archive with a manifest, instead of
+    /// leaving them in place
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        conflicts_with_all = ["output_dir", "single_file", "stdout"]
+    )]
+    archive: Option<PathBuf>,
 }
 
-#[derive(Args, Debug)]
+#[derive(Args, Clone, Debug)]
 struct CommonArgs {
-    /// Files to process
+    /// Files to process, also FSH files and SUSHI project folders, built with SUSHI first
+    #[arg(value_hint = ValueHint::AnyPath)]
     files: Vec<PathBuf>,
+
+    /// SUSHI command building the FSH input
+    #[arg(long, default_value = "sushi", value_hint = ValueHint::CommandName)]
+    sushi: PathBuf,
+
+    /// FHIR version of the structure definitions, instead of the one given by their fhirVersion
+    #[arg(long, value_enum)]
+    fhir_version: Option<FhirVersion>,
+
+    /// Language of the element texts, taken from their translation extensions when present, and of
+    /// the generated headers and labels (en, sv)
+    #[arg(long)]
+    lang: Option<String>,
+
+    /// Consolidate profiles on profiles into the last one, with the tightest cardinality, the types
+    /// allowed by all and the strongest binding of each element along the derivation chain
+    #[arg(long)]
+    flatten: bool,
+
+    /// Only include the elements that a structure definition adds or constrains compared to its
+    /// loaded base, and their ancestors
+    #[arg(long, conflicts_with = "flatten")]
+    changed_only: bool,
+
+    /// Only include the elements flagged as summary (isSummary), and their ancestors
+    #[arg(long)]
+    summary_only: bool,
+
+    /// Only include the must-support elements, and their ancestors
+    #[arg(long)]
+    must_support_only: bool,
+
+    /// Only include the element with this id, its descendants and ancestors, e.g.
+    /// EHDSPatient.name, leaving out the structure definitions without it
+    #[arg(long, value_name = "ELEMENT")]
+    subtree: Option<String>,
+
+    /// Byte-identical output for identical input: files are processed sorted by path, whatever
+    /// order the shell expands them in, and the summary leaves out the elapsed time
+    #[arg(long)]
+    deterministic: bool,
+
+    /// Leave content out of all outputs, e.g. definitions,bindings, keeping the elements, their
+    /// cardinalities and datatypes
+    #[arg(long, value_enum, value_delimiter = ',')]
+    redact: Vec<Redaction>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Redaction {
+    /// Definitions, replaced by the short descriptions, and the descriptions of the resources
+    Definitions,
+    /// Requirements of the elements
+    Requirements,
+    /// Human descriptions of the invariants, keeping their keys and severities
+    Constraints,
+    /// Targets of the concept map mappings and of the structure map rules, keeping their sources
+    Mappings,
+    /// Binding descriptions, keeping the value sets and strengths
+    Bindings,
+    /// Bound value sets and their codes, keeping the strengths
+    ValueSets,
+    /// Obligations and their documentation
+    Obligations,
 }
 
 #[derive(Subcommand, Debug)]
@@ -40,6 +208,61 @@ enum Commands {
     Table(TableArgs),
     /// Generate a markdown table in a single file based on obligations of a structure definition
     Obligations(ObligationsArgs),
+    /// Generate an mdBook source tree or static HTML site with a chapter per structure definition
+    Site(SiteArgs),
+    /// Generate a single self-contained HTML data dictionary with client-side search
+    HtmlDictionary(HtmlDictionaryArgs),
+    /// Export the parsed structure definitions and their element trees as JSON
+    Json(JsonArgs),
+    /// Generate a markdown matrix comparing the elements of profiles on the same base
+    Compare(CompareArgs),
+    /// Generate a markdown report of where each structure definition and value set is used
+    WhereUsed(WhereUsedArgs),
+    /// Generate a markdown report of the changes in the model since a git revision
+    Diff(DiffArgs),
+    /// Generate a single markdown data dictionary with tables, diagrams, bindings and an element index
+    Dictionary(DictionaryArgs),
+    /// Generate a JSON instance skeleton in a separate file for each structure definition
+    Example(ExampleArgs),
+    /// Generate pseudo-random instances in a separate file for each structure definition
+    Testdata(TestDataArgs),
+    /// Generate a list of FHIRPath expressions selecting the leaf elements of each structure definition
+    Fhirpath(FhirPathArgs),
+    /// Generate a CSV file with a row per element path, to fill in with mappings
+    Paths(PathsArgs),
+    /// Generate a CSV or NDJSON file with a row per attribute of each element, for analytics databases
+    Attributes(AttributesArgs),
+    /// Generate a PlantUML activity diagram and a markdown rule listing for each StructureMap
+    StructureMap(StructureMapArgs),
+    /// Generate a mapping table of source and target codes for each ConceptMap
+    ConceptMap(ConceptMapArgs),
+    /// Generate a markdown table of the search parameters of each resource type
+    SearchParameters(SearchParametersArgs),
+    /// Generate a plantUml object diagram of example instances, with links for their references
+    InstanceDiagram(InstanceDiagramArgs),
+    /// Generate an openEHR ADL 2 archetype in a separate file for each logical model (experimental)
+    Adl(AdlArgs),
+    /// Generate a printable PDF per structure definition, or a combined one, through Typst
+    Pdf(PdfArgs),
+    /// Generate an Emacs org-mode document in a separate file for each structure definition
+    Org(OrgArgs),
+    /// Generate a markdown report of complexity measures per structure definition
+    Metrics(MetricsArgs),
+    /// Export the structure definitions and their relations as an ArchiMate model in the Open Exchange format
+    Archimate(ArchimateArgs),
+    /// Browse the structure definitions in the terminal, and export element subtrees to a generator
+    Browse(BrowseArgs),
+    /// Serve a static HTML site of the structure definitions on localhost, regenerated when they change
+    Serve(ServeArgs),
+    /// Run the generators listed in a TOML manifest, with options per input
+    #[command(after_long_help = batch::MANIFEST_KEYS)]
+    Batch(BatchArgs),
+    /// Run several generators on the structure definitions, loaded once
+    #[command(after_long_help = pipeline::CONFIG_KEYS)]
+    All(AllArgs),
+    /// Print a shell completion script
+    #[command(hide = true)]
+    Completions(CompletionsArgs),
 }
 
 #[derive(Args, Debug)]
@@ -55,9 +278,86 @@ struct PlantUmlArgs {
     #[arg(short, long)]
     cardinality_hide: bool,
 
+    /// Hide stereotypes
+    #[arg(long)]
+    stereotype_hide: bool,
+
+    /// Keep elements in the class box even when they are drawn as a relation
+    #[arg(short, long)]
+    keep_related_elements: bool,
+
     /// Output tile name
     #[arg(short, long, default_value = "output.plantuml")]
     output_file: PathBuf,
+
+    /// Write each class and its outgoing relations into a separate file
+    #[arg(short, long)]
+    split: bool,
+
+    /// Style file to include in each diagram instead of the built-in skin parameters
+    #[arg(short, long)]
+    include_style: Option<PathBuf>,
+
+    /// Group classes into packages
+    #[arg(short, long, value_enum)]
+    group_by: Option<GroupBy>,
+
+    /// JSON file mapping structure definition ids to package names, used with `--group-by mapping`
+    #[arg(short = 'm', long)]
+    group_map: Option<PathBuf>,
+
+    /// Show must-support elements in bold
+    #[arg(short, long)]
+    bold_must_support: bool,
+
+    /// Color used for modifier elements, e.g. red or #FF8800
+    #[arg(long)]
+    modifier_color: Option<String>,
+
+    /// Marker added after the name of required elements (min >= 1)
+    #[arg(long)]
+    required_marker: Option<String>,
+
+    /// Summarize bound elements in a note next to each class or at the bottom of the class box
+    #[arg(long, value_enum)]
+    bindings: Option<BindingDisplay>,
+
+    /// Draw the base of each structure definition as a superclass, also when it is not among the files
+    #[arg(long)]
+    show_base: bool,
+
+    /// Draw elements with children, like backbone elements, as classes of their own
+    #[arg(short, long)]
+    nested_classes: bool,
+
+    /// Add a legend explaining the notation used in the diagram
+    #[arg(long)]
+    legend: bool,
+
+    /// Add a note above each class with the metadata of the structure definition: url, version,
+    /// status, date, publisher, description, derivation and base
+    #[arg(long)]
+    metadata: bool,
+}
+
+#[derive(ValueEnum, Clone, Debug, PartialEq)]
+enum BindingDisplay {
+    /// Note to the right of the class
+    Note,
+    /// Separate section at the bottom of the class box
+    Box,
+}
+
+#[derive(ValueEnum, Clone, Debug, PartialEq)]
+enum GroupBy {
+    /// Directory of the structure definition file
+    Directory,
+    /// Canonical URL up to the StructureDefinition part
+    UrlPrefix,
+    /// Resource, datatype or logical model
+    Kind,
+    /// Explicit mapping file
+    Mapping,
 }
 
 #[derive(Args, Debug)]
@@ -71,9 +371,78 @@ struct MindmapArgs {
     /// Whether to add a link to the structure definition in the mind map
     #[arg(short, long)]
     link: bool,
+
+    /// Show cardinality after the element name
+    #[arg(short = 'c', long)]
+    show_cardinality: bool,
+
+    /// Show short description after the element name
+    #[arg(short = 's', long)]
+    show_short: bool,
+
+    /// Show the short description on a second line of each node instead, as a note in FreeMind and OPML
+    #[arg(long, conflicts_with = "show_short")]
+    short_line: bool,
+
+    /// Node colors per hierarchical level, starting with the root, e.g. Orange,#AADDFF
+    #[arg(short, long, value_delimiter = ',')]
+    depth_colors: Vec<String>,
+
+    /// Node color for must-support elements, overrides the level color
+    #[arg(long)]
+    must_support_color: Option<String>,
+
+    /// Node color for required elements (min >= 1), overrides the level and must-support colors
+    #[arg(long)]
+    required_color: Option<String>,
+
+    /// Generate a single mind map with the given title and one branch per structure definition
+    #[arg(long)]
+    combined: Option<String>,
+
+    /// In a combined mind map, place structure definitions below the loaded definition they are based on
+    #[arg(long, requires = "combined")]
+    by_base: bool,
+
+    /// Output format of the mind map
+    #[arg(short, long, value_enum, default_value_t = MindmapFormat::PlantUml)]
+    format: MindmapFormat,
+
+    /// Side of the root the branches of a PlantUML mind map are placed on
+    #[arg(long, value_enum, default_value_t = MindmapDirection::Right)]
+    direction: MindmapDirection,
+
+    /// With `--direction both`, the top-level branch, counted from 1, from which the branches are
+    /// on the left side instead of the second half
+    #[arg(long)]
+    left_from: Option<usize>,
+
+    #[command(flatten)]
+    naming: NamingArgs,
+}
+
+#[derive(ValueEnum, Clone, Debug, PartialEq)]
+enum MindmapFormat {
+    /// PlantUML mind map
+    PlantUml,
+    /// FreeMind/Freeplane .mm file
+    #[value(name = "freemind")]
+    FreeMind,
+    /// OPML outline
+    Opml,
+}
+
+#[derive(ValueEnum, Clone, Debug, PartialEq)]
+enum MindmapDirection {
+    Right,
+    Left,
+    /// Top-level branches split between the right and the left side
+    Both,
 }
 
 #[derive(Args, Debug)]
+// -l of the tables, kept when --lang became a common option, as mindmap and dictionary use it for links
+#[command(mut_arg("lang", |arg| arg.short('l')))]
 struct TableArgs {
     #[command(flatten)]
     common: CommonArgs,
@@ -85,6 +454,83 @@ struct TableArgs {
     /// Prefix used for code generation
     #[arg(short, long, default_value = "A")]
     prefix_code: String,
+
+    /// Code prefix per structure definition id, e.g. EHDSPatient=PAT,EHDSObservation=OBS
+    #[arg(short = 'm', long, value_delimiter = ',')]
+    prefix_map: Vec<String>,
+
+    /// Numbering scheme of the generated codes
+    #[arg(short, long, value_enum, default_value_t = NumberingScheme::Dotted)]
+    numbering: NumberingScheme,
+
+    /// JSON file mapping element ids to codes from an earlier release, kept stable in the output
+    #[arg(long)]
+    codes_file: Option<PathBuf>,
+
+    /// Write the codes of all elements to a JSON file for use with --codes-file
+    #[arg(short, long)]
+    write_codes: Option<PathBuf>,
+
+    /// Emit a heading and sub-table per element with children instead of one flat table
+    #[arg(short, long)]
+    sections: bool,
+
+    /// Add a flags column with the must-support (S), modifier (?!) and summary (Σ) flags
+    #[arg(short, long)]
+    flags: bool,
+
+    /// How markdown in descriptions and bindings is handled
+    #[arg(long, value_enum, default_value_t = TextMode::Escape)]
+    text_mode: TextMode,
+
+    /// Truncate descriptions and bindings to this number of characters
+    #[arg(long)]
+    truncate: Option<usize>,
+
+    /// Link target of datatypes that are other processed structure definitions, {id} is replaced by the id
+    #[arg(long, default_value = "{id}.md")]
+    link_pattern: String,
+
+    /// Do not link datatypes to other processed structure definitions
+    #[arg(long)]
+    links_hide: bool,
+
+    /// JSON file with label translations, overriding the built-in ones
+    #[arg(short, long)]
+    translation_file: Option<PathBuf>,
+
+    /// Add a source column with the loaded profile that introduced or last constrained each element
+    #[arg(long)]
+    provenance: bool,
+
+    /// Add a column with the obligations of each element per actor, e.g. "actor-full: SHALL:populate"
+    #[arg(long)]
+    obligations: bool,
+
+    /// Only show the elements with obligations for this actor, given by canonical URL or its last
+    /// part, and only its obligations
+    #[arg(long, requires = "obligations")]
+    actor: Option<String>,
+
+    /// Start each table with a table of contents linking to the top-level elements and the
+    /// elements with children
+    #[arg(long)]
+    toc: bool,
+
+    /// Start each file with the metadata of the structure definition as YAML front matter: url,
+    /// version, status, date, publisher, description, derivation and base
+    #[arg(long)]
+    metadata: bool,
+
+    /// Show bindings as numbered footnotes below each table, with the value set name, canonical
+    /// URL, version and strength
+    #[arg(long)]
+    binding_footnotes: bool,
+
+    /// List the codes (code — display) of required bindings in the binding cell when the value
+    /// set is among the files and has at most this number of codes
+    #[arg(long, value_name = "MAX_CODES")]
+    inline_codes: Option<usize>,
 }
 
 #[derive(Args, Debug)]
@@ -97,390 +543,906 @@ struct ObligationsArgs {
     prefix_code: String,
 
     /// Folder with actor definitions
-    #[arg(short, long)]
+    #[arg(short, long, value_hint = ValueHint::DirPath)]
     actors_folder: Option<PathBuf>,
 
     /// If only obligations should be included in table
     #[arg(short, long)]
     only_obligations: bool,
-}
 
-#[derive(Debug, Clone, Display, Hash, PartialEq, Eq, PartialOrd, Ord)]
-struct ElementInfo {
-    id: String,
-    short: String,
-    definition: String,
-    datatype: Vec<String>,
-    min: String,
-    max: String,
-    global_min: String,
-    global_max: String,
-    binding: Option<String>,
-    binding_strength: Option<String>,
-    obligation: Vec<(String, String, String)>,
-    requirements: Option<String>,
+    #[command(flatten)]
+    naming: NamingArgs,
 }
 
-struct StructureDefTreeInfo {
-    id: String,
-    base: String,
-    element_tree: Tree<ElementInfo>,
+#[derive(Args, Debug)]
+struct SiteArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// Title of the site
+    #[arg(short, long, default_value = "Data model")]
+    title: String,
+
+    /// Generate plain static HTML instead of an mdBook source tree
+    #[arg(long)]
+    html: bool,
+
+    /// Start each page with the metadata of the structure definition: url, version, status, date,
+    /// publisher, description, derivation and base
+    #[arg(long)]
+    metadata: bool,
+
+    /// Render the class diagrams of the pages with this PlantUML executable, run as `plantuml -tsvg
+    /// -pipe`, instead of showing their PlantUML source only
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::CommandName, conflicts_with = "plantuml_server", requires = "html")]
+    plantuml: Option<PathBuf>,
+
+    /// Show the class diagrams of the pages as images rendered by a PlantUML server, e.g.
+    /// https://www.plantuml.com/plantuml, instead of showing their PlantUML source only
+    #[arg(long, value_name = "URL", requires = "html")]
+    plantuml_server: Option<String>,
 }
 
-trait SearchableTree<T> {
-    fn find_first<F>(&self, predicate: F) -> Option<usize>
-    where
-        F: Fn(&T) -> bool;
+#[derive(Args, Debug)]
+struct HtmlDictionaryArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// Output file name
+    #[arg(short, long, default_value = "dictionary.html")]
+    output_file: PathBuf,
+
+    /// Title of the data dictionary
+    #[arg(short, long, default_value = "Data dictionary")]
+    title: String,
 }
 
-impl SearchableTree<ElementInfo> for Tree<ElementInfo> {
-    fn find_first<F>(&self, predicate: F) -> Option<usize>
-    where
-        F: Fn(&ElementInfo) -> bool,
-    {
-        for node in self.iter() {
-            if predicate(node.1) {
-                return Some(node.0);
-            }
-        }
-        None
-    }
+#[derive(Args, Debug)]
+struct JsonArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// Output file name
+    #[arg(short, long, default_value = "model.json")]
+    output_file: PathBuf,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
+#[derive(Args, Debug)]
+struct DiffArgs {
+    #[command(flatten)]
+    common: CommonArgs,
 
-    match cli.command {
-        Commands::Table(args) => {
-            // first load all structure definitions into in-memory structs
-            let docs = load_structure_definition_files(&args.common.files)?;
-            let alpha_index_code = args.prefix_code == "A";
-            for (doc_num, doc) in docs.iter().enumerate() {
-                let prefix = if alpha_index_code {
-                    generate_code(doc_num)
-                } else {
-                    args.prefix_code.clone()
-                };
+    /// Git revision to compare the working tree with, e.g. the tag of the last release
+    #[arg(long, value_name = "REV")]
+    git: String,
 
-                let _base = ();
+    /// Output file name
+    #[arg(short, long, default_value = "changes.md")]
+    output_file: PathBuf,
+}
 
-                println!("processing: {}", doc.id);
-                let output = File::create(format!("{}.md", doc.id))?;
-                let mut writer = BufWriter::new(output); // Create a buffered writer
+#[derive(Args, Debug)]
+struct CompareArgs {
+    #[command(flatten)]
+    common: CommonArgs,
 
-                writeln!(writer, "## {}", doc.id)?;
-                writeln!(
-                    writer,
-                    "| Level | Element Name | Element Description | Data type | Cardinality | Binding requirements | Relevance for support level \"full\" | Relevance for support level \"basic\" |\n|-------|---------------|---------------------|------------|--------------|----------------------|---|---|" 
-                    //"| Code | Path | Element | Description | Datatype | Cardinality | Global Cardinality | Preferred Code System | Requirements |"
-                )?;
-                // writeln!(
-                //     writer,
-                //     "| --- | --- | --- | --- | --- | --- | --- | --- | --- "
-                // )?;
+    /// Output file name
+    #[arg(short, long, default_value = "comparison.md")]
+    output_file: PathBuf,
 
-                let mut levels = Vec::<usize>::new();
-                levels.push(0);
-                let mut current_level: usize = 0;
-                // sorted_elements.sort_by(|a, b| a.id.cmp(&b.id));
+    /// Only include the element paths where the profiles differ
+    #[arg(short, long)]
+    differences_only: bool,
+}
 
-                // let mut log = vec![];
+#[derive(Args, Debug)]
+struct WhereUsedArgs {
+    #[command(flatten)]
+    common: CommonArgs,
 
-                doc.element_tree.traverse(
-                    |_idx, element, _| {
-                        let hier_level: usize = count_char_occurrences(&element.id, '.');
-                        let element_part: String = if hier_level > 0 {
-                            get_slice_after_last_occurrence(&element.id, '.').unwrap_or(element.id.clone())
-                        } else {
-                            element.id.clone()
-                        };
-                        // let element_path: String = if hier_level > 0 {
-                        //     get_slice_after_first_occurrence(&element.id, '.')
-                        //         .unwrap_or(element.id.clone())
-                        // } else {
-                        //     element.id.clone()
-                        // };
-                        // if (hier_level as isize - current_level as isize).abs() > 1 {
-                        //     return Err(format!("Hierarchical level difference is too large: {}", element.id).into());
-                        // }
-                        match hier_level.cmp(&current_level) {
-                            std::cmp::Ordering::Greater => {
-                                levels.push(1);
-                                current_level += 1;
-                            }
-                            std::cmp::Ordering::Less => {
-                                levels.pop();
-                                current_level -= 1;
-                                levels[current_level] += 1;
-                            }
-                            std::cmp::Ordering::Equal => {
-                                levels[current_level] += 1;
-                            }
-                        }
-                        
-                        let level = "+".repeat(hier_level);
+    /// Output file name
+    #[arg(short, long, default_value = "where-used.md")]
+    output_file: PathBuf,
+}
 
-                        let mut code = prefix.clone();
-                        for level in &levels[1..=current_level] {
-                            code.push('.');
-                            code.push_str(&level.to_string());
-                        }
+#[derive(Args, Debug)]
+struct DictionaryArgs {
+    #[command(flatten)]
+    common: CommonArgs,
 
-                        let description = if element.short == element.definition {
-                            element.short.clone()
-                        } else {
-                            format!(
-                                "{}<br/>{}",
-                                element.short,
-                                element.definition.replace("\n", "<br/>")
-                            )
-                        };
+    /// Output file name
+    #[arg(short, long, default_value = "dictionary.md")]
+    output_file: PathBuf,
 
-                        // let element_part_no_x = element_part.replace("[x]", "");
-                        write!(
-                            writer,
-                            "| {} | {} | {} |",
-                            level,
-                            element_part,
-                            // camel_to_spaced_pascal(&element_part_no_x),
-                            description
-                        ).unwrap_or(());
-
-                        if hier_level == 0 {
-                            write!(writer, " Derived from parent data type: {} | |", doc.base).unwrap_or(());
-                        } else {
-                            write!(writer, " {} | {}..{} |", reduce_datatypes(&element.datatype), element.min, element.max).unwrap_or(());
-                        }
+    /// Title of the data dictionary
+    #[arg(short, long, default_value = "Data dictionary")]
+    title: String,
 
-                        if let Some(binding) = &element.binding {
-                            write!(writer, " {} |", binding).unwrap_or(());
-                        } else {
-                            write!(writer, " |").unwrap_or(());
-                        }
+    /// Write the class diagrams to separate files next to the output and link them instead of embedding them
+    #[arg(short, long)]
+    link_diagrams: bool,
+}
 
-                        if let Some((_, code, _)) = element.obligation.iter().find(|o| o.0 == "https://ehds.eu/specifications/fhir/actor-full") {
-                            write!(writer, " {} |", match get_slice_before_first_occurrence(code, ':') {
-                                Some(s) => s,
-                                None => code.to_string(),
-                            }).unwrap_or(());
-                        } else {
-                            write!(writer, " | |").unwrap_or(());
-                        }
-                        if let Some((_, code, _)) = element.obligation.iter().find(|o| o.0 == "https://ehds.eu/specifications/fhir/actor-basic") {
-                            write!(writer, " {} |", match get_slice_before_first_occurrence(code, ':') {
-                                Some(s) => s,
-                                None => code.to_string(),
-                            }).unwrap_or(());
-                        } else {
-                            write!(writer, " | |").unwrap_or(());
-                        }
-                                                // if let Some(binding_strength) = &element.binding_strength {
-                        //     write!(writer, " {} |", binding_strength).unwrap();
-                        // } else {
-                        //     write!(writer, " |").unwrap();
-                        // }
-                        // if let Some(requirements) = &element.requirements {
-                        //     write!(writer, " {} |", requirements.replace("\n", "<br/>")).unwrap();
-                        // } else {
-                        //     write!(writer, " |").unwrap();
-                        // }
-                        // if let Some((actor, code, documentation)) = element.obligation.first() {
-                        //     write!(writer, " {} ({}) | {} |", actor, code, documentation.replace("\n", "<br/>")).unwrap();
-                        // } else {
-                        //     write!(writer, " | |").unwrap();
-                        // }
-                        writeln!(writer).unwrap_or(());
-                    },
-                    |_, _, _| (),
-                    &mut (),
-                );
-            }
-        }
-        Commands::PlantUml(args) => {
-            // first load all structure definitions into in-memory structs
-            let docs = load_structure_definition_files(&args.common.files)?;
-            let output = File::create(args.output_file)?;
-            let mut writer = BufWriter::new(output); // Create a buffered writer
+#[derive(Args, Debug)]
+struct ExampleArgs {
+    #[command(flatten)]
+    common: CommonArgs,
 
-            writeln!(
-                writer,
-                "@startuml\nskinparam linetype polyline\nhide circle\nhide stereotype\nhide methods\n"
-            )?;
+    /// Name of the file of each skeleton, {id} is replaced by the id of the structure definition
+    #[arg(short, long, default_value = "{id}-example.json")]
+    output_pattern: String,
+}
 
-            for doc in docs.iter() {
-                println!("processing: {}", doc.id);
-                writeln!(writer, "class **{}** {{", doc.id)?;
-                let mut relations = String::new();
+#[derive(Args, Debug)]
+struct TestDataArgs {
+    #[command(flatten)]
+    common: CommonArgs,
 
-                // let mut _element_number = 0;
+    /// Number of instances per structure definition
+    #[arg(short = 'n', long, default_value_t = 10)]
+    count: usize,
 
-                doc.element_tree.traverse(
-                    |_idx, element, _| {
-                        if let Some(element_part) =
-                            get_slice_after_last_occurrence(&element.id, '.')
-                            && element.max != "0"
-                        {
-                            let hier_level = count_char_occurrences(&element.id, '.') * 2;
-                            // if the datatype is one of the classes drawn, add a relation instead of a class element
-                            // TODO: element is removed from element list if there is one datatype that is among the structure definitions
-                            let mut show_this_element = true;
-                            if element_part.ends_with("[x]") {
-                                let element_part_no_x = element_part.replace("[x]", "");
-                                let choice: String = format!("{}{}", doc.id, element_part_no_x);
-                                let mut local_relations = String::new();
-                                for datatype in element.datatype.iter() {
-                                    // TODO: or use a hashmap for faster lookup
-                                    // TODO: look also for Reference(X or T)
-                                    if docs.iter().any(|d| datatype == &d.id) {
-                                        local_relations += &format!(
-                                            "{} .. \"**{}**\" : {} >\n",
-                                            choice, datatype, element_part_no_x
-                                        );
-                                        // will hide element if there is just one datatype that is another class in the diagram,
-                                        show_this_element = false; // do not show element if it is a choice
-                                    }
-                                }
-                                if !show_this_element {
-                                    relations += &format!("<> {}\n", choice);
-                                    relations += &format!(
-                                        "\"**{}**\" -- \"{}..{}\" {} : {} >\n",
-                                        doc.id, element.min, element.max, choice, element_part_no_x
-                                    );
-                                    relations += &local_relations;
-                                }
-                            } else {
-                                for datatype in element.datatype.iter() {
-                                    // TODO: or use a hashmap for faster lookup
-                                    // TODO: look also for Reference(X or T)
-                                    if docs.iter().any(|d| datatype == &d.id) {
-                                        relations += &format!(
-                                            "\"**{}**\" -- \"{}..{}\" \"**{}**\" : {} >\n",
-                                            doc.id,
-                                            element.global_min,
-                                            element.global_max,
-                                            datatype,
-                                            element_part
-                                        );
-                                        show_this_element = false; // do not show element if datatype is another class in the diagram
-                                    }
-                                }
-                            }
+    /// Seed of the pseudo-random values, the same seed gives the same instances
+    #[arg(short, long, default_value_t = 1)]
+    seed: u64,
 
-                            if show_this_element && !args.elements_hide {
-                                write!(
-                                    writer,
-                                    "{:>hier_level$}|_ {} : {}",
-                                    "",
-                                    element_part,
-                                    reduce_datatypes(&element.datatype)
-                                )
-                                .unwrap();
-                                if !args.cardinality_hide {
-                                    write!(writer, " [{}..{}]", element.min, element.max).unwrap();
-                                }
-                                writeln!(writer).unwrap();
-                            }
-                        }
-                    },
-                    |_, _, _| (),
-                    &mut (),
-                );
+    /// Name of the file of each structure definition, {id} is replaced by its id
+    #[arg(short, long, default_value = "{id}-testdata.json")]
+    output_pattern: String,
+}
 
-                writeln!(writer, "}}").unwrap();
+#[derive(Args, Debug)]
+struct FhirPathArgs {
+    #[command(flatten)]
+    common: CommonArgs,
 
-                write!(writer, "{}", relations).unwrap();
-            }
+    /// Output file name
+    #[arg(short, long, default_value = "selectors.txt")]
+    output_file: PathBuf,
+}
 
-            writeln!(writer, "@enduml")?;
-        }
-        Commands::Mindmap(mindmap_args) => {
-            // first load all structure definitions into in-memory structs
-            let docs = load_structure_definition_files(&mindmap_args.common.files)?;
-            for doc in docs.iter() {
-                println!("processing: {}", doc.id);
+#[derive(Args, Debug)]
+struct PathsArgs {
+    #[command(flatten)]
+    common: CommonArgs,
 
-                let model = if let Some(title) = doc.id.strip_suffix("Obligations") {
-                    title
-                } else {
-                    &doc.id
-                };
+    /// Output file name
+    #[arg(short, long, default_value = "paths.csv")]
+    output_file: PathBuf,
+}
 
-                let output = File::create(format!("{}_mindmap.plantuml", model))?;
-                let mut writer = BufWriter::new(output); // Create a buffered writer
+#[derive(Args, Debug)]
+struct AttributesArgs {
+    #[command(flatten)]
+    common: CommonArgs,
 
-                if mindmap_args.link {
-                    writeln!(
-                        writer,
-                        "@startmindmap\nskinparam dpi 200\nskinparam topurl StructureDefinition-\n\n* **[[{}.html {}]]**",
-                        model, model
-                    )?;
-                } else {
-                    writeln!(
-                        writer,
-                        "@startmindmap\nskinparam dpi 200\nskinparam topurl StructureDefinition-\n\n* **{}**",
-                        model
-                    )?;
-                }
+    /// Output file name
+    #[arg(short, long, default_value = "attributes.csv")]
+    output_file: PathBuf,
 
-                doc.element_tree.traverse(
-                    |_idx, element, _| {
-                        if let Some(element_part) =
-                            get_slice_after_last_occurrence(&element.id, '.')
-                            && element.max != "0"
-                        {
-                            let hier_level = count_char_occurrences(&element.id, '.') + 1;
+    /// Format of the rows, by default NDJSON for files named .ndjson or .jsonl and CSV otherwise
+    #[arg(short, long, value_enum)]
+    format: Option<AttributesFormat>,
+}
 
-                            writeln!(
-                                writer,
-                                "{}{} {}",
-                                "*".repeat(hier_level),
-                                if hier_level > mindmap_args.box_level {
-                                    "_"
-                                } else {
-                                    ""
-                                },
-                                camel_to_spaced_pascal(&element_part.replace("[x]", ""))
-                            )
-                            .unwrap();
-                        }
-                    },
-                    |_, _, _| (),
-                    &mut (),
-                );
+#[derive(ValueEnum, Clone, Debug, PartialEq)]
+enum AttributesFormat {
+    /// Comma-separated values with a header row
+    Csv,
+    /// A JSON object per line
+    Ndjson,
+}
 
-                writeln!(writer, "@endmindmap")?;
-            }
-        }
-        Commands::Obligations(args) => {
-            let actors = if let Some(actors_folder) = args.actors_folder {
-                load_actor_files(&actors_folder)?
-            } else {
-                HashMap::<String, String>::new()
-            };
+#[derive(Args, Debug)]
+struct StructureMapArgs {
+    #[command(flatten)]
+    common: CommonArgs,
 
-            let docs = load_structure_definition_files(&args.common.files)?;
-            for doc in docs.iter() {
-                println!("processing: {}", doc.id);
-                let output = File::create(format!("{}.html", doc.id))?;
-                let mut writer = BufWriter::new(output); // Create a buffered writer
+    /// Name of the files of each map without extension, {id} is replaced by the id of the map; the
+    /// diagram is written to .plantuml and the rule listing to .md
+    #[arg(short, long, default_value = "{id}_map")]
+    output_pattern: String,
+}
 
-                writeln!(
-                    writer,
-                    "<h1>{}</h1>",
-                    if !args.only_obligations
-                        && let Some(title) = doc.id.strip_suffix("Obligations")
-                    {
-                        title
-                    } else {
-                        &doc.id
-                    }
-                )?;
+#[derive(Args, Debug)]
+struct ConceptMapArgs {
+    #[command(flatten)]
+    common: CommonArgs,
 
-                let mut unique_actors = HashSet::<String>::new();
+    /// Name of the file of each map without extension, {id} is replaced by the id of the map
+    #[arg(short, long, default_value = "{id}_conceptmap")]
+    output_pattern: String,
 
-                // identify unique actors
-                doc.element_tree.traverse(
-                    |_idx, element, _| {
+    /// Format of the mapping tables
+    #[arg(short, long, value_enum, default_value_t = ConceptMapFormat::Markdown)]
+    format: ConceptMapFormat,
+}
+
+#[derive(ValueEnum, Clone, Debug, PartialEq)]
+enum ConceptMapFormat {
+    /// A table per source and target system
+    Markdown,
+    /// A row per mapping with the source and target systems as columns
+    Csv,
+}
+
+#[derive(Args, Debug)]
+struct SearchParametersArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// Name of the file of each resource type, {resource} is replaced by the type
+    #[arg(short, long, default_value = "{resource}_search.md")]
+    output_pattern: String,
+}
+
+#[derive(Args, Debug)]
+struct InstanceDiagramArgs {
+    /// Structure definitions of the instances, whose required, must-support and summary elements
+    /// are the fields shown
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// Instance, or bundle of instances, to draw
+    #[arg(short, long, required = true, value_hint = ValueHint::FilePath)]
+    instance: Vec<PathBuf>,
+
+    /// Show all fields of the instances instead of the key fields of their profiles
+    #[arg(short, long)]
+    all_fields: bool,
+
+    /// Output file name
+    #[arg(short, long, default_value = "instances.plantuml")]
+    output_file: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct AdlArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// Name of the file of each archetype, {id} is replaced by the id of the logical model
+    #[arg(short, long, default_value = "{id}.adl")]
+    output_pattern: String,
+}
+
+#[derive(Args, Debug)]
+struct PdfArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// Write a single PDF with this title, a table of contents and a chapter per structure definition
+    #[arg(short, long)]
+    combined: Option<String>,
+
+    /// Folder of rendered class diagrams, <id>.svg or <id>.png, embedded before the tables
+    #[arg(short, long, value_hint = ValueHint::DirPath)]
+    diagrams_dir: Option<PathBuf>,
+
+    /// Only write the Typst sources, without compiling them to PDF
+    #[arg(short, long)]
+    source_only: bool,
+
+    /// Typst executable compiling the sources
+    #[arg(long, default_value = "typst", value_hint = ValueHint::CommandName)]
+    typst: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct OrgArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// Name of the file of each document, {id} is replaced by the id of the structure definition
+    #[arg(short, long, default_value = "{id}.org")]
+    output_pattern: String,
+}
+
+#[derive(Args, Debug)]
+struct MetricsArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// Output file name
+    #[arg(short, long, default_value = "metrics.md")]
+    output_file: PathBuf,
+
+    /// Also write the measures to a JSON file
+    #[arg(short, long)]
+    json_file: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct ArchimateArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// Output file name
+    #[arg(short, long, default_value = "model.xml")]
+    output_file: PathBuf,
+
+    /// Name of the model
+    #[arg(short, long, default_value = "Data model")]
+    title: String,
+}
+
+#[derive(Args, Debug)]
+struct BrowseArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+#[derive(Args, Debug)]
+struct ServeArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// Port on localhost, 0 for any free one
+    #[arg(short, long, default_value_t = 8000)]
+    port: u16,
+
+    /// Title of the site
+    #[arg(short, long, default_value = "Data model")]
+    title: String,
+
+    /// Render the class diagrams of the pages with this PlantUML executable, run as `plantuml -tsvg
+    /// -pipe`, instead of showing their PlantUML source only
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::CommandName, conflicts_with = "plantuml_server")]
+    plantuml: Option<PathBuf>,
+
+    /// Show the class diagrams of the pages as images rendered by a PlantUML server, e.g.
+    /// https://www.plantuml.com/plantuml, instead of showing their PlantUML source only
+    #[arg(long, value_name = "URL")]
+    plantuml_server: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct BatchArgs {
+    /// Manifest listing the inputs, their generators and options
+    #[arg(value_hint = ValueHint::FilePath)]
+    manifest: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct AllArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// Generators to run, e.g. plant-uml,table,mindmap, instead of those listed in the config file
+    #[arg(short, long, value_delimiter = ',', value_name = "GENERATORS")]
+    with: Vec<String>,
+
+    /// TOML file with the generators to run and a table of options per generator
+    #[arg(short, long, value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct CompletionsArgs {
+    /// Shell to complete in
+    #[arg(value_enum)]
+    shell: clap_complete::Shell,
+}
+
+#[derive(Debug, Clone, Display, Hash, PartialEq, Eq, PartialOrd, Ord)]
+struct ElementInfo {
+    id: Rc<str>,
+    /// level in the element tree, 0 for the root; slices are one level below the element they slice
+    depth: usize,
+    short: Rc<str>,
+    definition: Rc<str>,
+    datatype: Vec<Rc<str>>,
+    reference: Vec<Rc<str>>,
+    min: Rc<str>,
+    max: Rc<str>,
+    global_min: Rc<str>,
+    global_max: Rc<str>,
+    binding: Option<String>,
+    binding_strength: Option<Rc<str>>,
+    value_set: Option<Rc<str>>,
+    value_set_title: Option<String>,
+    /// system, code and display of the concepts of the value set, when it is among the loaded
+    /// files, shared by the elements bound to it
+    value_set_codes: Rc<[(String, String, String)]>,
+    /// type and JSON of the fixed or pattern value, e.g. ("Code", "\"final\"")
+    fixed_value: Option<(String, String)>,
+    obligation: Vec<(String, String, String)>,
+    /// key, severity and human description of the invariants, e.g. ("ele-1", "error", "All FHIR
+    /// elements must have a @value or children")
+    constraints: Vec<(String, String, String)>,
+    requirements: Option<String>,
+    must_support: bool,
+    is_modifier: bool,
+    is_summary: bool,
+}
+
+#[derive(Clone)]
+struct StructureDefTreeInfo {
+    id: String,
+    url: String,
+    // metadata, empty when not given
+    version: String,
+    status: String,
+    date: String,
+    publisher: String,
+    description: String,
+    kind: String,
+    derivation: String,
+    sd_type: String,
+    base: String,
+    is_abstract: bool,
+    // R5 interfaces, abstract resources like CanonicalResource
+    is_interface: bool,
+    fhir_version: FhirVersion,
+    /// type and expression of the contexts an extension is used in, e.g. ("element", "Patient")
+    contexts: Vec<(String, String)>,
+    source: PathBuf,
+    element_tree: Tree<ElementInfo>,
+}
+
+/// The metadata of a structure definition that is given, identifying the version documented, e.g.
+/// ("version", "0.1.0")
+fn structure_metadata(doc: &StructureDefTreeInfo) -> Vec<(&'static str, &str)> {
+    [
+        ("url", doc.url.as_str()),
+        ("version", &doc.version),
+        ("status", &doc.status),
+        ("date", &doc.date),
+        ("publisher", &doc.publisher),
+        ("description", &doc.description),
+        ("derivation", &doc.derivation),
+        ("base", &doc.base),
+    ]
+    .into_iter()
+    .filter(|(_, value)| !value.is_empty())
+    .collect()
+}
+
+/// Default values of a subcommand's options, as if it was given no arguments
+fn default_args<T: Args + FromArgMatches>() -> T {
+    let command = T::augment_args(clap::Command::new("defaults"));
+    T::from_arg_matches(&command.get_matches_from(["defaults"])).expect("all options have defaults")
+}
+
+impl CommonArgs {
+    /// The first of the input files and options on them that is given, e.g. "--lang", for the
+    /// commands that give them once to several generators
+    fn given_option(&self) -> Option<&'static str> {
+        [
+            ("the input files", !self.files.is_empty()),
+            ("--sushi", self.sushi != Path::new("sushi")),
+            ("--fhir-version", self.fhir_version.is_some()),
+            ("--lang", self.lang.is_some()),
+            ("--flatten", self.flatten),
+            ("--changed-only", self.changed_only),
+            ("--summary-only", self.summary_only),
+            ("--must-support-only", self.must_support_only),
+            ("--subtree", self.subtree.is_some()),
+            ("--deterministic", self.deterministic),
+            ("--redact", !self.redact.is_empty()),
+        ]
+        .into_iter()
+        .find_map(|(option, given)| given.then_some(option))
+    }
+}
+
+impl MindmapArgs {
+    /// The first option given that only PlantUML mind maps have, e.g. "--link", as FreeMind and
+    /// OPML files are written without links, colors, boxes or sides
+    fn plantuml_option(&self) -> Option<&'static str> {
+        [
+            ("--box-level", self.box_level != 255),
+            ("--link", self.link),
+            ("--depth-colors", !self.depth_colors.is_empty()),
+            ("--must-support-color", self.must_support_color.is_some()),
+            ("--required-color", self.required_color.is_some()),
+            ("--direction", self.direction != MindmapDirection::Right),
+            ("--left-from", self.left_from.is_some()),
+        ]
+        .into_iter()
+        .find_map(|(option, given)| given.then_some(option))
+    }
+}
+
+impl Commands {
+    /// Whether the generator follows element types or bases into other structure definitions, where
+    /// circular references among them are reported
+    fn follows_types(&self) -> bool {
+        matches!(self, Commands::Mindmap(_) | Commands::Example(_) | Commands::Testdata(_))
+    }
+
+    /// Whether a generated document is written to standard output
+    fn writes_to_stdout(&self) -> bool {
+        let stdout = Path::new("-");
+        match self {
+            Commands::PlantUml(args) => !args.split && args.output_file == stdout,
+            Commands::Mindmap(_) => false,
+            Commands::Table(_) => false,
+            Commands::Obligations(_) => false,
+            Commands::Site(_) => false,
+            Commands::HtmlDictionary(args) => args.output_file == stdout,
+            Commands::Json(args) => args.output_file == stdout,
+            Commands::Compare(args) => args.output_file == stdout,
+            Commands::WhereUsed(args) => args.output_file == stdout,
+            Commands::Diff(args) => args.output_file == stdout,
+            Commands::Dictionary(args) => args.output_file == stdout,
+            Commands::Example(_) => false,
+            Commands::Testdata(_) => false,
+            Commands::Fhirpath(args) => args.output_file == stdout,
+            Commands::Paths(args) => args.output_file == stdout,
+            Commands::Attributes(args) => args.output_file == stdout,
+            Commands::StructureMap(_) => false,
+            Commands::ConceptMap(_) => false,
+            Commands::SearchParameters(_) => false,
+            Commands::InstanceDiagram(args) => args.output_file == stdout,
+            Commands::Adl(_) => false,
+            Commands::Pdf(_) => false,
+            Commands::Org(_) => false,
+            Commands::Metrics(args) => args.output_file == stdout || args.json_file.as_deref() == Some(stdout),
+            Commands::Archimate(args) => args.output_file == stdout,
+            Commands::Browse(_) => false,
+            Commands::Serve(_) => false,
+            Commands::Batch(_) => false,
+            Commands::All(_) => false,
+            Commands::Completions(_) => true,
+        }
+    }
+
+    /// The options of a generator from the structure definitions, none for the other commands
+    fn common_mut(&mut self) -> Option<&mut CommonArgs> {
+        match self {
+            Commands::PlantUml(args) => Some(&mut args.common),
+            Commands::Mindmap(args) => Some(&mut args.common),
+            Commands::Table(args) => Some(&mut args.common),
+            Commands::Obligations(args) => Some(&mut args.common),
+            Commands::Site(args) => Some(&mut args.common),
+            Commands::HtmlDictionary(args) => Some(&mut args.common),
+            Commands::Json(args) => Some(&mut args.common),
+            Commands::Compare(args) => Some(&mut args.common),
+            Commands::WhereUsed(args) => Some(&mut args.common),
+            Commands::Dictionary(args) => Some(&mut args.common),
+            Commands::Example(args) => Some(&mut args.common),
+            Commands::Testdata(args) => Some(&mut args.common),
+            Commands::Fhirpath(args) => Some(&mut args.common),
+            Commands::Paths(args) => Some(&mut args.common),
+            Commands::Attributes(args) => Some(&mut args.common),
+            Commands::Adl(args) => Some(&mut args.common),
+            Commands::Pdf(args) => Some(&mut args.common),
+            Commands::Org(args) => Some(&mut args.common),
+            Commands::Metrics(args) => Some(&mut args.common),
+            Commands::Archimate(args) => Some(&mut args.common),
+            Commands::Diff(_)
+            | Commands::StructureMap(_)
+            | Commands::ConceptMap(_)
+            | Commands::SearchParameters(_)
+            | Commands::InstanceDiagram(_)
+            | Commands::Browse(_)
+            | Commands::Serve(_)
+            | Commands::Batch(_)
+            | Commands::All(_)
+            | Commands::Completions(_) => None,
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    let cli = Cli::parse();
+    diagnostics::set_format(cli.diagnostics_format);
+    output::set_force(cli.force);
+    output::set_target(output_target(&cli));
+
+    if let Some(dir) = &cli.generate_man {
+        return cli_reference::write_man_pages(Cli::command(), dir);
+    }
+    if cli.help_all {
+        cli_reference::print_help_all(Cli::command());
+        return Ok(());
+    }
+    let Some(command) = cli.command else {
+        Cli::command().print_help()?;
+        return Ok(());
+    };
+    if cli.stdout || command.writes_to_stdout() {
+        summary::set_output_to_stdout();
+    }
+    // the completion script is printed alone, without a summary
+    let completions = matches!(command, Commands::Completions(_));
+
+    let result = run(command).and_then(|()| match &cli.archive {
+        Some(archive) => archive::write_archive(archive, &output::outputs()),
+        None => Ok(()),
+    });
+
+    // a failed run is summed up too, with what it had read and written before the error
+    let summary = if completions { Ok(()) } else { summary::report(started, cli.summary_file.as_deref()) };
+    result?;
+    summary?;
+    Ok(())
+}
+
+/// Where the documents go by the global options
+fn output_target(cli: &Cli) -> OutputTarget {
+    if cli.stdout {
+        OutputTarget::Stdout
+    } else if let Some(single_file) = &cli.single_file {
+        OutputTarget::SingleFile(single_file.clone())
+    } else if let Some(output_dir) = &cli.output_dir {
+        OutputTarget::Directory(output_dir.clone())
+    } else {
+        OutputTarget::Files
+    }
+}
+
+/// Runs a subcommand
+fn run(mut command: Commands) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Commands::Diff(args) => {
+            diff::generate_diff(&args)?;
+        }
+        Commands::StructureMap(args) => {
+            structure_map::generate_structure_maps(&args)?;
+        }
+        Commands::ConceptMap(args) => {
+            concept_map::generate_concept_maps(&args)?;
+        }
+        Commands::SearchParameters(args) => {
+            search_parameter::generate_search_parameters(&args)?;
+        }
+        Commands::InstanceDiagram(args) => {
+            instance_diagram::generate_instance_diagram(&args)?;
+        }
+        #[cfg(unix)]
+        Commands::Browse(args) => {
+            browse::browse(&args)?;
+        }
+        // the terminal is driven with termios
+        #[cfg(not(unix))]
+        Commands::Browse(_) => return Err("browse needs a Unix-like terminal".into()),
+        Commands::Serve(args) => {
+            serve::serve(&args)?;
+        }
+        Commands::Batch(args) => {
+            batch::run_batch(&args)?;
+        }
+        Commands::All(args) => {
+            pipeline::run_all(&args)?;
+        }
+        Commands::Completions(args) => {
+            clap_complete::generate(args.shell, &mut Cli::command(), "fhir-generate", &mut std::io::stdout());
+        }
+        _ => {
+            let common = command.common_mut().expect("a generator from the structure definitions");
+            // first load all structure definitions into in-memory structs
+            let docs = load_structure_definition_files(common)?;
+            if command.follows_types() {
+                cycles::report_cycles(&docs);
+            }
+            generate(command, &docs)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs a generator on the loaded structure definitions
+fn generate(command: Commands, docs: &[StructureDefTreeInfo]) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Commands::Table(args) => {
+            let ctx = TableContext::new(&args, docs)?;
+            let mut all_codes = BTreeMap::<String, String>::new();
+            for (doc_num, doc) in docs.iter().enumerate() {
+                status!("processing: {}", doc.id);
+                let output = create_output_file(format!("{}.md", doc.id))?;
+                let mut writer = BufWriter::new(output); // Create a buffered writer
+
+                if args.metadata && output::has_own_files() {
+                    write_front_matter(&mut writer, doc)?;
+                }
+                let codes = write_table(&mut writer, doc, doc_num, &ctx)?;
+                all_codes.extend(
+                    doc.element_tree
+                        .iter()
+                        .filter_map(|(idx, element)| codes.get(&idx).map(|code| (element.id.to_string(), code.clone()))),
+                );
+            }
+
+            if let Some(write_codes) = &args.write_codes {
+                let output = create_output_file(write_codes)?;
+                serde_json::to_writer_pretty(BufWriter::new(output), &all_codes)?;
+            }
+        }
+        Commands::PlantUml(args) => {
+            let index = DocIndex::new(docs);
+            let package_map = if let Some(group_map) = &args.group_map {
+                load_string_map(group_map)?
+            } else if args.group_by == Some(GroupBy::Mapping) {
+                return Err("Grouping by mapping requires --group-map".into());
+            } else {
+                HashMap::<String, String>::new()
+            };
+
+            if args.split {
+                for doc in docs.iter() {
+                    status!("processing: {}", doc.id);
+                    let output = create_output_file(format!("{}.plantuml", doc.id))?;
+                    let mut writer = BufWriter::new(output); // Create a buffered writer
+
+                    write_plantuml_header(&mut writer, &args)?;
+                    let package = plantuml_package_of(doc, args.group_by.as_ref(), &package_map);
+                    if let Some(package) = &package {
+                        writeln!(writer, "package \"{}\" {{", package)?;
+                    }
+                    let relations = write_plantuml_class(&mut writer, doc, &index, &args)?;
+                    if package.is_some() {
+                        writeln!(writer, "}}")?;
+                    }
+                    // the base is not drawn in the file of a single class, even when loaded
+                    if shows_base(doc, &args) {
+                        writeln!(writer, "class **{}** {{\n}}", doc.base)?;
+                    }
+                    write!(writer, "{}", relations)?;
+                    write_plantuml_legend(&mut writer, &args)?;
+                    writeln!(writer, "@enduml")?;
+                }
+            } else {
+                let output = create_output_file(&args.output_file)?;
+                let mut writer = BufWriter::new(output); // Create a buffered writer
+
+                write_plantuml_header(&mut writer, &args)?;
+
+                // keep packages in the order they are first encountered
+                let mut packages = Vec::<(Option<String>, Vec<&StructureDefTreeInfo>)>::new();
+                for doc in docs.iter() {
+                    let package = plantuml_package_of(doc, args.group_by.as_ref(), &package_map);
+                    if let Some((_, members)) = packages.iter_mut().find(|(p, _)| *p == package) {
+                        members.push(doc);
+                    } else {
+                        packages.push((package, vec![doc]));
+                    }
+                }
+
+                // relations are written after all packages so that no class is implicitly declared outside its package
+                let mut relations = String::new();
+                for (package, members) in packages.iter() {
+                    if let Some(package) = package {
+                        writeln!(writer, "package \"{}\" {{", package)?;
+                    }
+                    for doc in members.iter() {
+                        status!("processing: {}", doc.id);
+                        relations += &write_plantuml_class(&mut writer, doc, &index, &args)?;
+                    }
+                    if package.is_some() {
+                        writeln!(writer, "}}")?;
+                    }
+                }
+                write!(writer, "{}", plantuml_base_classes(&index, &args))?;
+                write!(writer, "{}", relations)?;
+                write_plantuml_legend(&mut writer, &args)?;
+                writeln!(writer, "@enduml")?;
+            }
+        }
+        Commands::Mindmap(mindmap_args) => {
+            // element texts may be in a language without generated labels, the labels are then in English
+            let labels = Labels::load(mindmap_args.common.lang.as_deref().unwrap_or("en"), None).or_else(|_| Labels::load("en", None))?;
+            if mindmap_args.format != MindmapFormat::PlantUml {
+                if let Some(option) = mindmap_args.plantuml_option() {
+                    let format = mindmap_args.format.to_possible_value().expect("no skipped formats");
+                    return Err(format!("{} applies to PlantUML mind maps only, not to --format {}", option, format.get_name()).into());
+                }
+                let extension = if mindmap_args.format == MindmapFormat::FreeMind { "mm" } else { "opml" };
+                if let Some(title) = &mindmap_args.combined {
+                    let output = create_output_file(format!("{}_mindmap.{}", title, extension))?;
+                    let mut writer = BufWriter::new(output); // Create a buffered writer
+
+                    write_mindmap_xml_header(&mut writer, title, &mindmap_args)?;
+                    write_mindmap_xml_open(&mut writer, 0, title, None, &mindmap_args)?;
+                    let index = DocIndex::new(docs);
+                    let is_branch_root = |doc: &StructureDefTreeInfo| !mindmap_args.by_base || index.base_of(doc).is_none();
+                    let mut visited = HashSet::<String>::new();
+                    // definitions based on each other in a cycle have no branch root, they follow the others
+                    for doc in docs.iter().filter(|d| is_branch_root(d)).chain(docs.iter()) {
+                        write_mindmap_xml_branch(&mut writer, doc, &index, &mindmap_args, &labels, 1, &mut visited)?;
+                    }
+                    write_mindmap_xml_close(&mut writer, 0, &mindmap_args)?;
+                    write_mindmap_xml_footer(&mut writer, &mindmap_args)?;
+                } else {
+                    let index = DocIndex::new(docs);
+                    for doc in docs.iter() {
+                        let model = mindmap_model_name(doc);
+                        let output = create_output_file(format!("{}_mindmap.{}", model, extension))?;
+                        let mut writer = BufWriter::new(output); // Create a buffered writer
+
+                        write_mindmap_xml_header(&mut writer, model, &mindmap_args)?;
+                        let mut visited = HashSet::<String>::new();
+                        write_mindmap_xml_branch(&mut writer, doc, &index, &mindmap_args, &labels, 0, &mut visited)?;
+                        write_mindmap_xml_footer(&mut writer, &mindmap_args)?;
+                    }
+                }
+            } else if let Some(title) = &mindmap_args.combined {
+                let output = create_output_file(format!("{}_mindmap.plantuml", title))?;
+                let mut writer = BufWriter::new(output); // Create a buffered writer
+
+                writeln!(
+                    writer,
+                    "@startmindmap\nskinparam dpi 200\nskinparam topurl StructureDefinition-\n\n*{} **{}**",
+                    mindmap_color(mindmap_args.depth_colors.first()),
+                    title
+                )?;
+
+                // structure definitions derived from another loaded one are placed below it when merging by base
+                let index = DocIndex::new(docs);
+                let is_branch_root = |doc: &StructureDefTreeInfo| !mindmap_args.by_base || index.base_of(doc).is_none();
+                let mut visited = HashSet::<String>::new();
+                // each branch is written on its own first, so that they can be split between the sides
+                let mut branches = Vec::<Vec<u8>>::new();
+                // definitions based on each other in a cycle have no branch root, they follow the others
+                for doc in docs.iter().filter(|d| is_branch_root(d)).chain(docs.iter()) {
+                    let mut branch = Vec::<u8>::new();
+                    write_mindmap_branch(&mut branch, doc, &index, &mindmap_args, &labels, 2, &mut visited)?;
+                    if !branch.is_empty() {
+                        branches.push(branch);
+                    }
+                }
+                let left_from = mindmap_left_from(&mindmap_args, branches.len());
+                for (branch_num, branch) in branches.iter().enumerate() {
+                    if left_from == Some(branch_num) {
+                        writeln!(writer, "left side")?;
+                    }
+                    writer.write_all(branch)?;
+                }
+
+                writeln!(writer, "@endmindmap")?;
+            } else {
+                for doc in docs.iter() {
+                    status!("processing: {}", doc.id);
+
+                    let model = mindmap_model_name(doc);
+
+                    let output = create_output_file(format!("{}_mindmap.plantuml", model))?;
+                    let mut writer = BufWriter::new(output); // Create a buffered writer
+
+                    writeln!(
+                        writer,
+                        "@startmindmap\nskinparam dpi 200\nskinparam topurl StructureDefinition-\n"
+                    )?;
+                    write_mindmap_title(&mut writer, 1, model, &mindmap_args)?;
+                    write_mindmap_elements(&mut writer, doc, &mindmap_args, 0)?;
+
+                    writeln!(writer, "@endmindmap")?;
+                }
+            }
+        }
+        Commands::Obligations(args) => {
+            let actors = if let Some(actors_folder) = args.actors_folder {
+                load_actor_files(&actors_folder)?
+            } else {
+                HashMap::<String, String>::new()
+            };
+
+            for doc in docs.iter() {
+                status!("processing: {}", doc.id);
+                let output = create_output_file(format!("{}.html", doc.id))?;
+                let mut writer = BufWriter::new(output); // Create a buffered writer
+
+                writeln!(
+                    writer,
+                    "<h1>{}</h1>",
+                    if !args.only_obligations
+                        && let Some(title) = doc.id.strip_suffix("Obligations")
+                    {
+                        title
+                    } else {
+                        &doc.id
+                    }
+                )?;
+
+                // sorted, so that the actor columns are in the same order in each run
+                let mut unique_actors = BTreeSet::<String>::new();
+
+                // identify unique actors
+                doc.element_tree.traverse(
+                    |_idx, element, _| {
                         if !element.obligation.is_empty() {
                             for obligation in &element.obligation {
                                 unique_actors.insert(obligation.0.clone());
@@ -491,122 +1453,1477 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     &mut (),
                 );
 
-                write!(
-                    writer,
-                    "<table>\n<tr><th>Element</th><th>Description</th><th>Datatype</th><th>Cardinality</th><th>Preferred Code System</th>"
-                )?;
-                for actor in unique_actors.iter() {
-                    let actor_name = if let Some(name) = actors.get(actor) {
-                        name.clone()
+                write!(
+                    writer,
+                    "<table>\n<tr><th>Element</th><th>Description</th><th>Datatype</th><th>Cardinality</th><th>Preferred Code System</th>"
+                )?;
+                for actor in unique_actors.iter() {
+                    let actor_name = if let Some(name) = actors.get(actor) {
+                        name.clone()
+                    } else {
+                        get_slice_after_last_occurrence(actor, '/').ok_or("Wrong actor URL")?
+                    };
+                    write!(writer, "<th>{}</th>", actor_name)?;
+                }
+                writeln!(writer, "</tr>")?;
+
+                doc.element_tree.traverse(
+                    |_idx, element, _| {
+                        if !args.only_obligations || !element.obligation.is_empty() {
+                            let hier_level: usize = element.depth;
+                            // let element_path: String = if hier_level > 0 {
+                            //     get_slice_after_first_occurrence(&element.id, '.')
+                            //         .unwrap_or(element.id.clone())
+                            // } else {
+                            //     element.id.clone()
+                            // };
+                            // let element_path_no_x =
+                            //     element_path.strip_suffix("[x]").unwrap_or(&element_path);
+
+                            // write!(writer, "<tr><td>{}</td>", element_path_no_x).unwrap();
+                            write!(
+                                writer,
+                                "<td>{}{}</td>",
+                                "&nbsp;&nbsp;".repeat(hier_level),
+                                display_name(&element.id, &args.naming, args.common.lang.as_deref())
+                            )
+                            .unwrap();
+
+                            let description = if element.short == element.definition {
+                                element.short.to_string()
+                            } else {
+                                format!(
+                                    "{}<br/>{}",
+                                    element.short,
+                                    element.definition.replace("\n", "<br/>")
+                                )
+                            };
+                            write!(writer, "<td>{}</td>", description).unwrap();
+
+                            write!(writer, "<td>{}</td>", reduce_datatypes(&element.datatype))
+                                .unwrap();
+
+                            write!(writer, "<td>{}..{}</td>", element.min, element.max).unwrap();
+
+                            if let Some(binding) = element.value_set_title.as_ref().or(element.binding.as_ref()) {
+                                write!(writer, "<td>{}</td>", binding).unwrap();
+                            } else {
+                                write!(writer, "<td></td>").unwrap();
+                            }
+
+                            let mut obligation_map =
+                                HashMap::<String, Vec<(String, String)>>::new();
+                            if !element.obligation.is_empty() {
+                                for obligation in &element.obligation {
+                                    let actor = obligation.0.clone();
+                                    let code = obligation.1.clone();
+                                    let documentation = obligation.2.clone();
+                                    let codes = obligation_map.entry(actor).or_default();
+                                    codes.push((code, documentation));
+                                }
+                            }
+
+                            for actor in unique_actors.iter() {
+                                if let Some(codes) = obligation_map.get(actor) {
+                                    write!(
+                                        writer,
+                                        "<td><table>{}</table></td>",
+                                        codes
+                                            .iter()
+                                            .map(|(code, documentation)| {
+                                                if documentation.is_empty() {
+                                                    format!("<tr><td>{}</td><td></td></tr>", code)
+                                                } else {
+                                                    format!(
+                                                        "<tr><td>{}</td><td>{}</td></tr>",
+                                                        code, documentation
+                                                    )
+                                                }
+                                            })
+                                            .collect::<Vec<_>>()
+                                            .join("")
+                                    )
+                                    .unwrap();
+                                } else {
+                                    write!(writer, "<td></td>").unwrap();
+                                }
+                            }
+
+                            writeln!(writer, "</tr>").unwrap();
+                        }
+                    },
+                    |_, _, _| (),
+                    &mut (),
+                );
+
+                // let no_of_actors = unique_actors.len();
+
+                writeln!(writer, "</table>")?;
+            }
+        }
+        Commands::Site(args) => {
+            site::generate_site(&args, docs)?;
+        }
+        Commands::HtmlDictionary(args) => {
+            html_dictionary::generate_html_dictionary(&args, docs)?;
+        }
+        Commands::Json(args) => {
+            json_export::export_json(&args, docs)?;
+        }
+        Commands::Compare(args) => {
+            compare::generate_comparison(&args, docs)?;
+        }
+        Commands::WhereUsed(args) => {
+            where_used::generate_where_used(&args, docs)?;
+        }
+        Commands::Dictionary(args) => {
+            dictionary::generate_dictionary(&args, docs)?;
+        }
+        Commands::Example(args) => {
+            example::generate_examples(&args, docs)?;
+        }
+        Commands::Testdata(args) => {
+            example::generate_test_data(&args, docs)?;
+        }
+        Commands::Fhirpath(args) => {
+            fhirpath::generate_fhirpath(&args, docs)?;
+        }
+        Commands::Paths(args) => {
+            paths::generate_paths(&args, docs)?;
+        }
+        Commands::Attributes(args) => {
+            attributes::generate_attributes(&args, docs)?;
+        }
+        Commands::Adl(args) => {
+            openehr::generate_archetypes(&args, docs)?;
+        }
+        Commands::Pdf(args) => {
+            pdf::generate_pdf(&args, docs)?;
+        }
+        Commands::Org(args) => {
+            org::generate_org(&args, docs)?;
+        }
+        Commands::Metrics(args) => {
+            metrics::generate_metrics(&args, docs)?;
+        }
+        Commands::Archimate(args) => {
+            archimate::generate_archimate(&args, docs)?;
+        }
+        _ => return Err("The command does not generate from the structure definitions".into()),
+    }
+
+    Ok(())
+}
+
+/// Name of the model shown in a mind map, without the obligations suffix
+fn mindmap_model_name(doc: &StructureDefTreeInfo) -> &str {
+    if let Some(title) = doc.id.strip_suffix("Obligations") {
+        title
+    } else {
+        &doc.id
+    }
+}
+
+fn write_mindmap_title<W: Write>(
+    writer: &mut W,
+    level: usize,
+    model: &str,
+    args: &MindmapArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let color = mindmap_color(args.depth_colors.get(level - 1));
+    if args.link {
+        writeln!(writer, "{}{} **[[{}.html {}]]**", "*".repeat(level), color, model, model)?;
+    } else {
+        writeln!(writer, "{}{} **{}**", "*".repeat(level), color, model)?;
+    }
+    Ok(())
+}
+
+/// Writes a structure definition as a branch of a combined mind map, followed by the
+/// structure definitions based on it when merging by base, in a node of their own after its
+/// elements
+fn write_mindmap_branch<W: Write>(
+    writer: &mut W,
+    doc: &StructureDefTreeInfo,
+    index: &DocIndex,
+    args: &MindmapArgs,
+    labels: &Labels,
+    level: usize,
+    visited: &mut HashSet<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !visited.insert(doc.id.clone()) {
+        return Ok(());
+    }
+    status!("processing: {}", doc.id);
+
+    write_mindmap_title(writer, level, mindmap_model_name(doc), args)?;
+    write_mindmap_elements(writer, doc, args, level - 1)?;
+
+    let derived = derived_branches(doc, index, args, visited);
+    if !derived.is_empty() {
+        let color = mindmap_color(args.depth_colors.get(level));
+        writeln!(writer, "{}{} //{}//", "*".repeat(level + 1), color, labels.get("derived_profiles"))?;
+        for derived in derived {
+            write_mindmap_branch(writer, derived, index, args, labels, level + 2, visited)?;
+        }
+    }
+    Ok(())
+}
+
+/// The structure definitions based on one, placed below it when merging by base and not
+/// written yet
+fn derived_branches<'a>(
+    doc: &'a StructureDefTreeInfo,
+    index: &DocIndex<'a>,
+    args: &MindmapArgs,
+    visited: &HashSet<String>,
+) -> Vec<&'a StructureDefTreeInfo> {
+    if !args.by_base {
+        return Vec::new();
+    }
+    index.derived_from(doc).filter(|derived| !visited.contains(&derived.id)).collect()
+}
+
+fn write_mindmap_xml_header<W: Write>(
+    writer: &mut W,
+    title: &str,
+    args: &MindmapArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if args.format == MindmapFormat::FreeMind {
+        writeln!(writer, "<map version=\"1.0.1\">")?;
+    } else {
+        writeln!(
+            writer,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<opml version=\"2.0\">\n<head><title>{}</title></head>\n<body>",
+            escape_xml(title)
+        )?;
+    }
+    Ok(())
+}
+
+fn write_mindmap_xml_footer<W: Write>(
+    writer: &mut W,
+    args: &MindmapArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if args.format == MindmapFormat::FreeMind {
+        writeln!(writer, "</map>")?;
+    } else {
+        writeln!(writer, "</body>\n</opml>")?;
+    }
+    Ok(())
+}
+
+fn write_mindmap_xml_open<W: Write>(
+    writer: &mut W,
+    level: usize,
+    text: &str,
+    note: Option<&str>,
+    args: &MindmapArgs,
+) -> std::io::Result<()> {
+    let indent = "  ".repeat(level);
+    match (&args.format, note) {
+        (MindmapFormat::FreeMind, None) => writeln!(writer, "{}<node TEXT=\"{}\">", indent, escape_xml(text)),
+        (MindmapFormat::FreeMind, Some(note)) => writeln!(
+            writer,
+            "{}<node TEXT=\"{}\">\n{}  <richcontent TYPE=\"NOTE\"><html><body><p>{}</p></body></html></richcontent>",
+            indent,
+            escape_xml(text),
+            indent,
+            escape_xml(note)
+        ),
+        (_, None) => writeln!(writer, "{}<outline text=\"{}\">", indent, escape_xml(text)),
+        (_, Some(note)) => writeln!(writer, "{}<outline text=\"{}\" _note=\"{}\">", indent, escape_xml(text), escape_xml(note)),
+    }
+}
+
+fn write_mindmap_xml_close<W: Write>(writer: &mut W, level: usize, args: &MindmapArgs) -> std::io::Result<()> {
+    if args.format == MindmapFormat::FreeMind {
+        writeln!(writer, "{}</node>", "  ".repeat(level))
+    } else {
+        writeln!(writer, "{}</outline>", "  ".repeat(level))
+    }
+}
+
+/// Writes a structure definition as a nested FreeMind or OPML node, with the structure
+/// definitions based on it inside when merging by base, in a node of their own after its elements
+fn write_mindmap_xml_branch<W: Write>(
+    writer: &mut W,
+    doc: &StructureDefTreeInfo,
+    index: &DocIndex,
+    args: &MindmapArgs,
+    labels: &Labels,
+    level: usize,
+    visited: &mut HashSet<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !visited.insert(doc.id.clone()) {
+        return Ok(());
+    }
+    status!("processing: {}", doc.id);
+
+    write_mindmap_xml_open(writer, level, mindmap_model_name(doc), None, args)?;
+
+    // the writer is passed as traversal state since both callbacks need to write
+    extension_view(doc).as_ref().unwrap_or(doc).element_tree.traverse(
+        |_idx, element, writer| {
+            if element.id.contains('.') && &*element.max != "0" {
+                let hier_level = element.depth + level;
+                write_mindmap_xml_open(writer, hier_level, &mindmap_label(element, args), mindmap_short_line(element, args), args)
+                    .unwrap();
+            }
+        },
+        |_idx, element, writer| {
+            if element.id.contains('.') && &*element.max != "0" {
+                let hier_level = element.depth + level;
+                write_mindmap_xml_close(writer, hier_level, args).unwrap();
+            }
+        },
+        writer,
+    );
+
+    let derived = derived_branches(doc, index, args, visited);
+    if !derived.is_empty() {
+        write_mindmap_xml_open(writer, level + 1, labels.get("derived_profiles"), None, args)?;
+        for derived in derived {
+            write_mindmap_xml_branch(writer, derived, index, args, labels, level + 2, visited)?;
+        }
+        write_mindmap_xml_close(writer, level + 1, args)?;
+    }
+
+    write_mindmap_xml_close(writer, level, args)?;
+    Ok(())
+}
+
+/// Label of a mind map node with the optional cardinality and short description
+fn mindmap_label(element: &ElementInfo, args: &MindmapArgs) -> String {
+    let mut label = display_name(&element.id, &args.naming, args.common.lang.as_deref());
+    if args.show_cardinality {
+        label.push_str(&format!(" [{}..{}]", element.min, element.max));
+    }
+    if args.show_short {
+        label.push_str(&format!(" — {}", element.short.replace("\n", " ")));
+    }
+    label
+}
+
+/// Position of the first of `branches` top-level branches of a PlantUML mind map on the left side
+fn mindmap_left_from(args: &MindmapArgs, branches: usize) -> Option<usize> {
+    match args.direction {
+        MindmapDirection::Right => None,
+        MindmapDirection::Left => Some(0),
+        MindmapDirection::Both => Some(args.left_from.map_or(branches.div_ceil(2), |branch| branch.saturating_sub(1))),
+    }
+}
+
+/// Short description of an element shown below its name, if asked for and not empty
+fn mindmap_short_line<'a>(element: &'a ElementInfo, args: &MindmapArgs) -> Option<&'a str> {
+    (args.short_line && !element.short.trim().is_empty()).then_some(element.short.trim())
+}
+
+/// Writes the element nodes of a structure definition, shifted `level_offset` levels down
+fn write_mindmap_elements<W: Write>(
+    writer: &mut W,
+    doc: &StructureDefTreeInfo,
+    args: &MindmapArgs,
+    level_offset: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let view = extension_view(doc);
+    let doc = view.as_ref().unwrap_or(doc);
+    // the elements are the top-level branches of the mind map of a single structure definition
+    let is_branch = |element: &ElementInfo| level_offset == 0 && element.depth == 1 && &*element.max != "0";
+    let left_from = mindmap_left_from(args, doc.element_tree.iter().filter(|(_, e)| is_branch(e)).count());
+    let mut branch_num = 0;
+    doc.element_tree.traverse(
+        |_idx, element, _| {
+            if is_branch(element) {
+                if left_from == Some(branch_num) {
+                    writeln!(writer, "left side").unwrap();
+                }
+                branch_num += 1;
+            }
+            if element.id.contains('.') && &*element.max != "0" {
+                let hier_level = element.depth + 1 + level_offset;
+                let label = mindmap_label(element, args);
+
+                // boxless nodes have no background to color
+                let style = if hier_level > args.box_level {
+                    "_".to_string()
+                } else if &*element.min != "0" && args.required_color.is_some() {
+                    mindmap_color(args.required_color.as_ref())
+                } else if element.must_support && args.must_support_color.is_some() {
+                    mindmap_color(args.must_support_color.as_ref())
+                } else {
+                    mindmap_color(args.depth_colors.get(hier_level - 1))
+                };
+
+                if let Some(short) = mindmap_short_line(element, args) {
+                    // a multiline node ends with the line ending with ';'
+                    let short = short.replace("\n", " ");
+                    writeln!(
+                        writer,
+                        "{}{}:{}\n<size:10>{}</size>;",
+                        "*".repeat(hier_level),
+                        style,
+                        label,
+                        short.trim_end_matches(';')
+                    )
+                    .unwrap();
+                } else {
+                    writeln!(
+                        writer,
+                        "{}{} {}",
+                        "*".repeat(hier_level),
+                        style,
+                        label
+                    )
+                    .unwrap();
+                }
+            }
+        },
+        |_, _, _| (),
+        &mut (),
+    );
+    Ok(())
+}
+
+/// Inline PlantUML color tag for a mind map node, empty if no color is given
+fn mindmap_color(color: Option<&String>) -> String {
+    match color {
+        Some(color) if color.starts_with('#') => format!("[{}]", color),
+        Some(color) => format!("[#{}]", color),
+        None => String::new(),
+    }
+}
+
+fn write_plantuml_header<W: Write>(
+    writer: &mut W,
+    args: &PlantUmlArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    writeln!(writer, "@startuml")?;
+    if let Some(style) = &args.include_style {
+        // shared style file replaces the built-in skin parameters
+        writeln!(writer, "!include {}\n", style.display())?;
+    } else {
+        writeln!(writer, "skinparam linetype polyline\nhide circle")?;
+        if args.stereotype_hide {
+            writeln!(writer, "hide stereotype")?;
+        }
+        writeln!(writer, "hide methods\n")?;
+    }
+    Ok(())
+}
+
+/// Writes a legend of the notation used with the options of the diagram, if asked for
+fn write_plantuml_legend<W: Write>(writer: &mut W, args: &PlantUmlArgs) -> std::io::Result<()> {
+    if !args.legend {
+        return Ok(());
+    }
+    // the symbols as rendered, which is what readers of the diagram see
+    let mut rows = vec![
+        ("line with a filled diamond", "the class at the diamond contains elements of the other datatype"),
+        ("dashed arrow", "an element referencing the resource at the arrowhead"),
+        ("hollow diamond", "choice of types of an element ending with [x]"),
+    ];
+    if args.show_base {
+        rows.push(("line with a hollow triangle", "derived from the class at the triangle"));
+    }
+    if args.nested_classes {
+        rows.push(("class named Class_element", "element with children, e.g. a backbone element"));
+    }
+    if !args.stereotype_hide && args.include_style.is_none() {
+        rows.push(("\u{ab}Profile\u{bb}", "kind of structure definition: Resource, DataType, LogicalModel, Profile or Extension"));
+    }
+    if !args.cardinality_hide {
+        rows.push(("[0..*]", "cardinality of an element, minimum..maximum"));
+    }
+    rows.push(("0..*", "cardinality of a relation, at the end of the contained or referenced class"));
+    writeln!(writer, "legend right\n|= Notation |= Meaning |")?;
+    for (notation, meaning) in rows {
+        writeln!(writer, "| {} | {} |", notation, meaning)?;
+    }
+    if args.bold_must_support {
+        writeln!(writer, "| **element** | must support |")?;
+    }
+    if let Some(color) = &args.modifier_color {
+        writeln!(writer, "| <color:{}>element</color> | modifier element |", color)?;
+    }
+    if let Some(marker) = &args.required_marker {
+        writeln!(writer, "| element {} | required element, minimum cardinality 1 or more |", marker)?;
+    }
+    writeln!(writer, "endlegend")
+}
+
+/// Package name of a structure definition according to the grouping criterion, if any
+fn plantuml_package_of(
+    doc: &StructureDefTreeInfo,
+    group_by: Option<&GroupBy>,
+    package_map: &HashMap<String, String>,
+) -> Option<String> {
+    match group_by? {
+        GroupBy::Directory => doc
+            .source
+            .parent()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string()),
+        GroupBy::UrlPrefix => match doc.url.find("/StructureDefinition/") {
+            Some(index) => Some(doc.url[..index].to_string()),
+            None => doc.url.rfind('/').map(|index| doc.url[..index].to_string()),
+        },
+        GroupBy::Kind => match doc.kind.as_str() {
+            "resource" => Some("Resources".to_string()),
+            "complex-type" | "primitive-type" => Some("Datatypes".to_string()),
+            "logical" => Some("Logical Models".to_string()),
+            _ => None,
+        },
+        GroupBy::Mapping => package_map.get(&doc.id).cloned(),
+    }
+}
+
+/// Stereotype of a structure definition derived from its kind, derivation and type
+fn plantuml_stereotype_of(doc: &StructureDefTreeInfo) -> Option<&'static str> {
+    if doc.sd_type == "Extension" && doc.derivation == "constraint" {
+        Some("Extension")
+    } else if doc.kind == "logical" {
+        Some("LogicalModel")
+    } else if doc.derivation == "constraint" {
+        Some("Profile")
+    } else if doc.kind == "resource" {
+        Some("Resource")
+    } else if doc.kind == "complex-type" || doc.kind == "primitive-type" {
+        Some("DataType")
+    } else {
+        None
+    }
+}
+
+/// Whether the base of a structure definition is drawn as its superclass, the FHIR Base of
+/// logical models is left out
+fn shows_base(doc: &StructureDefTreeInfo, args: &PlantUmlArgs) -> bool {
+    args.show_base && doc.base != doc.id && doc.base != "Base"
+}
+
+/// Empty classes for the bases of the structure definitions that are not among them
+fn plantuml_base_classes(index: &DocIndex, args: &PlantUmlArgs) -> String {
+    let mut bases = Vec::<&str>::new();
+    for doc in index.docs.iter().filter(|d| shows_base(d, args)) {
+        if !index.contains(&doc.base) && !bases.contains(&doc.base.as_str()) {
+            bases.push(&doc.base);
+        }
+    }
+    bases.iter().map(|base| format!("class **{}** {{\n}}\n", base)).collect()
+}
+
+/// Writes the class of a single structure definition and returns its outgoing relations
+fn write_plantuml_class<W: Write>(
+    writer: &mut W,
+    doc: &StructureDefTreeInfo,
+    index: &DocIndex,
+    args: &PlantUmlArgs,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let view = extension_view(doc);
+    let doc = view.as_ref().unwrap_or(doc);
+    let keyword = if doc.is_interface {
+        "interface"
+    } else if doc.is_abstract {
+        "abstract class"
+    } else {
+        "class"
+    };
+    if let Some(stereotype) = plantuml_stereotype_of(doc) {
+        writeln!(writer, "{} **{}** <<{}>> {{", keyword, doc.id, stereotype)?;
+    } else {
+        writeln!(writer, "{} **{}** {{", keyword, doc.id)?;
+    }
+    let mut relations = String::new();
+    // specializations of abstract definitions in the diagram are drawn as generalizations
+    let base = index.base_of(doc);
+    if shows_base(doc, args) || base.is_some_and(|base| base.is_abstract || base.is_interface) {
+        relations += &format!("\"**{}**\" --|> \"**{}**\"\n", doc.id, base.map(|base| base.id.as_str()).unwrap_or(&doc.base));
+    }
+    let mut bindings = Vec::<String>::new();
+    for (_idx, element) in doc.element_tree.iter() {
+        if let Some(element_part) = get_slice_after_last_occurrence(&element.id, '.')
+            && &*element.max != "0"
+            && let Some(value_set) = value_set_name(element)
+        {
+            bindings.push(format!(
+                "{} : {} ({})",
+                element_part,
+                value_set,
+                element.binding_strength.as_deref().unwrap_or("unknown")
+            ));
+        }
+    }
+
+    let class = PlantUmlClassRef {
+        reference: format!("\"**{}**\"", doc.id),
+        name: doc.id.clone(),
+    };
+    let mut children = HashMap::<&str, Vec<&ElementInfo>>::new();
+    // classes of the elements with children, written after this one is closed
+    let mut backbones = Vec::<(&ElementInfo, PlantUmlClassRef)>::new();
+
+    if args.nested_classes {
+        for (idx, element) in doc.element_tree.iter().filter(|(_, e)| &*e.max != "0") {
+            if let Some(parent) = doc.element_tree.get_parent_of(Some(idx)).and_then(|parent| doc.element_tree.get(parent)) {
+                children.entry(parent.id.as_ref()).or_default().push(element);
+            }
+        }
+        for (_idx, element) in doc.element_tree.iter() {
+            if let Some((_, path)) = element.id.split_once('.')
+                && children.contains_key(element.id.as_ref())
+            {
+                let name = format!("{}_{}", doc.id, path.replace(['.', '[', ']'], "_"));
+                backbones.push((element, PlantUmlClassRef { reference: name.clone(), name }));
+            }
+        }
+        if let Some((_, root)) = doc.element_tree.iter().next() {
+            let members = children.get(root.id.as_ref()).cloned().unwrap_or_default();
+            write_plantuml_members(writer, &members, &class, &backbones, index, args, &mut relations)?;
+        }
+    } else {
+        doc.element_tree.traverse(
+            |_idx, element, _| {
+                if element.id.contains('.') && &*element.max != "0" {
+                    let hier_level = element.depth * 2;
+                    write_plantuml_element(writer, element, &class, hier_level, index, args, &mut relations).unwrap();
+                }
+            },
+            |_, _, _| (),
+            &mut (),
+        );
+    }
+
+    if args.bindings == Some(BindingDisplay::Box) && !bindings.is_empty() {
+        writeln!(writer, "  --")?;
+        for binding in bindings.iter() {
+            writeln!(writer, "  {}", binding)?;
+        }
+    }
+
+    writeln!(writer, "}}")?;
+
+    if args.metadata {
+        writeln!(writer, "note top of \"**{}**\"", doc.id)?;
+        for (name, value) in structure_metadata(doc) {
+            writeln!(writer, "  {}: {}", name, value.replace('\n', " "))?;
+        }
+        writeln!(writer, "end note")?;
+    }
+
+    if args.bindings == Some(BindingDisplay::Note) && !bindings.is_empty() {
+        writeln!(writer, "note right of \"**{}**\"", doc.id)?;
+        for binding in bindings.iter() {
+            writeln!(writer, "  {}", binding)?;
+        }
+        writeln!(writer, "end note")?;
+    }
+
+    for (backbone, backbone_class) in backbones.iter() {
+        writeln!(
+            writer,
+            "class \"{}.{}\" as {} <<BackboneElement>> {{",
+            doc.id,
+            backbone.id.split_once('.').map(|(_, path)| path).unwrap_or_default(),
+            backbone_class.name
+        )?;
+        let members = children.get(backbone.id.as_ref()).cloned().unwrap_or_default();
+        write_plantuml_members(writer, &members, backbone_class, &backbones, index, args, &mut relations)?;
+        writeln!(writer, "}}")?;
+    }
+
+    Ok(relations)
+}
+
+/// How relations refer to the class an element is drawn in
+struct PlantUmlClassRef {
+    // class name in relations, quoted for the structure definitions and an alias for backbone classes
+    reference: String,
+    // prefix of the names of choice diamonds
+    name: String,
+}
+
+/// Writes the direct children of an element into its class, drawing those with a class of their
+/// own as compositions
+fn write_plantuml_members<W: Write>(
+    writer: &mut W,
+    members: &[&ElementInfo],
+    class: &PlantUmlClassRef,
+    backbones: &[(&ElementInfo, PlantUmlClassRef)],
+    index: &DocIndex,
+    args: &PlantUmlArgs,
+    relations: &mut String,
+) -> std::io::Result<()> {
+    for member in members.iter() {
+        if let Some((_, backbone)) = backbones.iter().find(|(element, _)| element.id == member.id) {
+            *relations += &format!(
+                "{} *-- \"{}..{}\" {} : {} >\n",
+                class.reference,
+                member.min,
+                member.max,
+                backbone.reference,
+                get_slice_after_last_occurrence(&member.id, '.').unwrap_or_default()
+            );
+            if !args.keep_related_elements {
+                continue;
+            }
+        }
+        write_plantuml_element(writer, member, class, 2, index, args, relations)?;
+    }
+    Ok(())
+}
+
+/// Writes an element into the class box, or adds a relation instead when its datatype is one of
+/// the classes drawn
+fn write_plantuml_element<W: Write>(
+    writer: &mut W,
+    element: &ElementInfo,
+    class: &PlantUmlClassRef,
+    hier_level: usize,
+    index: &DocIndex,
+    args: &PlantUmlArgs,
+    relations: &mut String,
+) -> std::io::Result<()> {
+    let element_part = get_slice_after_last_occurrence(&element.id, '.').unwrap_or_default();
+    // if the datatype is one of the classes drawn, add a relation instead of a class element
+    // TODO: element is removed from element list if there is one datatype that is among the structure definitions
+    let mut related = false;
+    if element_part.ends_with("[x]") {
+        let element_part_no_x = element_part.replace("[x]", "");
+        let choice: String = format!("{}{}", class.name, element_part_no_x);
+        let mut local_relations = String::new();
+        for datatype in element.datatype.iter() {
+            if let Some(target) = index.get(datatype) {
+                local_relations += &format!(
+                    "{} {} \"**{}**\" : {} >\n",
+                    choice,
+                    if element.reference.contains(datatype) { "..>" } else { "*--" },
+                    target.id,
+                    element_part_no_x
+                );
+                // will hide element if there is just one datatype that is another class in the diagram,
+                related = true; // do not show element if it is a choice
+            }
+        }
+        if related {
+            *relations += &format!("<> {}\n", choice);
+            *relations += &format!(
+                "{} -- \"{}..{}\" {} : {} >\n",
+                class.reference, element.min, element.max, choice, element_part_no_x
+            );
+            *relations += &local_relations;
+        }
+    } else {
+        for datatype in element.datatype.iter() {
+            if let Some(target) = index.get(datatype) {
+                // references are drawn as dashed associations, contained datatypes as compositions,
+                // from nested classes with the local cardinality
+                let (min, max) = if args.nested_classes {
+                    (&element.min, &element.max)
+                } else {
+                    (&element.global_min, &element.global_max)
+                };
+                *relations += &format!(
+                    "{} {} \"{}..{}\" \"**{}**\" : {} >\n",
+                    class.reference,
+                    if element.reference.contains(datatype) { "..>" } else { "*--" },
+                    min,
+                    max,
+                    target.id,
+                    element_part
+                );
+                related = true; // do not show element if datatype is another class in the diagram
+            }
+        }
+    }
+
+    let show_this_element = !related || args.keep_related_elements;
+    if show_this_element && !args.elements_hide {
+        let mut element_name = element_part.clone();
+        if args.bold_must_support && element.must_support {
+            element_name = format!("**{}**", element_name);
+        }
+        if let Some(color) = &args.modifier_color
+            && element.is_modifier
+        {
+            element_name = format!("<color:{}>{}</color>", color, element_name);
+        }
+        if let Some(marker) = &args.required_marker
+            && &*element.min != "0"
+        {
+            element_name = format!("{} {}", element_name, marker);
+        }
+        write!(
+            writer,
+            "{:>hier_level$}|_ {} : {}",
+            "",
+            element_name,
+            reduce_datatypes(&element.datatype)
+        )?;
+        if !args.cardinality_hide {
+            write!(writer, " [{}..{}]", element.min, element.max)?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Name of the value set bound to an element, falling back to the binding description
+fn value_set_name(element: &ElementInfo) -> Option<String> {
+    if let Some(title) = &element.value_set_title {
+        Some(title.clone())
+    } else if let Some(value_set) = &element.value_set {
+        let canonical = get_slice_before_first_occurrence(value_set, '|').unwrap_or(value_set.to_string());
+        get_slice_after_last_occurrence(&canonical, '/').or(Some(canonical))
+    } else {
+        element.binding.clone()
+    }
+}
+
+/// Options and lookups shared by all tables of a run
+struct TableContext<'a> {
+    args: &'a TableArgs,
+    labels: Labels,
+    index: DocIndex<'a>,
+    prefix_map: HashMap<String, String>,
+    stable_codes: HashMap<String, String>,
+    // source profile per element id, per structure definition id
+    sources: HashMap<String, HashMap<String, String>>,
+}
+
+impl<'a> TableContext<'a> {
+    fn new(args: &'a TableArgs, docs: &'a [StructureDefTreeInfo]) -> Result<Self, Box<dyn std::error::Error>> {
+        let index = DocIndex::new(docs);
+        Ok(TableContext {
+            args,
+            labels: Labels::load(args.common.lang.as_deref().unwrap_or("en"), args.translation_file.as_ref())?,
+            sources: if args.provenance {
+                docs.iter().map(|doc| (doc.id.clone(), provenance::element_sources(doc, &index))).collect()
+            } else {
+                HashMap::new()
+            },
+            index,
+            prefix_map: parse_prefix_map(&args.prefix_map)?,
+            stable_codes: if let Some(codes_file) = &args.codes_file {
+                load_string_map(codes_file)?
+            } else {
+                HashMap::<String, String>::new()
+            },
+        })
+    }
+
+    /// Code prefix of a structure definition, `doc_num` being its position among the inputs
+    fn prefix_of(&self, doc_num: usize, doc: &StructureDefTreeInfo) -> String {
+        if let Some(prefix) = self.prefix_map.get(&doc.id) {
+            prefix.clone()
+        } else if self.args.prefix_code == "A" {
+            generate_code(doc_num)
+        } else {
+            self.args.prefix_code.clone()
+        }
+    }
+}
+
+/// Writes the markdown table of a structure definition and returns the codes of its elements
+fn write_table<W: Write>(
+    writer: &mut W,
+    doc: &StructureDefTreeInfo,
+    doc_num: usize,
+    ctx: &TableContext,
+) -> Result<HashMap<usize, String>, Box<dyn std::error::Error>> {
+    let args = ctx.args;
+    let codes = number_elements(&doc.element_tree, &ctx.prefix_of(doc_num, doc), &args.numbering, &ctx.stable_codes);
+
+    writeln!(writer, "## {}", doc.id)?;
+    // front matter is only valid at the top of a file, one after the other the documents have the
+    // metadata below their headings
+    if args.metadata && !output::has_own_files() {
+        for (name, value) in structure_metadata(doc) {
+            writeln!(writer, "- **{}**: {}", name, value.replace('\n', " "))?;
+        }
+        writeln!(writer)?;
+    }
+    if is_extension(doc) {
+        write_extension_table(writer, doc, &codes, ctx)?;
+        return Ok(codes);
+    }
+    if args.toc {
+        write_table_toc(writer, doc, ctx)?;
+    }
+    if args.sections {
+        write_table_sections(writer, doc, &codes, ctx)?;
+        return Ok(codes);
+    }
+    writeln!(
+        writer,
+        "{}",
+        table_header(ctx, true)
+        //"| Code | Path | Element | Description | Datatype | Cardinality | Global Cardinality | Preferred Code System | Requirements |"
+    )?;
+    // writeln!(
+    //     writer,
+    //     "| --- | --- | --- | --- | --- | --- | --- | --- | --- "
+    // )?;
+
+    let mut footnotes = Vec::<BindingFootnote>::new();
+    doc.element_tree.traverse(
+        |idx, element, _| {
+            if !shows_table_row(element, args) {
+                return;
+            }
+            let hier_level: usize = element.depth;
+            let element_part: String = if hier_level > 0 {
+                get_slice_after_last_occurrence(&element.id, '.').unwrap_or(element.id.to_string())
+            } else {
+                element.id.to_string()
+            };
+            // let element_path: String = if hier_level > 0 {
+            //     get_slice_after_first_occurrence(&element.id, '.')
+            //         .unwrap_or(element.id.clone())
+            // } else {
+            //     element.id.clone()
+            // };
+
+            let level = "+".repeat(hier_level);
+            // the elements listed in the table of contents are link targets
+            let anchor = if args.toc && is_toc_entry(doc, idx, element) {
+                format!("<a id=\"{}\"></a>", element_anchor(doc, &element.id))
+            } else {
+                String::new()
+            };
+
+            let code = codes.get(&idx).map(|c| c.as_str()).unwrap_or_default();
+
+            if !args.code_hide {
+                write!(writer, "| {} ", code).unwrap_or(());
+            }
+            // let element_part_no_x = element_part.replace("[x]", "");
+            write!(
+                writer,
+                "| {} | {}{} |",
+                level,
+                anchor,
+                element_part,
+                // camel_to_spaced_pascal(&element_part_no_x),
+            ).unwrap_or(());
+            if args.flags {
+                write!(writer, " {} |", element_flags(element)).unwrap_or(());
+            }
+            write_table_cells(writer, element, doc, ctx, &mut footnotes).unwrap_or(());
+
+                                    // if let Some(binding_strength) = &element.binding_strength {
+            //     write!(writer, " {} |", binding_strength).unwrap();
+            // } else {
+            //     write!(writer, " |").unwrap();
+            // }
+            // if let Some(requirements) = &element.requirements {
+            //     write!(writer, " {} |", requirements.replace("\n", "<br/>")).unwrap();
+            // } else {
+            //     write!(writer, " |").unwrap();
+            // }
+            // if let Some((actor, code, documentation)) = element.obligation.first() {
+            //     write!(writer, " {} ({}) | {} |", actor, code, documentation.replace("\n", "<br/>")).unwrap();
+            // } else {
+            //     write!(writer, " | |").unwrap();
+            // }
+            writeln!(writer).unwrap_or(());
+        },
+        |_, _, _| (),
+        &mut (),
+    );
+    write_binding_footnotes(writer, doc, &footnotes, 0, ctx)?;
+
+    Ok(codes)
+}
+
+/// Whether a structure definition defines an extension, rather than a profile or datatype
+fn is_extension(doc: &StructureDefTreeInfo) -> bool {
+    doc.sd_type == "Extension" && doc.derivation == "constraint"
+}
+
+/// Writes the contexts an extension is used in and a row for the extension and each of its
+/// sub-extensions, with the types and binding of its value, leaving out the id, url and value
+/// elements repeated in each
+fn write_extension_table<W: Write>(
+    writer: &mut W,
+    doc: &StructureDefTreeInfo,
+    codes: &HashMap<usize, String>,
+    ctx: &TableContext,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let args = ctx.args;
+    writeln!(writer, "\n{}\n", doc.url)?;
+    if !doc.contexts.is_empty() {
+        let contexts = doc
+            .contexts
+            .iter()
+            .map(|(context_type, expression)| match context_type.as_str() {
+                "element" => format!("`{}`", expression),
+                "fhirpath" => format!("FHIRPath `{}`", expression),
+                context_type => format!("{} `{}`", context_type, expression),
+            })
+            .collect::<Vec<_>>();
+        writeln!(writer, "**{}**: {}\n", ctx.labels.get("context_of_use"), contexts.join(", "))?;
+    }
+    writeln!(writer, "{}", table_header(ctx, false))?;
+
+    let mut footnotes = Vec::<BindingFootnote>::new();
+    for (idx, name, mut row) in extension_rows(doc) {
+        let element = doc.element_tree.get_unchecked(idx);
+        if !shows_table_row(element, args) {
+            continue;
+        }
+        let name = if element.depth == 0 { doc.id.clone() } else { name };
+        if element.depth == 0 {
+            // below the root, so that the types are written
+            row.id = Rc::from(format!("{}.value[x]", element.id));
+        }
+
+        if !args.code_hide {
+            write!(writer, "| {} ", codes.get(&idx).map(|c| c.as_str()).unwrap_or_default())?;
+        }
+        write!(writer, "| {} |", name)?;
+        if args.flags {
+            write!(writer, " {} |", element_flags(element))?;
+        }
+        write_table_cells(writer, &row, doc, ctx, &mut footnotes)?;
+        writeln!(writer)?;
+    }
+    write_binding_footnotes(writer, doc, &footnotes, 0, ctx)?;
+    Ok(())
+}
+
+/// The extension and each of its sub-extensions of an extension definition, with their node, their
+/// name and the element with the types and binding of its value: the slice names down to the
+/// sub-extension, e.g. "period" or "coverage.start", and none for the extension itself
+fn extension_rows(doc: &StructureDefTreeInfo) -> Vec<(usize, String, ElementInfo)> {
+    let tree = &doc.element_tree;
+    let value_of = |element: &ElementInfo| {
+        let value_id = format!("{}.value[x]", element.id);
+        tree.iter().map(|(_, value)| value).find(|value| *value.id == value_id && &*value.max != "0")
+    };
+    let mut rows = Vec::<(usize, String, ElementInfo)>::new();
+    for (idx, element) in tree.iter().filter(|(_, element)| &*element.max != "0") {
+        let name = if element.depth == 0 {
+            String::new()
+        } else if get_slice_after_last_occurrence(&element.id, '.').is_some_and(|part| part.starts_with("extension:")) {
+            element.id.split('.').filter_map(|part| part.strip_prefix("extension:")).collect::<Vec<_>>().join(".")
+        } else {
+            continue;
+        };
+        let mut row = element.clone();
+        let value = value_of(element);
+        row.datatype = value.map(|value| value.datatype.clone()).unwrap_or_default();
+        row.binding = value.and_then(|value| value.binding.clone());
+        row.binding_strength = value.and_then(|value| value.binding_strength.clone());
+        row.value_set = value.and_then(|value| value.value_set.clone());
+        row.value_set_title = value.and_then(|value| value.value_set_title.clone());
+        row.value_set_codes = value.map(|value| Rc::clone(&value.value_set_codes)).unwrap_or_default();
+        rows.push((idx, name, row));
+    }
+    rows
+}
+
+/// An extension definition as the extension with its sub-extensions below it, named by their slice
+/// names, e.g. `Extension.coverage.start`, and typed by their values, for the generators drawing
+/// the elements; none for the other structure definitions
+fn extension_view(doc: &StructureDefTreeInfo) -> Option<StructureDefTreeInfo> {
+    if !is_extension(doc) {
+        return None;
+    }
+    let mut element_tree = Tree::new();
+    let mut new_indices = HashMap::<usize, usize>::new();
+    for (idx, _name, mut element) in extension_rows(doc) {
+        let tree = &doc.element_tree;
+        let new_idx = match tree.get_parent_of(Some(idx)) {
+            None => element_tree.add_node(element),
+            Some(parent) => {
+                // below the extension or sub-extension it is in, past the element of the slices
+                let mut ancestor = Some(parent);
+                while let Some(ancestor_idx) = ancestor
+                    && !new_indices.contains_key(&ancestor_idx)
+                {
+                    ancestor = tree.get_parent_of(ancestor);
+                }
+                // left out with the extension it is in, e.g. one with max 0
+                let Some(new_parent) = ancestor.and_then(|ancestor| new_indices.get(&ancestor).copied()) else {
+                    continue;
+                };
+                let parent = element_tree.get_unchecked(new_parent);
+                let slice = get_slice_after_last_occurrence(&element.id, ':').unwrap_or_default();
+                element.id = Rc::from(format!("{}.{}", parent.id, slice));
+                element.depth = parent.depth + 1;
+                element_tree.add_child(new_parent, element)
+            }
+        };
+        new_indices.insert(idx, new_idx);
+    }
+    Some(StructureDefTreeInfo { element_tree, ..doc.clone() })
+}
+
+/// Whether an element is listed in the table of contents: a top-level element or an element with
+/// children, of an element with children in sections
+fn is_toc_entry(doc: &StructureDefTreeInfo, idx: usize, element: &ElementInfo) -> bool {
+    let has_children = !doc.element_tree.children(idx).is_empty();
+    element.id.contains('.') && (has_children || element.depth == 1)
+}
+
+/// Stable anchor of an element derived from its id, prefixed with the structure definition id when
+/// the id starts with another name, so that profiles of the same resource differ in a combined file
+fn element_anchor(doc: &StructureDefTreeInfo, id: &str) -> String {
+    if id.split('.').next() == Some(doc.id.as_str()) {
+        slugify(id)
+    } else {
+        slugify(&format!("{}-{}", doc.id, id))
+    }
+}
+
+/// Writes the metadata of a structure definition as YAML front matter, the values quoted as JSON
+/// strings are valid YAML
+fn write_front_matter<W: Write>(writer: &mut W, doc: &StructureDefTreeInfo) -> Result<(), Box<dyn std::error::Error>> {
+    writeln!(writer, "---")?;
+    for (name, value) in structure_metadata(doc) {
+        writeln!(writer, "{}: {}", name, serde_json::to_string(value)?)?;
+    }
+    writeln!(writer, "---")?;
+    Ok(())
+}
+
+/// Writes a nested list of links to the top-level elements and the elements with children, or
+/// to the sections when a section is written per element with children
+fn write_table_toc<W: Write>(
+    writer: &mut W,
+    doc: &StructureDefTreeInfo,
+    ctx: &TableContext,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let args = ctx.args;
+    let mut entries = Vec::<(usize, &ElementInfo)>::new();
+    doc.element_tree.traverse(
+        |idx, element, _| {
+            let has_children = !doc.element_tree.children(idx).is_empty();
+            if shows_table_row(element, args)
+                && &*element.max != "0"
+                && if args.sections { has_children } else { is_toc_entry(doc, idx, element) }
+            {
+                entries.push((idx, element));
+            }
+        },
+        |_, _, _| (),
+        &mut (),
+    );
+    if entries.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer, "\n**{}**\n", ctx.labels.get("contents"))?;
+    for (_, element) in entries.iter() {
+        let depth = element.depth;
+        let name = if depth == 0 {
+            element.id.to_string()
+        } else {
+            get_slice_after_last_occurrence(&element.id, '.').unwrap_or(element.id.to_string())
+        };
+        // in sections the root is first, one level above its children
+        let indent = if args.sections { depth } else { depth.saturating_sub(1) };
+        writeln!(writer, "{}- [{}](#{})", "  ".repeat(indent), name, element_anchor(doc, &element.id))?;
+    }
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// Header and separator rows of a markdown table, optionally with the code, level, flags and source columns
+fn table_header(ctx: &TableContext, with_level: bool) -> String {
+    let (args, labels) = (ctx.args, &ctx.labels);
+    let mut columns = Vec::<&str>::new();
+    if !args.code_hide {
+        columns.push(labels.get("code"));
+    }
+    if with_level {
+        columns.push(labels.get("level"));
+    }
+    columns.push(labels.get("element_name"));
+    if args.flags {
+        columns.push(labels.get("flags"));
+    }
+    columns.extend([
+        labels.get("element_description"),
+        labels.get("data_type"),
+        labels.get("cardinality"),
+        labels.get("binding_requirements"),
+        labels.get("relevance_full"),
+        labels.get("relevance_basic"),
+    ]);
+    if args.provenance {
+        columns.push(labels.get("source"));
+    }
+    if args.obligations {
+        columns.push(labels.get("obligations"));
+    }
+    format!("| {} |\n|{}|", columns.join(" | "), vec!["---"; columns.len()].join("|"))
+}
+
+/// Flags of an element using the symbols of the FHIR specification
+fn element_flags(element: &ElementInfo) -> String {
+    let mut flags = Vec::<&str>::new();
+    if element.must_support {
+        flags.push("S");
+    }
+    if element.is_modifier {
+        flags.push("?!");
+    }
+    if element.is_summary {
+        flags.push("Σ");
+    }
+    flags.join(" ")
+}
+
+/// Writes the cells following the element name: description, datatype, cardinality, binding and support levels
+fn write_table_cells<W: Write>(
+    writer: &mut W,
+    element: &ElementInfo,
+    doc: &StructureDefTreeInfo,
+    ctx: &TableContext,
+    footnotes: &mut Vec<BindingFootnote>,
+) -> std::io::Result<()> {
+    let (args, labels) = (ctx.args, &ctx.labels);
+    let sanitize = |text: &str| sanitize_markdown(text, args.text_mode, args.truncate);
+    let description = if element.short == element.definition {
+        sanitize(&element.short)
+    } else {
+        format!("{}<br/>{}", sanitize(&element.short), sanitize(&element.definition))
+    };
+    write!(writer, " {} |", description)?;
+
+    if element.id.contains('.') {
+        let datatypes = if args.links_hide {
+            reduce_datatypes(&element.datatype)
+        } else {
+            // datatypes that are other processed structure definitions link to their tables
+            let linked = element
+                .datatype
+                .iter()
+                .map(|datatype| {
+                    if let Some(target) = ctx.index.get(datatype) {
+                        format!("[{}]({})", datatype, args.link_pattern.replace("{id}", &target.id))
                     } else {
-                        get_slice_after_last_occurrence(actor, '/').ok_or("Wrong actor URL")?
-                    };
-                    write!(writer, "<th>{}</th>", actor_name)?;
-                }
-                writeln!(writer, "</tr>")?;
+                        datatype.to_string()
+                    }
+                })
+                .collect::<Vec<_>>();
+            reduce_datatypes(&linked)
+        };
+        write!(
+            writer,
+            " {} | {} |",
+            datatypes,
+            labels.cardinality(&element.min, &element.max)
+        )?;
+    } else {
+        write!(writer, " {} {} | |", labels.get("derived_from"), doc.base)?;
+    }
 
-                doc.element_tree.traverse(
-                    |_idx, element, _| {
-                        if !args.only_obligations || !element.obligation.is_empty() {
-                            let hier_level: usize = count_char_occurrences(&element.id, '.');
-                            let element_part: String = if hier_level > 0 {
-                                get_slice_after_last_occurrence(&element.id, '.').unwrap()
-                            } else {
-                                element.id.clone()
-                            };
-                            // let element_path: String = if hier_level > 0 {
-                            //     get_slice_after_first_occurrence(&element.id, '.')
-                            //         .unwrap_or(element.id.clone())
-                            // } else {
-                            //     element.id.clone()
-                            // };
-                            // let element_path_no_x =
-                            //     element_path.strip_suffix("[x]").unwrap_or(&element_path);
+    if args.binding_footnotes
+        && let Some(name) = value_set_name(element)
+    {
+        let footnote = BindingFootnote::of(element, name);
+        let number = match footnotes.iter().position(|f| *f == footnote) {
+            Some(position) => position + 1,
+            None => {
+                footnotes.push(footnote);
+                footnotes.len()
+            }
+        };
+        write!(writer, " [{}](#{}){} |", number, footnote_anchor(doc, number), inline_codes(element, args))?;
+    } else if let Some(binding) = element.value_set_title.as_ref().or(element.binding.as_ref()) {
+        write!(writer, " {}{} |", sanitize(binding), inline_codes(element, args))?;
+    } else {
+        write!(writer, " |")?;
+    }
 
-                            // write!(writer, "<tr><td>{}</td>", element_path_no_x).unwrap();
-                            let element_part_no_x = element_part.replace("[x]", "");
-                            write!(
-                                writer,
-                                "<td>{}{}</td>",
-                                "&nbsp;&nbsp;".repeat(hier_level),
-                                camel_to_spaced_pascal(&element_part_no_x)
-                            )
-                            .unwrap();
+    for actor in [
+        "https://ehds.eu/specifications/fhir/actor-full",
+        "https://ehds.eu/specifications/fhir/actor-basic",
+    ] {
+        if let Some((_, code, _)) = element.obligation.iter().find(|o| o.0 == actor) {
+            write!(writer, " {} |", match get_slice_before_first_occurrence(code, ':') {
+                Some(s) => s,
+                None => code.to_string(),
+            })?;
+        } else {
+            // a single empty cell, the header has one column per actor
+            write!(writer, " |")?;
+        }
+    }
 
-                            let description = if element.short == element.definition {
-                                element.short.clone()
-                            } else {
-                                format!(
-                                    "{}<br/>{}",
-                                    element.short,
-                                    element.definition.replace("\n", "<br/>")
-                                )
-                            };
-                            write!(writer, "<td>{}</td>", description).unwrap();
+    if args.provenance {
+        let source = ctx.sources.get(&doc.id).and_then(|sources| sources.get(element.id.as_ref()));
+        write!(writer, " {} |", source.map(|s| s.as_str()).unwrap_or_default())?;
+    }
+    if args.obligations {
+        let obligations = element
+            .obligation
+            .iter()
+            .filter(|(actor, _, _)| args.actor.as_ref().is_none_or(|selected| is_actor(actor, selected)))
+            .map(|(actor, code, _)| {
+                format!("{}: {}", get_slice_after_last_occurrence(actor, '/').unwrap_or(actor.clone()), code)
+            })
+            .collect::<Vec<_>>();
+        write!(writer, " {} |", obligations.join("<br/>"))?;
+    }
+    Ok(())
+}
 
-                            write!(writer, "<td>{}</td>", reduce_datatypes(&element.datatype))
-                                .unwrap();
+/// The codes of a small value set of a required binding, each on a line after the binding, with
+/// `--inline-codes`
+fn inline_codes(element: &ElementInfo, args: &TableArgs) -> String {
+    match args.inline_codes {
+        Some(max_codes)
+            if element.binding_strength.as_deref() == Some("required")
+                && !element.value_set_codes.is_empty()
+                && element.value_set_codes.len() <= max_codes =>
+        {
+            element
+                .value_set_codes
+                .iter()
+                .map(|(_, code, display)| {
+                    let code = sanitize_markdown(code, args.text_mode, None);
+                    if display.is_empty() {
+                        format!("<br/>{}", code)
+                    } else {
+                        format!("<br/>{} — {}", code, sanitize_markdown(display, args.text_mode, None))
+                    }
+                })
+                .collect()
+        }
+        _ => String::new(),
+    }
+}
 
-                            write!(writer, "<td>{}..{}</td>", element.min, element.max).unwrap();
+/// A binding shown below a table, referred to by its number in the binding cell
+#[derive(PartialEq)]
+struct BindingFootnote {
+    name: String,
+    canonical: Option<String>,
+    version: Option<String>,
+    strength: Option<String>,
+}
 
-                            if let Some(binding) = &element.binding {
-                                write!(writer, "<td>{}</td>", binding).unwrap();
-                            } else {
-                                write!(writer, "<td></td>").unwrap();
-                            }
+impl BindingFootnote {
+    fn of(element: &ElementInfo, name: String) -> Self {
+        let (canonical, version) = match element.value_set.as_deref().map(|vs| vs.split_once('|')) {
+            Some(Some((canonical, version))) => (Some(canonical.to_string()), Some(version.to_string())),
+            Some(None) => (element.value_set.as_deref().map(str::to_string), None),
+            None => (None, None),
+        };
+        BindingFootnote {
+            name,
+            canonical,
+            version,
+            strength: element.binding_strength.as_deref().map(str::to_string),
+        }
+    }
+}
 
-                            let mut obligation_map =
-                                HashMap::<String, Vec<(String, String)>>::new();
-                            if !element.obligation.is_empty() {
-                                for obligation in &element.obligation {
-                                    let actor = obligation.0.clone();
-                                    let code = obligation.1.clone();
-                                    let documentation = obligation.2.clone();
-                                    let codes = obligation_map.entry(actor).or_default();
-                                    codes.push((code, documentation));
-                                }
-                            }
+fn footnote_anchor(doc: &StructureDefTreeInfo, number: usize) -> String {
+    slugify(&format!("{}-binding-{}", doc.id, number))
+}
 
-                            for actor in unique_actors.iter() {
-                                if let Some(codes) = obligation_map.get(actor) {
-                                    write!(
-                                        writer,
-                                        "<td><table>{}</table></td>",
-                                        codes
-                                            .iter()
-                                            .map(|(code, documentation)| {
-                                                if documentation.is_empty() {
-                                                    format!("<tr><td>{}</td><td></td></tr>", code)
-                                                } else {
-                                                    format!(
-                                                        "<tr><td>{}</td><td>{}</td></tr>",
-                                                        code, documentation
-                                                    )
-                                                }
-                                            })
-                                            .collect::<Vec<_>>()
-                                            .join("")
-                                    )
-                                    .unwrap();
-                                } else {
-                                    write!(writer, "<td></td>").unwrap();
-                                }
-                            }
+/// Writes the footnotes of a table from number `first` + 1, the earlier ones being below earlier
+/// tables of the structure definition
+fn write_binding_footnotes<W: Write>(
+    writer: &mut W,
+    doc: &StructureDefTreeInfo,
+    footnotes: &[BindingFootnote],
+    first: usize,
+    ctx: &TableContext,
+) -> std::io::Result<()> {
+    if footnotes.len() <= first {
+        return Ok(());
+    }
+    writeln!(writer)?;
+    for (position, footnote) in footnotes.iter().enumerate().skip(first) {
+        let mut parts = vec![format!("**{}**", sanitize_markdown(&footnote.name, ctx.args.text_mode, None))];
+        if let Some(canonical) = &footnote.canonical {
+            parts.push(format!("[{}]({})", canonical, canonical));
+        }
+        if let Some(version) = &footnote.version {
+            parts.push(format!("{} {}", ctx.labels.get("version"), version));
+        }
+        if let Some(strength) = &footnote.strength {
+            parts.push(strength.clone());
+        }
+        writeln!(
+            writer,
+            "{}. <a id=\"{}\"></a>{}",
+            position + 1,
+            footnote_anchor(doc, position + 1),
+            parts.join(", ")
+        )?;
+    }
+    Ok(())
+}
 
-                            writeln!(writer, "</tr>").unwrap();
-                        }
-                    },
-                    |_, _, _| (),
-                    &mut (),
-                );
+/// Whether an actor canonical URL is the selected one, given by URL or its last part
+fn is_actor(actor: &str, selected: &str) -> bool {
+    actor == selected || get_slice_after_last_occurrence(actor, '/').is_some_and(|name| name == selected)
+}
 
-                // let no_of_actors = unique_actors.len();
+/// Whether an element is a row of the table, all unless an actor is selected, then the root and
+/// the elements with obligations for the actor
+fn shows_table_row(element: &ElementInfo, args: &TableArgs) -> bool {
+    match &args.actor {
+        Some(selected) => {
+            !element.id.contains('.') || element.obligation.iter().any(|(actor, _, _)| is_actor(actor, selected))
+        }
+        None => true,
+    }
+}
 
-                writeln!(writer, "</table>")?;
-            }
+/// Writes a heading per element with children followed by a table of its direct children
+fn write_table_sections<W: Write>(
+    writer: &mut W,
+    doc: &StructureDefTreeInfo,
+    codes: &HashMap<usize, String>,
+    ctx: &TableContext,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (args, labels) = (ctx.args, &ctx.labels);
+    let mut elements = Vec::<(usize, &ElementInfo)>::new();
+    doc.element_tree.traverse(|idx, element, _| elements.push((idx, element)), |_, _, _| (), &mut ());
+
+    let mut children = HashMap::<&str, Vec<(usize, &ElementInfo)>>::new();
+    for (idx, element) in elements.iter() {
+        if let Some(parent) = doc.element_tree.get_parent_of(Some(*idx)).and_then(|parent| doc.element_tree.get(parent)) {
+            children.entry(parent.id.as_ref()).or_default().push((*idx, element));
         }
     }
 
+    // numbered across the sections of the structure definition
+    let mut footnotes = Vec::<BindingFootnote>::new();
+    for (idx, element) in elements.iter() {
+        let Some(section_children) = children.get(element.id.as_ref()) else {
+            continue;
+        };
+        let first_footnote = footnotes.len();
+        writeln!(writer, "\n<a id=\"{}\"></a>\n\n### {}\n", element_anchor(doc, &element.id), element.id)?;
+        if let Some(parent) = doc.element_tree.get_parent_of(Some(*idx)).and_then(|parent| doc.element_tree.get(parent)) {
+            writeln!(writer, "{} [{}](#{})\n", labels.get("part_of"), parent.id, element_anchor(doc, &parent.id))?;
+        }
+        writeln!(writer, "{}", table_header(ctx, false))?;
+        for (child_idx, child) in section_children.iter().filter(|(_, child)| shows_table_row(child, args)) {
+            if !args.code_hide {
+                write!(writer, "| {} ", codes.get(child_idx).map(|c| c.as_str()).unwrap_or_default())?;
+            }
+            let element_part = get_slice_after_last_occurrence(&child.id, '.').unwrap_or(child.id.to_string());
+            if children.contains_key(child.id.as_ref()) {
+                write!(writer, "| [{}](#{}) |", element_part, element_anchor(doc, &child.id))?;
+            } else {
+                write!(writer, "| {} |", element_part)?;
+            }
+            if args.flags {
+                write!(writer, " {} |", element_flags(child))?;
+            }
+            write_table_cells(writer, child, doc, ctx, &mut footnotes)?;
+            writeln!(writer)?;
+        }
+        write_binding_footnotes(writer, doc, &footnotes, first_footnote, ctx)?;
+    }
     Ok(())
 }
 
@@ -632,79 +2949,468 @@ fn load_actor_files(path: &PathBuf) -> Result<HashMap<String, String>, Box<dyn s
     Ok(actors)
 }
 
+fn parse_prefix_map(entries: &[String]) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    let mut prefix_map = HashMap::<String, String>::new();
+    for entry in entries {
+        let (id, prefix) = entry
+            .split_once('=')
+            .ok_or(format!("Invalid prefix mapping '{}', expected <id>=<prefix>", entry))?;
+        prefix_map.insert(id.trim().to_string(), prefix.trim().to_string());
+    }
+    Ok(prefix_map)
+}
+
+/// Loads a JSON object with string values, e.g. a package mapping or a file of stable codes
+fn load_string_map(path: &PathBuf) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    let doc = load_json_from_file(path)?;
+    let mut map = HashMap::<String, String>::new();
+    for (key, value) in doc.as_object().ok_or(format!("'{}' must contain a JSON object", path.display()))? {
+        let value = value.as_str().ok_or(format!("Value of '{}' must be a string", key))?;
+        map.insert(key.clone(), value.to_string());
+    }
+    Ok(map)
+}
+
 fn load_structure_definition_files(
-    files: &[PathBuf],
+    common: &CommonArgs,
 ) -> Result<Vec<StructureDefTreeInfo>, Box<dyn std::error::Error>> {
+    let inputs = input_files(common);
+    let mut resources = Vec::<(&PathBuf, serde_json::Value)>::new();
+    for file in inputs.files.iter() {
+        match resource_reader::read_resource(file) {
+            Ok(doc) => resources.push((inputs.origin(file), doc)),
+            Err(e) => diagnostics::emit(Diagnostic::error("unreadable-file", e.to_string()).in_file(inputs.origin(file))),
+        }
+    }
+    structure_definitions_of(common, &resources)
+}
+
+/// The structure definitions among resources read from files, bound to the value sets among them,
+/// with the options of the command applied
+fn structure_definitions_of(
+    common: &CommonArgs,
+    resources: &[(&PathBuf, serde_json::Value)],
+) -> Result<Vec<StructureDefTreeInfo>, Box<dyn std::error::Error>> {
+    let value_sets = load_value_sets(resources);
+
     let mut docs = Vec::<StructureDefTreeInfo>::new();
-    for file in files.iter() {
-        match load_single_structure_definition_file_into_tree(file) {
+    let mut interner = Interner::default();
+    for (file, doc) in resources.iter() {
+        if doc["resourceType"] == "ValueSet" || doc["resourceType"] == "Bundle" {
+            summary::file_read();
+            continue;
+        }
+        if let Some(resource_type) = doc["resourceType"].as_str().filter(|t| OTHER_RESOURCES.contains(t)) {
+            summary::file_skipped(file, &format!("{} is not a structure definition", resource_type));
+            continue;
+        }
+        let fhir_version = FhirVersion::detect(doc, common.fhir_version);
+        match load_single_structure_definition_file_into_tree(
+            file,
+            doc,
+            fhir_version,
+            &value_sets,
+            common.lang.as_deref(),
+            &mut interner,
+        ) {
             Ok(doc_info) => {
+                summary::file_read();
+                summary::elements_processed(doc_info.element_tree.iter().count());
                 docs.push(doc_info);
             }
             Err(e) => {
-                println!("Error reading file '{}': {}", file.display(), e);
+                let diagnostic = match e.downcast::<Diagnostic>() {
+                    Ok(diagnostic) => *diagnostic,
+                    Err(e) => Diagnostic::error("invalid-structure-definition", e.to_string()),
+                };
+                diagnostics::emit(diagnostic.in_file(file));
             }
         }
     }
+    if common.flatten {
+        docs = provenance::flatten(docs);
+    }
+    if common.changed_only {
+        // all are compared before any is filtered, as a filtered base would hide inherited elements
+        let index = DocIndex::new(&docs);
+        let changed = docs.iter().map(|doc| provenance::changed_elements(doc, &index)).collect::<Vec<_>>();
+        docs = docs
+            .into_iter()
+            .zip(changed)
+            .map(|(doc, changed)| match changed {
+                Some(changed) => retain_elements(doc, |element| changed.contains(element.id.as_ref())),
+                None => {
+                    diagnostics::emit(
+                        Diagnostic::warning("unresolved-base", format!("Base {} is not loaded, all elements are shown", doc.base))
+                            .in_file(&doc.source),
+                    );
+                    doc
+                }
+            })
+            .collect();
+    }
+    if common.summary_only {
+        docs = docs.into_iter().map(|doc| retain_elements(doc, |element| element.is_summary)).collect();
+    }
+    if common.must_support_only {
+        docs = docs.into_iter().map(|doc| retain_elements(doc, |element| element.must_support)).collect();
+    }
+    if let Some(subtree) = &common.subtree {
+        let in_subtree = |id: &str| {
+            id.strip_prefix(subtree.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', ':']))
+        };
+        docs = docs
+            .into_iter()
+            .filter(|doc| doc.element_tree.iter().any(|(_, element)| in_subtree(&element.id)))
+            .map(|doc| retain_elements(doc, |element| in_subtree(&element.id)))
+            .collect();
+    }
+    for doc in docs.iter_mut() {
+        if common.redact.contains(&Redaction::Definitions) {
+            doc.description.clear();
+        }
+        for (_idx, element) in doc.element_tree.iter_mut() {
+            redact(element, &common.redact);
+        }
+    }
     Ok(docs)
 }
 
+/// Leaves the redacted content out of an element
+fn redact(element: &mut ElementInfo, redactions: &[Redaction]) {
+    for redaction in redactions {
+        match redaction {
+            Redaction::Definitions => element.definition = element.short.clone(),
+            Redaction::Requirements => element.requirements = None,
+            Redaction::Constraints => element.constraints.iter_mut().for_each(|(_, _, human)| human.clear()),
+            Redaction::Mappings => (),
+            Redaction::Bindings => element.binding = None,
+            Redaction::ValueSets => {
+                element.value_set = None;
+                element.value_set_title = None;
+                element.value_set_codes = Rc::from([]);
+            }
+            Redaction::Obligations => element.obligation.clear(),
+        }
+    }
+}
+
+/// Leaves the redacted content out of a resource other than a structure definition: its
+/// descriptions and documentation with the definitions, and the targets of its mappings
+fn redact_resource(resource: &mut serde_json::Value, redactions: &[Redaction]) {
+    fn remove_members(value: &mut serde_json::Value, members: &[&str]) {
+        match value {
+            serde_json::Value::Array(items) => items.iter_mut().for_each(|item| remove_members(item, members)),
+            serde_json::Value::Object(object) => {
+                object.retain(|member, _| !members.contains(&member.as_str()));
+                object.values_mut().for_each(|item| remove_members(item, members));
+            }
+            _ => (),
+        }
+    }
+    if redactions.contains(&Redaction::Definitions) {
+        remove_members(resource, &["description", "documentation"]);
+    }
+    if redactions.contains(&Redaction::Mappings) {
+        let is_concept_map = resource["resourceType"] == "ConceptMap";
+        let groups = resource["group"].as_array_mut().into_iter().flatten();
+        if is_concept_map {
+            // the relationships are kept, without the codes they map to
+            for group in groups {
+                group.as_object_mut().map(|group| group.remove("unmapped"));
+                for target in group["element"].as_array_mut().into_iter().flatten().flat_map(|element| {
+                    element["target"].as_array_mut().into_iter().flatten()
+                }) {
+                    remove_members(target, &["code", "display", "comment"]);
+                }
+            }
+        } else {
+            for group in groups {
+                remove_members(group, &["target"]);
+            }
+        }
+    }
+}
+
+/// Resources rendered by their own subcommand, which the generators of structure definitions skip
+const OTHER_RESOURCES: &[&str] = &["StructureMap", "ConceptMap", "SearchParameter", "CapabilityStatement"];
+
+/// The files given to a command, which are never overwritten, sorted with `--deterministic`, with
+/// the resources built from the FSH input in place of it
+fn input_files(common: &CommonArgs) -> fsh::Inputs {
+    output::protect_inputs(&common.files);
+    let mut files = common.files.iter().collect::<Vec<_>>();
+    if common.deterministic {
+        // paths compare byte-wise, not by locale
+        files.sort();
+        files.dedup();
+        summary::omit_elapsed_time();
+    }
+    fsh::expand(files, &common.sushi, common.fhir_version, Path::to_path_buf)
+}
+
+/// The resources of the types among the files, also the entries of bundles, for the subcommands
+/// rendering other resources than structure definitions. Files without one are skipped.
+fn load_resources(common: &CommonArgs, resource_types: &[&str]) -> Vec<(PathBuf, serde_json::Value)> {
+    let mut resources = Vec::<(PathBuf, serde_json::Value)>::new();
+    let inputs = input_files(common);
+    for built in inputs.files.iter() {
+        let file = inputs.origin(built);
+        let doc = match resource_reader::read_resource(built) {
+            Ok(doc) => doc,
+            Err(e) => {
+                diagnostics::emit(Diagnostic::error("unreadable-file", e.to_string()).in_file(file));
+                continue;
+            }
+        };
+        let found = if doc["resourceType"] == "Bundle" {
+            doc["entry"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|entry| entry["resource"].clone())
+                .filter(|resource| resource_types.contains(&resource["resourceType"].as_str().unwrap_or_default()))
+                .collect()
+        } else if resource_types.contains(&doc["resourceType"].as_str().unwrap_or_default()) {
+            vec![doc]
+        } else {
+            Vec::new()
+        };
+        if found.is_empty() {
+            summary::file_skipped(file, &format!("no {} in the file", resource_types.join(" or ")));
+        } else {
+            summary::file_read();
+            resources.extend(found.into_iter().map(|mut resource| {
+                redact_resource(&mut resource, &common.redact);
+                (file.clone(), resource)
+            }));
+        }
+    }
+    resources
+}
+
+/// Keeps the root, the elements for which `keep` is true and their ancestors
+fn retain_elements<F>(doc: StructureDefTreeInfo, keep: F) -> StructureDefTreeInfo
+where
+    F: Fn(&ElementInfo) -> bool,
+{
+    let tree = &doc.element_tree;
+    let mut kept = HashSet::<usize>::new();
+    for (idx, element) in tree.iter() {
+        if kept.contains(&idx) || !(keep(element) || tree.get_parent_of(Some(idx)).is_none()) {
+            continue;
+        }
+        let mut ancestor = Some(idx);
+        while let Some(ancestor_idx) = ancestor
+            && kept.insert(ancestor_idx)
+        {
+            ancestor = tree.get_parent_of(ancestor);
+        }
+    }
+
+    // parents come before their children, so their new index is known when a child is added
+    let mut element_tree = Tree::new();
+    let mut new_indices = HashMap::<usize, usize>::new();
+    for (idx, element) in tree.iter().filter(|(idx, _)| kept.contains(idx)) {
+        let new_idx = match tree.get_parent_of(Some(idx)).and_then(|parent| new_indices.get(&parent)) {
+            Some(&parent) => element_tree.add_child(parent, element.clone()),
+            None => element_tree.add_node(element.clone()),
+        };
+        new_indices.insert(idx, new_idx);
+    }
+    StructureDefTreeInfo { element_tree, ..doc }
+}
+
+/// What is used of a ValueSet resource among the loaded files
+#[derive(Default)]
+struct ValueSetInfo {
+    /// title, or name
+    title: Option<String>,
+    /// system, code and display of the concepts listed in the compose or expansion
+    codes: Rc<[(String, String, String)]>,
+}
+
+/// Codes of a value set expansion, including the nested ones
+fn expansion_codes(contains: &serde_json::Value, codes: &mut Vec<(String, String, String)>) {
+    for concept in contains.as_array().into_iter().flatten() {
+        if let (Some(system), Some(code)) = (concept["system"].as_str(), concept["code"].as_str()) {
+            let display = concept["display"].as_str().unwrap_or_default();
+            codes.push((system.to_string(), code.to_string(), display.to_string()));
+        }
+        expansion_codes(&concept["contains"], codes);
+    }
+}
+
+/// The ValueSet resources among the loaded files, also those in bundles, keyed by canonical URL
+fn load_value_sets(resources: &[(&PathBuf, serde_json::Value)]) -> HashMap<String, ValueSetInfo> {
+    let mut value_sets = HashMap::<String, ValueSetInfo>::new();
+    let mut add_value_set = |vs: &serde_json::Value| {
+        if vs["resourceType"] == "ValueSet"
+            && let Some(url) = vs["url"].as_str()
+        {
+            let title = vs["title"].as_str().or(vs["name"].as_str()).map(|s| s.to_string());
+            let mut codes = Vec::<(String, String, String)>::new();
+            for include in vs["compose"]["include"].as_array().into_iter().flatten() {
+                let system = include["system"].as_str().unwrap_or_default();
+                for concept in include["concept"].as_array().into_iter().flatten() {
+                    if let Some(code) = concept["code"].as_str() {
+                        let display = concept["display"].as_str().unwrap_or_default();
+                        codes.push((system.to_string(), code.to_string(), display.to_string()));
+                    }
+                }
+            }
+            expansion_codes(&vs["expansion"]["contains"], &mut codes);
+            // the compose and the expansion may list a concept with different displays
+            let mut seen = HashSet::<(String, String)>::new();
+            codes.retain(|(system, code, _)| seen.insert((system.clone(), code.clone())));
+            let codes = Rc::<[(String, String, String)]>::from(codes);
+            if let Some(version) = vs["version"].as_str() {
+                let info = ValueSetInfo { title: title.clone(), codes: Rc::clone(&codes) };
+                value_sets.insert(format!("{}|{}", url, version), info);
+            }
+            value_sets.insert(url.to_string(), ValueSetInfo { title, codes });
+        }
+    };
+    for (_file, doc) in resources.iter() {
+        if doc["resourceType"] == "Bundle" {
+            for entry in doc["entry"].as_array().into_iter().flatten() {
+                add_value_set(&entry["resource"]);
+            }
+        } else {
+            add_value_set(doc);
+        }
+    }
+    value_sets
+}
+
+/// Translation into `lang` from the translation extensions of a string, given the primitive
+/// extension object of the string, e.g. `_short`. A language without region also matches one with
+/// a region, e.g. "sv" matches "sv-SE".
+fn translation(primitive: &serde_json::Value, lang: Option<&str>) -> Option<String> {
+    let lang = lang?;
+    let translations = primitive["extension"]
+        .as_array()?
+        .iter()
+        .filter(|ext| ext["url"] == "http://hl7.org/fhir/StructureDefinition/translation")
+        .filter_map(|ext| {
+            let parts = ext["extension"].as_array()?;
+            let part = |url: &str| parts.iter().find(|p| p["url"] == url);
+            let code = part("lang")?["valueCode"].as_str()?;
+            let content = part("content")?;
+            let content = content["valueString"].as_str().or(content["valueMarkdown"].as_str())?;
+            Some((code, content))
+        })
+        .collect::<Vec<_>>();
+    let exact = translations.iter().find(|(code, _)| code.eq_ignore_ascii_case(lang));
+    let regional = || {
+        translations.iter().find(|(code, _)| {
+            get_slice_before_first_occurrence(code, '-').is_some_and(|primary| primary.eq_ignore_ascii_case(lang))
+        })
+    };
+    exact.or_else(regional).map(|(_, content)| content.to_string())
+}
+
+/// Node of the parent of an element in the tree: for a slice the element it slices, e.g.
+/// Patient.identifier for Patient.identifier:ssn and Patient.identifier:ssn for the reslice
+/// Patient.identifier:ssn/temporary, otherwise the element of the path above it. Below a type of
+/// a choice element, e.g. Observation.valueQuantity.unit, the parent is the choice element
+/// Observation.value[x] when there is no Observation.valueQuantity.
+fn parent_node_of(element_id: &str, nodes: &HashMap<&str, usize>) -> Option<usize> {
+    let (path, name) = element_id.rsplit_once('.')?;
+    let sliced = name.rsplit_once('/').map(|(sliced, _)| sliced).or(name.split_once(':').map(|(sliced, _)| sliced));
+    if let Some(sliced) = sliced
+        && let Some(&node) = nodes.get(format!("{}.{}", path, sliced).as_str())
+    {
+        return Some(node);
+    }
+    if let Some(&node) = nodes.get(path) {
+        return Some(node);
+    }
+    let (choice_path, type_name) = path.rsplit_once('.')?;
+    let stem = &type_name[..type_name.find(|c: char| c.is_ascii_uppercase())?];
+    nodes.get(format!("{}.{}[x]", choice_path, stem).as_str()).copied()
+}
+
 fn load_single_structure_definition_file_into_tree(
-    file: &PathBuf,
+    file: &Path,
+    doc: &serde_json::Value,
+    fhir_version: FhirVersion,
+    value_sets: &HashMap<String, ValueSetInfo>,
+    lang: Option<&str>,
+    interner: &mut Interner,
 ) -> Result<StructureDefTreeInfo, Box<dyn std::error::Error>> {
-    let doc = load_json_from_file(file)?;
-    let id = doc["id"].as_str().ok_or("Missing id")?;
+    let id = doc["id"].as_str().ok_or_else(|| Diagnostic::error("missing-id", "Missing id"))?;
     let snapshot = doc["snapshot"]["element"]
         .as_array()
-        .ok_or("Missing snapshot")?;
+        .ok_or_else(|| Diagnostic::error("missing-snapshot", "Missing snapshot"))?;
     let mut element_tree: Tree<ElementInfo> = Tree::new();
-    // let mut elements = Vec::<ElementInfo>::new();
+    // node of each element id, borrowed from the document, to find the parents without a search
+    let mut nodes = HashMap::<&str, usize>::new();
     for element in snapshot.iter() {
-        let element_id = element["id"].as_str().ok_or("Missing element id")?;
-        let parent_id = element_id
-            .rfind('.')
-            .map(|last_index| &element_id[..last_index]);
-        let parent_node = if let Some(pid) = parent_id {
-            element_tree.find_first(|e| e.id == pid)
+        let element_id = element["id"]
+            .as_str()
+            .ok_or_else(|| Diagnostic::error("missing-element-id", "Missing element id"))?;
+        let parent_node = if element_id.contains('.') {
+            let parent_node = parent_node_of(element_id, &nodes);
+            if parent_node.is_none() {
+                diagnostics::emit(
+                    Diagnostic::warning("missing-parent", "Parent element not found, shown at the top level")
+                        .at(element_id)
+                        .in_file(file),
+                );
+            }
+            parent_node
         } else {
             None
         };
         let short = element["short"]
             .as_str()
-            .ok_or("Missing short description")?
-            .to_string();
+            .ok_or_else(|| Diagnostic::error("missing-short", "Missing short description").at(element_id))?;
+        let short = match translation(&element["_short"], lang) {
+            Some(translated) => interner.intern(&translated),
+            None => interner.intern(short),
+        };
         let definition = element["definition"]
             .as_str()
-            .ok_or("Missing definition")?
-            .to_string();
+            .ok_or_else(|| Diagnostic::error("missing-definition", "Missing definition").at(element_id))?;
+        let definition = match translation(&element["_definition"], lang) {
+            Some(translated) => interner.intern(&translated),
+            None => interner.intern(definition),
+        };
         let requirements = element["requirements"].as_str().map(|s| s.to_string());
 
-        let mut datatype = Vec::<String>::new();
+        let mut datatype = Vec::<Rc<str>>::new();
+        // datatypes that are only reached through a Reference
+        let mut reference = Vec::<Rc<str>>::new();
         if let Some(type_array) = element["type"].as_array() {
             for dt in type_array {
                 if let Some(code) = dt["code"].as_str() {
-                    let code = code.to_string();
-                    if code.starts_with("http") {
-                        if let Some(end) = get_slice_after_last_occurrence(&code, '/') {
-                            datatype.push(end);
+                    if let Some(primitive) = primitive_of_system_type(dt) {
+                        datatype.push(interner.intern(primitive));
+                    } else if code.starts_with("http") {
+                        if let Some((_, end)) = code.rsplit_once('/') {
+                            datatype.push(interner.intern(end));
                         };
                     } else if code == "Reference" {
-                        // TODO: does not distinguish between Reference and direct datatype
                         if let Some(profiles) = dt["targetProfile"].as_array() {
                             for profile_value in profiles {
-                                if let Some(profile) = profile_value.as_str() {
-                                    let profile = profile.to_string();
-                                    if let Some(end) =
-                                        get_slice_after_last_occurrence(&profile, '/')
-                                    {
-                                        datatype.push(end);
-                                    };
+                                if let Some((_, end)) = profile_value.as_str().and_then(|profile| profile.rsplit_once('/')) {
+                                    let end = interner.intern(end);
+                                    reference.push(Rc::clone(&end));
+                                    datatype.push(end);
                                 }
                             }
                         }
                     } else {
-                        datatype.push(code);
+                        datatype.push(interner.intern(code));
                     }
+                } else {
+                    diagnostics::emit(
+                        Diagnostic::warning("unresolved-type", "Type without a code is left out")
+                            .at(element_id)
+                            .in_file(file),
+                    );
                 }
             }
         }
@@ -712,20 +3418,22 @@ fn load_single_structure_definition_file_into_tree(
         let mut obligation = Vec::<(String, String, String)>::new();
         if let Some(ext_array) = element["extension"].as_array() {
             for ext in ext_array {
-                if ext["url"].as_str() == Some("http://hl7.org/fhir/StructureDefinition/obligation")
-                {
-                    let mut code = String::new();
-                    let mut actor = String::new();
+                if fhir_version.obligation_urls().contains(&ext["url"].as_str().unwrap_or_default()) {
+                    // an obligation may have several codes and actors, e.g. SHALL:populate and SHALL:handle
+                    let mut codes = Vec::<String>::new();
+                    let mut actors = Vec::<String>::new();
                     let mut documentation = String::new();
                     if let Some(ext2_array) = ext["extension"].as_array() {
                         for ext2 in ext2_array {
                             if ext2["url"].as_str() == Some("code") {
                                 if let Some(value) = ext2["valueCode"].as_str() {
-                                    code = value.to_string();
+                                    codes.push(value.to_string());
                                 }
-                            } else if ext2["url"].as_str() == Some("actor") {
+                            } else if ext2["url"].as_str() == Some("actor")
+                                || ext2["url"].as_str() == Some("actorId")
+                            {
                                 if let Some(value) = ext2["valueCanonical"].as_str() {
-                                    actor = value.to_string();
+                                    actors.push(value.to_string());
                                 }
                             } else if ext2["url"].as_str() == Some("documentation")
                                 && let Some(value) = ext2["valueMarkdown"].as_str()
@@ -734,29 +3442,37 @@ fn load_single_structure_definition_file_into_tree(
                             }
                         }
                     }
-                    if !code.is_empty() && !actor.is_empty() {
-                        obligation.push((actor, code, documentation));
+                    if actors.is_empty() && !codes.is_empty() {
+                        diagnostics::emit(
+                            Diagnostic::warning("obligation-without-actor", "Obligation without an actor is left out")
+                                .at(element_id)
+                                .in_file(file),
+                        );
+                    }
+                    for actor in actors.iter() {
+                        for code in codes.iter() {
+                            obligation.push((actor.clone(), code.clone(), documentation.clone()));
+                        }
                     }
                 }
             }
         }
 
-        let min = if element["min"].is_string() {
-            element["min"]
-                .as_str()
-                .ok_or(format!("Missing min cardinality: {:?}", element["min"]))?
-                .to_string()
-        } else {
-            element["min"].to_string()
+        // the min is a number, given as a string by some tools; 0 and 1 are taken without formatting
+        let min = match &element["min"] {
+            serde_json::Value::String(min) => interner.intern(min),
+            min if min.as_u64() == Some(0) => interner.intern("0"),
+            min if min.as_u64() == Some(1) => interner.intern("1"),
+            min => interner.intern(&min.to_string()),
         };
 
         let max = element["max"]
             .as_str()
-            .ok_or("Missing max cardinality")?
-            .to_string();
+            .ok_or_else(|| Diagnostic::error("missing-cardinality", "Missing max cardinality").at(element_id))?;
+        let max = interner.intern(max);
 
-        let mut global_min = min.clone();
-        let mut global_max: String = max.clone();
+        let mut global_min = Rc::clone(&min);
+        let mut global_max = Rc::clone(&max);
         let mut parent_iterator = parent_node;
         while let Some(p) = parent_iterator {
             if p == 0 {
@@ -764,24 +3480,24 @@ fn load_single_structure_definition_file_into_tree(
             }
             if let Some(e) = element_tree.get_data_of(p) {
                 let parent_min = &e.min;
-                if global_min == "0" {
+                if &*global_min == "0" {
                     // do nothing
                 } else {
                     let res = parent_min.cmp(&global_min);
                     if res == std::cmp::Ordering::Less {
-                        global_min = parent_min.clone();
+                        global_min = Rc::clone(parent_min);
                     };
                 }
 
                 let parent_max = &e.max;
-                if global_max == "*" {
+                if &*global_max == "*" {
                     // do nothing
-                } else if parent_max == "*" {
-                    global_max = parent_max.clone();
+                } else if &**parent_max == "*" {
+                    global_max = Rc::clone(parent_max);
                 } else {
                     let res = parent_max.cmp(&global_max);
                     if res == std::cmp::Ordering::Greater {
-                        global_max = parent_max.clone();
+                        global_max = Rc::clone(parent_max);
                     };
                 }
             }
@@ -791,51 +3507,122 @@ fn load_single_structure_definition_file_into_tree(
         let binding = element["binding"]["description"]
             .as_str()
             .map(|s| s.to_string());
-        let binding_strength = element["binding"]["strength"]
-            .as_str()
-            .map(|s| s.to_string());
+        let binding_strength = element["binding"]["strength"].as_str().map(|strength| interner.intern(strength));
+        let value_set = element["binding"]["valueSet"].as_str().map(|value_set| interner.intern(value_set));
+        // versioned canonicals fall back to the unversioned value set
+        let value_set_info = value_set.as_ref().and_then(|vs| {
+            value_sets
+                .get(vs.as_ref())
+                .or_else(|| value_sets.get(&get_slice_before_first_occurrence(vs, '|')?))
+        });
+        let value_set_title = value_set_info.and_then(|info| info.title.clone());
+        let value_set_codes = value_set_info.map(|info| Rc::clone(&info.codes)).unwrap_or_default();
+
+        let constraints = element["constraint"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|constraint| {
+                let member = |name: &str| constraint[name].as_str().unwrap_or_default().to_string();
+                (member("key"), member("severity"), member("human"))
+            })
+            .collect::<Vec<_>>();
+
+        let fixed_value = element.as_object().and_then(|properties| {
+            properties.iter().find_map(|(key, value)| {
+                let datatype = key.strip_prefix("fixed").or_else(|| key.strip_prefix("pattern"))?;
+                Some((datatype.to_string(), value.to_string()))
+            })
+        });
 
-        if let Some(parent) = parent_node {
-            element_tree.add_child(
-                parent,
-                ElementInfo {
-                    id: element_id.to_string(),
-                    short: short.clone(),
-                    definition: definition.clone(),
-                    datatype: datatype.clone(),
-                    min: min.clone(),
-                    max: max.to_string(),
-                    global_min: global_min.clone(),
-                    global_max: global_max.clone(),
-                    binding: binding.clone(),
-                    binding_strength: binding_strength.clone(),
-                    obligation: obligation.clone(),
-                    requirements: requirements.clone(),
-                },
-            );
-        } else {
-            element_tree.add_node(ElementInfo {
-                id: element_id.to_string(),
-                short: short.clone(),
-                definition: definition.clone(),
-                datatype: datatype.clone(),
-                min: min.clone(),
-                max: max.to_string(),
-                global_min: global_min.clone(),
-                global_max: global_max.clone(),
-                binding: binding.clone(),
-                binding_strength: binding_strength.clone(),
-                obligation: obligation.clone(),
-                requirements: requirements.clone(),
-            });
-        }
+        let depth = parent_node
+            .and_then(|parent| element_tree.get(parent))
+            .map_or(0, |parent| parent.depth + 1);
+
+        let must_support = element["mustSupport"].as_bool().unwrap_or(false);
+        let is_modifier = element["isModifier"].as_bool().unwrap_or(false);
+        let is_summary = element["isSummary"].as_bool().unwrap_or(false);
+
+        let element_info = ElementInfo {
+            id: interner.intern(element_id),
+            depth,
+            short,
+            definition,
+            datatype,
+            reference,
+            min,
+            max,
+            global_min,
+            global_max,
+            binding,
+            binding_strength,
+            value_set,
+            value_set_title,
+            value_set_codes,
+            fixed_value,
+            obligation,
+            constraints,
+            requirements,
+            must_support,
+            is_modifier,
+            is_summary,
+        };
+        let node = match parent_node {
+            Some(parent) => element_tree.add_child(parent, element_info),
+            None => element_tree.add_node(element_info),
+        };
+        nodes.entry(element_id).or_insert(node);
+    }
+
+    let url = doc["url"].as_str().unwrap_or_default().to_string();
+    let metadata = |name: &str| doc[name].as_str().unwrap_or_default().to_string();
+    let kind = doc["kind"].as_str().unwrap_or_default().to_string();
+
+    let is_abstract = doc["abstract"].as_bool().unwrap_or(false);
+    let is_interface = doc["extension"].as_array().is_some_and(|exts| {
+        exts.iter().any(|ext| {
+            ext["url"] == "http://hl7.org/fhir/StructureDefinition/structuredefinition-interface"
+                && ext["valueBoolean"] == true
+        })
+    });
+    let base_definition = doc["baseDefinition"]
+        .as_str()
+        .ok_or_else(|| Diagnostic::error("missing-base", "Missing base"))?;
+    let mut base = get_slice_after_last_occurrence(base_definition, '/').unwrap();
+    // logical models specialize Element before R5, report them like R5 ones
+    if kind == "logical" && base == fhir_version.logical_root() {
+        base = "Base".to_string();
     }
 
-    let base = get_slice_after_last_occurrence(doc["baseDefinition"].as_str().ok_or("Missing base")?, '/').unwrap();
+    let derivation = doc["derivation"].as_str().unwrap_or_default().to_string();
+    let sd_type = doc["type"].as_str().unwrap_or_default().to_string();
+    let contexts = doc["context"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|context| {
+            let member = |name: &str| context[name].as_str().unwrap_or_default().to_string();
+            (member("type"), member("expression"))
+        })
+        .collect::<Vec<_>>();
 
     Ok(StructureDefTreeInfo {
         id: id.to_string(),
+        url,
+        version: metadata("version"),
+        status: metadata("status"),
+        date: metadata("date"),
+        publisher: metadata("publisher"),
+        description: metadata("description"),
+        kind,
+        derivation,
+        sd_type,
         base,
+        is_abstract,
+        is_interface,
+        fhir_version,
+        contexts,
+        source: file.to_path_buf(),
         element_tree,
     })
 }
@@ -0,0 +1,108 @@
+use crate::doc_index::DocIndex;
+use crate::provenance::element_sources;
+use crate::output::{self, create_output_file};
+use crate::summary::status;
+use crate::utils::{get_slice_after_last_occurrence};
+use crate::{MetricsArgs, StructureDefTreeInfo};
+use serde_json::json;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Complexity measures of a structure definition, counting the elements that are allowed
+struct Metrics {
+    elements: usize,
+    max_depth: usize,
+    must_support: usize,
+    required_bindings: usize,
+    /// elements introduced or constrained by the structure definition rather than by a loaded base
+    constrained: usize,
+    extensions: usize,
+}
+
+impl Metrics {
+    fn of(doc: &StructureDefTreeInfo, index: &DocIndex) -> Self {
+        let sources = element_sources(doc, index);
+        let mut metrics = Metrics {
+            elements: 0,
+            max_depth: 0,
+            must_support: 0,
+            required_bindings: 0,
+            constrained: 0,
+            extensions: 0,
+        };
+        for (_idx, element) in doc.element_tree.iter().filter(|(_, e)| e.id.contains('.') && &*e.max != "0") {
+            metrics.elements += 1;
+            metrics.max_depth = metrics.max_depth.max(element.depth);
+            metrics.must_support += usize::from(element.must_support);
+            metrics.required_bindings += usize::from(element.binding_strength.as_deref() == Some("required"));
+            metrics.constrained += usize::from(sources.get(element.id.as_ref()) == Some(&doc.id));
+            let name = get_slice_after_last_occurrence(&element.id, '.').unwrap_or_default();
+            metrics.extensions += usize::from(name.starts_with("extension:") || name.starts_with("modifierExtension:"));
+        }
+        metrics
+    }
+
+    /// Share of the elements constrained by the structure definition, 0 without elements
+    fn constrained_ratio(&self) -> f64 {
+        if self.elements == 0 { 0.0 } else { self.constrained as f64 / self.elements as f64 }
+    }
+}
+
+/// Writes a markdown table of complexity measures per structure definition, and the same
+/// measures as JSON with `--json-file`
+pub fn generate_metrics(args: &MetricsArgs, docs: &[StructureDefTreeInfo]) -> Result<(), Box<dyn std::error::Error>> {
+    // the table and the JSON would run into each other on standard output
+    let on_stdout = |file: &Path| output::is_stdout() || file == Path::new("-");
+    if let Some(json_file) = &args.json_file
+        && on_stdout(&args.output_file)
+        && on_stdout(json_file)
+    {
+        return Err("The table and --json-file cannot both be written to standard output, write one of them to a file".into());
+    }
+    let mut writer = BufWriter::new(create_output_file(&args.output_file)?);
+    writeln!(writer, "# Metrics\n")?;
+    writeln!(
+        writer,
+        "| Structure definition | Elements | Max depth | Must support | Required bindings | Constrained | Constrained ratio | Extensions |\n|---|---|---|---|---|---|---|---|"
+    )?;
+    let index = DocIndex::new(docs);
+    let mut all_metrics = Vec::new();
+    for doc in docs.iter() {
+        status!("processing: {}", doc.id);
+        let metrics = Metrics::of(doc, &index);
+        writeln!(
+            writer,
+            "| {} | {} | {} | {} | {} | {} | {:.2} | {} |",
+            doc.id,
+            metrics.elements,
+            metrics.max_depth,
+            metrics.must_support,
+            metrics.required_bindings,
+            metrics.constrained,
+            metrics.constrained_ratio(),
+            metrics.extensions
+        )?;
+        all_metrics.push(json!({
+            "id": doc.id,
+            "url": doc.url,
+            "elements": metrics.elements,
+            "maxDepth": metrics.max_depth,
+            "mustSupport": metrics.must_support,
+            "requiredBindings": metrics.required_bindings,
+            "constrained": metrics.constrained,
+            "constrainedRatio": metrics.constrained_ratio(),
+            "extensions": metrics.extensions,
+        }));
+    }
+    writeln!(
+        writer,
+        "\nElements are counted without the root and the elements with max 0. An element is constrained when no loaded base has the same constraints."
+    )?;
+    // the table is complete before the JSON follows it in a --single-file
+    writer.flush()?;
+
+    if let Some(json_file) = &args.json_file {
+        serde_json::to_writer_pretty(BufWriter::new(create_output_file(json_file)?), &all_metrics)?;
+    }
+    Ok(())
+}
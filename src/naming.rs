@@ -0,0 +1,223 @@
+use crate::utils::{camel_to_spaced_pascal, get_slice_after_last_occurrence, load_json_from_file};
+use clap::{Args, ValueEnum};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+#[derive(ValueEnum, Clone, Debug, PartialEq)]
+pub enum NameStyle {
+    /// Split camel case into capitalized words, e.g. "Date Of Birth"
+    Spaced,
+    /// Split camel case into title case words, e.g. "Date of Birth"
+    Title,
+    /// Element name as in the structure definition, e.g. "dateOfBirth"
+    Original,
+    /// Full element path, e.g. "EHDSPatient.dateOfBirth"
+    Path,
+}
+
+/// Display labels per element path, consulted before the name style: the labels of an element by
+/// language in the order given, none for a label in any language
+#[derive(Clone, Debug, Default)]
+pub struct RenameMap(HashMap<String, Vec<(Option<String>, String)>>);
+
+impl RenameMap {
+    /// The label of an element in a language, or else the one without a language; without a
+    /// language, the one without a language or else the first
+    fn label(&self, element_id: &str, lang: Option<&str>) -> Option<&String> {
+        let labels = self.0.get(element_id)?;
+        let in_lang = |lang: Option<&str>| labels.iter().find(|(l, _)| l.as_deref() == lang).map(|(_, label)| label);
+        match lang {
+            Some(_) => in_lang(lang).or_else(|| in_lang(None)),
+            None => in_lang(None).or_else(|| labels.first().map(|(_, label)| label)),
+        }
+    }
+
+    fn insert(&mut self, element_id: &str, lang: Option<&str>, label: &str) {
+        let lang = lang.filter(|lang| !lang.is_empty() && *lang != "label").map(|lang| lang.to_string());
+        self.0.entry(element_id.to_string()).or_default().push((lang, label.to_string()));
+    }
+}
+
+/// Parses a rename map file given on the command line, from element path to label or to labels
+/// per language, by its extension:
+///
+/// - JSON, an object, e.g. `{"EHDSPatient.name": "Patient name"}` or
+///   `{"EHDSPatient.name": {"en": "Patient name", "sv": "Patientens namn"}}`
+/// - CSV, a header row with the languages after the path, e.g. `element,en,sv`, or `label`, and
+///   quoted fields for labels with commas, quotes or line breaks
+/// - YAML, a mapping like the JSON object, e.g. `EHDSPatient.name: Patient name`
+pub fn parse_rename_map(path: &str) -> Result<RenameMap, String> {
+    let mut renames = RenameMap::default();
+    match Path::new(path).extension().and_then(|extension| extension.to_str()) {
+        Some("csv") => {
+            let text = read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+            let mut rows = csv_rows(&text).into_iter();
+            let header = rows.next().ok_or("rename map has no header row")?;
+            for row in rows {
+                for (lang, label) in header.iter().zip(row.iter()).skip(1).filter(|(_, label)| !label.is_empty()) {
+                    renames.insert(&row[0], Some(lang), label);
+                }
+            }
+        }
+        Some("yaml" | "yml") => {
+            let text = read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+            parse_yaml_labels(&text, &mut renames)?;
+        }
+        _ => {
+            let doc = load_json_from_file(&PathBuf::from(path)).map_err(|e| e.to_string())?;
+            for (id, label) in doc.as_object().ok_or("rename map must be a JSON object")? {
+                match label {
+                    Value::String(label) => renames.insert(id, None, label),
+                    Value::Object(labels) => {
+                        for (lang, label) in labels {
+                            let label = label.as_str().ok_or(format!("label of '{}' in {} must be a string", id, lang))?;
+                            renames.insert(id, Some(lang), label);
+                        }
+                    }
+                    _ => return Err(format!("label of '{}' must be a string or an object of labels per language", id)),
+                }
+            }
+        }
+    }
+    Ok(renames)
+}
+
+/// The rows of CSV, of fields unquoted, without the empty rows. A quoted field may have commas,
+/// doubled quotes and line breaks in it.
+fn csv_rows(text: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::<Vec<String>>::new();
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    let mut end_row = |fields: &mut Vec<String>| {
+        let row = std::mem::replace(fields, vec![String::new()]);
+        if row.len() > 1 || !row[0].trim().is_empty() {
+            rows.push(row.iter().map(|field| field.trim().to_string()).collect());
+        }
+    };
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            '\n' if !quoted => end_row(&mut fields),
+            // the line breaks of CRLF files, kept in quoted fields as plain line breaks
+            '\r' if chars.peek() == Some(&'\n') => {}
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    end_row(&mut fields);
+    rows
+}
+
+/// The labels of a YAML mapping from element path to a label or to a mapping of labels per
+/// language, the subset of YAML a label file needs
+fn parse_yaml_labels(text: &str, renames: &mut RenameMap) -> Result<(), String> {
+    let mut element = None::<String>;
+    for (line_num, line) in text.lines().enumerate() {
+        let content = line.trim_end();
+        if content.trim().is_empty() || content.trim_start().starts_with('#') || content == "---" {
+            continue;
+        }
+        // element ids of slices have colons not followed by a space
+        let content = content.trim_start();
+        let (key, value) = content
+            .split_once(": ")
+            .or_else(|| content.strip_suffix(':').map(|key| (key, "")))
+            .ok_or(format!("line {} of the rename map is not a key: value", line_num + 1))?;
+        let (key, value) = (yaml_scalar(key), yaml_scalar(value));
+        if !line.starts_with([' ', '\t']) {
+            if value.is_empty() {
+                element = Some(key);
+            } else {
+                renames.insert(&key, None, &value);
+                element = None;
+            }
+        } else if let Some(element) = &element {
+            renames.insert(element, Some(&key), &value);
+        } else {
+            return Err(format!("line {} of the rename map is indented without an element", line_num + 1));
+        }
+    }
+    Ok(())
+}
+
+/// A plain or quoted YAML scalar, without a comment after it
+fn yaml_scalar(s: &str) -> String {
+    let s = s.trim();
+    if let Some(quoted) = s.strip_prefix('"').and_then(|s| s.rsplit_once('"')) {
+        quoted.0.replace("\\\"", "\"")
+    } else if let Some(quoted) = s.strip_prefix('\'').and_then(|s| s.rsplit_once('\'')) {
+        quoted.0.replace("''", "'")
+    } else {
+        s.split(" #").next().unwrap_or_default().trim().to_string()
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct NamingArgs {
+    /// How element names are turned into display labels
+    #[arg(long, value_enum, default_value_t = NameStyle::Spaced)]
+    pub name_style: NameStyle,
+
+    /// JSON, CSV or YAML file with display labels per element path, and per language given by
+    /// --lang, e.g. {"EHDSPatient.name": "Patient name"}
+    #[arg(long, visible_alias = "labels", value_parser = parse_rename_map)]
+    pub rename_map: Option<RenameMap>,
+
+    /// Acronyms kept in upper case in addition to the built-in ones, e.g. NHS,GP
+    #[arg(long, value_delimiter = ',')]
+    pub acronyms: Vec<String>,
+}
+
+const SMALL_WORDS: &[&str] = &["a", "an", "and", "as", "at", "by", "for", "in", "of", "on", "or", "the", "to"];
+
+const ACRONYMS: &[&str] = &["EHDS", "HL7", "ICU", "ID", "ODS", "UCUM", "URI", "URL"];
+
+/// Display name of an element according to the rename map, in the language if given, and name style
+pub fn display_name(element_id: &str, args: &NamingArgs, lang: Option<&str>) -> String {
+    if let Some(label) = args.rename_map.as_ref().and_then(|r| r.label(element_id, lang)) {
+        return label.clone();
+    }
+    let element_part = get_slice_after_last_occurrence(element_id, '.')
+        .unwrap_or(element_id.to_string())
+        .replace("[x]", "");
+    let acronym = |word: &str| {
+        ACRONYMS
+            .iter()
+            .copied()
+            .chain(args.acronyms.iter().map(|a| a.as_str()))
+            .find(|a| a.eq_ignore_ascii_case(word))
+            .map(|a| a.to_uppercase())
+    };
+    let words = || camel_to_spaced_pascal(&element_part).split(' ').map(|w| w.to_string()).collect::<Vec<_>>();
+    match args.name_style {
+        NameStyle::Spaced => words()
+            .iter()
+            .map(|word| acronym(word).unwrap_or(word.clone()))
+            .collect::<Vec<_>>()
+            .join(" "),
+        NameStyle::Title => words()
+            .iter()
+            .enumerate()
+            .map(|(i, word)| {
+                let lower = word.to_lowercase();
+                if let Some(acronym) = acronym(word) {
+                    acronym
+                } else if i > 0 && SMALL_WORDS.contains(&lower.as_str()) {
+                    lower
+                } else {
+                    word.clone()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" "),
+        NameStyle::Original => element_part,
+        NameStyle::Path => element_id.replace("[x]", ""),
+    }
+}
@@ -0,0 +1,89 @@
+use crate::ElementInfo;
+use crate::utils::generate_code;
+use clap::ValueEnum;
+use easy_tree::Tree;
+use std::collections::HashMap;
+
+#[derive(ValueEnum, Clone, Debug, PartialEq)]
+pub enum NumberingScheme {
+    /// Dotted decimal, e.g. A.1.2.3
+    Dotted,
+    /// Alternating numbers and letters, e.g. A.1.a.1
+    Alphanumeric,
+}
+
+struct NumberingState {
+    // codes of the elements from the root to the current element
+    path: Vec<String>,
+    codes: HashMap<usize, String>,
+    // highest code segment index taken below each parent code
+    used: HashMap<String, usize>,
+}
+
+fn segment(scheme: &NumberingScheme, level: usize, n: usize) -> String {
+    match scheme {
+        NumberingScheme::Alphanumeric if level.is_multiple_of(2) => generate_code(n).to_lowercase(),
+        _ => n.to_string(),
+    }
+}
+
+// Inverse of `segment`: "12" is 12, "a" is 1, "z" is 26 and "aa" is 27.
+fn segment_index(segment: &str) -> Option<usize> {
+    if let Ok(n) = segment.parse::<usize>() {
+        Some(n)
+    } else if !segment.is_empty() && segment.chars().all(|c| c.is_ascii_lowercase()) {
+        Some(segment.bytes().fold(0, |acc, b| acc * 26 + (b - b'a') as usize + 1))
+    } else {
+        None
+    }
+}
+
+/// Assigns a hierarchical code to every element of the tree, keyed by node index.
+///
+/// The numbering follows the actual parent/child links, so jumps in the element paths do not
+/// matter. Elements found in `stable_codes` (element id to code) keep their code, and new
+/// elements are numbered after the highest code among their siblings, so codes of removed
+/// elements are never reused.
+pub fn number_elements(
+    tree: &Tree<ElementInfo>,
+    prefix: &str,
+    scheme: &NumberingScheme,
+    stable_codes: &HashMap<String, String>,
+) -> HashMap<usize, String> {
+    let mut state = NumberingState {
+        path: Vec::new(),
+        codes: HashMap::new(),
+        used: HashMap::new(),
+    };
+    for code in stable_codes.values() {
+        if let Some((parent, last)) = code.rsplit_once('.')
+            && let Some(index) = segment_index(last)
+        {
+            let highest = state.used.entry(parent.to_string()).or_default();
+            *highest = index.max(*highest);
+        }
+    }
+
+    tree.traverse(
+        |idx, element, state| {
+            let code = if let Some(code) = stable_codes.get(element.id.as_ref()) {
+                code.clone()
+            } else if let Some(parent_code) = state.path.last() {
+                let level = state.path.len();
+                let highest = state.used.entry(parent_code.clone()).or_default();
+                *highest += 1;
+                format!("{}.{}", parent_code, segment(scheme, level, *highest))
+            } else {
+                prefix.to_string()
+            };
+            state.codes.insert(idx, code.clone());
+            state.path.push(code);
+        },
+        |_, _, state| {
+            state.path.pop();
+        },
+        &mut state,
+    );
+
+    state.codes
+}
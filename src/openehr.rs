@@ -0,0 +1,221 @@
+use crate::doc_index::DocIndex;
+use crate::output::create_output_file;
+use crate::summary::status;
+use crate::utils::{camel_to_spaced_pascal, get_slice_after_last_occurrence, slugify};
+use crate::{AdlArgs, ElementInfo, StructureDefTreeInfo, value_set_name};
+use std::fmt::Write as _;
+use std::io::{BufWriter, Write};
+
+/// Writes an openEHR ADL 2 archetype per logical model, by default <id>.adl: a CLUSTER per element
+/// with children and an ELEMENT per other element, occurrences from the cardinalities and a term
+/// binding per bound value set. Elements typed by another loaded logical model become slots for
+/// its archetype. Other structure definitions are skipped.
+pub fn generate_archetypes(args: &AdlArgs, docs: &[StructureDefTreeInfo]) -> Result<(), Box<dyn std::error::Error>> {
+    let index = DocIndex::new(docs);
+    for doc in docs.iter() {
+        if doc.kind != "logical" {
+            status!("skipping: {} is not a logical model", doc.id);
+            continue;
+        }
+        status!("processing: {}", doc.id);
+        let Some((root_idx, root)) = doc.element_tree.iter().next() else {
+            continue;
+        };
+        let mut archetype = Archetype {
+            index: &index,
+            doc,
+            definition: String::new(),
+            terms: Vec::new(),
+            bindings: Vec::new(),
+            next_id: 1,
+        };
+        let root_code = archetype.id_code();
+        archetype.terms.push((root_code.clone(), name_of(&root.id), root.short.to_string()));
+        writeln!(archetype.definition, "    CLUSTER[{}] matches {{    -- {}", root_code, comment(&name_of(&root.id)))?;
+        archetype.write_items(root_idx, 2)?;
+        writeln!(archetype.definition, "    }}")?;
+
+        let mut writer = BufWriter::new(create_output_file(args.output_pattern.replace("{id}", &doc.id))?);
+        archetype.write(&mut writer, root)?;
+    }
+    Ok(())
+}
+
+/// An archetype being built, `terms` holding the code, text and description of each node and
+/// value set, `bindings` the value set canonical of each value set code
+struct Archetype<'a> {
+    index: &'a DocIndex<'a>,
+    doc: &'a StructureDefTreeInfo,
+    definition: String,
+    terms: Vec<(String, String, String)>,
+    bindings: Vec<(String, String)>,
+    next_id: usize,
+}
+
+impl Archetype<'_> {
+    fn id_code(&mut self) -> String {
+        let code = format!("id{}", self.next_id);
+        self.next_id += 1;
+        code
+    }
+
+    /// Writes the items attribute with the children of an element, if any
+    fn write_items(&mut self, idx: usize, depth: usize) -> std::fmt::Result {
+        let children = self
+            .doc
+            .element_tree
+            .children(idx)
+            .iter()
+            .filter_map(|&child_idx| self.doc.element_tree.get(child_idx).map(|element| (child_idx, element)))
+            // slices have no counterpart in an archetype
+            .filter(|(_, element)| &*element.max != "0" && !element.id.contains(':'))
+            .collect::<Vec<_>>();
+        if children.is_empty() {
+            return Ok(());
+        }
+        let indent = "    ".repeat(depth);
+        writeln!(self.definition, "{}items matches {{", indent)?;
+        for (child_idx, element) in children {
+            self.write_node(child_idx, element, depth + 1)?;
+        }
+        writeln!(self.definition, "{}}}", indent)
+    }
+
+    fn write_node(&mut self, idx: usize, element: &ElementInfo, depth: usize) -> std::fmt::Result {
+        let indent = "    ".repeat(depth);
+        let name = name_of(&element.id);
+        let occurrences = format!("{}..{}", element.min, element.max);
+        let code = self.id_code();
+        self.terms.push((code.clone(), name.clone(), element.short.to_string()));
+
+        if !self.doc.element_tree.children(idx).is_empty() {
+            writeln!(self.definition, "{}CLUSTER[{}] occurrences matches {{{}}} matches {{    -- {}", indent, code, occurrences, comment(&name))?;
+            self.write_items(idx, depth + 1)?;
+            return writeln!(self.definition, "{}}}", indent);
+        }
+
+        // an element typed by another loaded logical model refers to its archetype
+        if let Some(model) = element
+            .datatype
+            .iter()
+            .find_map(|datatype| self.index.get(datatype).filter(|d| d.kind == "logical"))
+        {
+            writeln!(self.definition, "{}allow_archetype CLUSTER[{}] occurrences matches {{{}}} matches {{    -- {}", indent, code, occurrences, comment(&name))?;
+            writeln!(
+                self.definition,
+                "{}    include\n{}        archetype_id/value matches {{/openEHR-EHR-CLUSTER\\.{}\\.v0/}}",
+                indent,
+                indent,
+                concept_of(&model.id)
+            )?;
+            return writeln!(self.definition, "{}}}", indent);
+        }
+
+        writeln!(self.definition, "{}ELEMENT[{}] occurrences matches {{{}}} matches {{    -- {}", indent, code, occurrences, comment(&name))?;
+        let mut rm_types = Vec::<&str>::new();
+        for datatype in element.datatype.iter() {
+            let rm_type = rm_type_of(datatype);
+            if !rm_types.contains(&rm_type) {
+                rm_types.push(rm_type);
+            }
+        }
+        if !rm_types.is_empty() {
+            writeln!(self.definition, "{}    value matches {{", indent)?;
+            for rm_type in rm_types {
+                let value_code = self.id_code();
+                match value_set_name(element).zip(element.value_set.as_ref()) {
+                    Some((value_set_name, value_set)) if rm_type == "DV_CODED_TEXT" => {
+                        let ac_code = format!("ac{}", self.bindings.len() + 1);
+                        self.terms.push((ac_code.clone(), value_set_name, String::new()));
+                        self.bindings.push((ac_code.clone(), value_set.to_string()));
+                        writeln!(
+                            self.definition,
+                            "{}        DV_CODED_TEXT[{}] matches {{\n{}            defining_code matches {{[{}]}}\n{}        }}",
+                            indent, value_code, indent, ac_code, indent
+                        )?;
+                    }
+                    _ => writeln!(self.definition, "{}        {}[{}]", indent, rm_type, value_code)?,
+                }
+            }
+            writeln!(self.definition, "{}    }}", indent)?;
+        }
+        writeln!(self.definition, "{}}}", indent)
+    }
+
+    fn write<W: Write>(&self, writer: &mut W, root: &ElementInfo) -> std::io::Result<()> {
+        writeln!(writer, "archetype (adl_version=2.0.6; rm_release=1.1.0; generated)")?;
+        writeln!(writer, "    {}\n", archetype_id(&self.doc.id))?;
+        writeln!(writer, "language\n    original_language = <[ISO_639-1::en]>\n")?;
+        writeln!(writer, "description")?;
+        writeln!(writer, "    original_author = <\n        [\"name\"] = <\"fhir-generate\">\n    >")?;
+        writeln!(writer, "    lifecycle_state = <\"unmanaged\">")?;
+        writeln!(writer, "    other_details = <\n        [\"source\"] = <\"{}\">\n    >", adl_string(&self.doc.url))?;
+        writeln!(writer, "    details = <\n        [\"en\"] = <\n            language = <[ISO_639-1::en]>")?;
+        writeln!(writer, "            purpose = <\"{}\">\n        >\n    >\n", adl_string(&root.definition))?;
+        writeln!(writer, "definition\n{}", self.definition)?;
+
+        writeln!(writer, "terminology\n    term_definitions = <\n        [\"en\"] = <")?;
+        for (code, text, description) in self.terms.iter() {
+            writeln!(
+                writer,
+                "            [\"{}\"] = <\n                text = <\"{}\">\n                description = <\"{}\">\n            >",
+                code,
+                adl_string(text),
+                adl_string(if description.is_empty() { text } else { description })
+            )?;
+        }
+        writeln!(writer, "        >\n    >")?;
+        if !self.bindings.is_empty() {
+            writeln!(writer, "    term_bindings = <\n        [\"fhir\"] = <")?;
+            for (code, value_set) in self.bindings.iter() {
+                writeln!(writer, "            [\"{}\"] = <{}>", code, value_set)?;
+            }
+            writeln!(writer, "        >\n    >")?;
+        }
+        Ok(())
+    }
+}
+
+/// Archetype id of a logical model, e.g. openEHR-EHR-CLUSTER.ehds_patient.v0.0.1 for EHDSPatient
+fn archetype_id(id: &str) -> String {
+    format!("openEHR-EHR-CLUSTER.{}.v0.0.1", concept_of(id))
+}
+
+fn concept_of(id: &str) -> String {
+    slugify(&camel_to_spaced_pascal(id)).replace('-', "_")
+}
+
+/// Reference model type of the value of an ELEMENT with a FHIR datatype
+fn rm_type_of(datatype: &str) -> &'static str {
+    match datatype {
+        "boolean" => "DV_BOOLEAN",
+        "integer" | "integer64" | "positiveInt" | "unsignedInt" => "DV_COUNT",
+        "decimal" | "Quantity" | "Age" | "Distance" | "Count" | "SimpleQuantity" => "DV_QUANTITY",
+        "date" => "DV_DATE",
+        "dateTime" | "instant" => "DV_DATE_TIME",
+        "time" => "DV_TIME",
+        "Duration" => "DV_DURATION",
+        "code" | "Coding" | "CodeableConcept" => "DV_CODED_TEXT",
+        "uri" | "url" | "canonical" | "oid" | "uuid" => "DV_URI",
+        "Identifier" => "DV_IDENTIFIER",
+        "Period" => "DV_INTERVAL<DV_DATE_TIME>",
+        "Range" => "DV_INTERVAL<DV_QUANTITY>",
+        "Ratio" => "DV_PROPORTION",
+        "Attachment" | "base64Binary" => "DV_MULTIMEDIA",
+        "Reference" => "DV_EHR_URI",
+        _ => "DV_TEXT",
+    }
+}
+
+fn name_of(id: &str) -> String {
+    get_slice_after_last_occurrence(id, '.').unwrap_or(id.to_string()).replace("[x]", "")
+}
+
+/// Text of a comment, which ends at the end of the line
+fn comment(s: &str) -> String {
+    s.replace(['\n', '\r'], " ")
+}
+
+fn adl_string(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
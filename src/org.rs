@@ -0,0 +1,108 @@
+use crate::numbering::number_elements;
+use crate::output::create_output_file;
+use crate::summary::status;
+use crate::utils::{get_slice_after_last_occurrence, reduce_datatypes};
+use crate::{OrgArgs, StructureDefTreeInfo, TableArgs, TableContext, default_args, value_set_name};
+use std::collections::HashMap;
+use std::io::{BufWriter, Write};
+
+/// Writes an Emacs org-mode document per structure definition, by default <id>.org, with a heading
+/// per element with children followed by a table of its children without children
+pub fn generate_org(args: &OrgArgs, docs: &[StructureDefTreeInfo]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut table_args = default_args::<TableArgs>();
+    table_args.common.lang = args.common.lang.clone();
+    let ctx = TableContext::new(&table_args, docs)?;
+
+    for (doc_num, doc) in docs.iter().enumerate() {
+        status!("processing: {}", doc.id);
+        let Some((root_idx, _)) = doc.element_tree.iter().next() else {
+            continue;
+        };
+        let codes = number_elements(&doc.element_tree, &ctx.prefix_of(doc_num, doc), &table_args.numbering, &ctx.stable_codes);
+        let mut writer = BufWriter::new(create_output_file(args.output_pattern.replace("{id}", &doc.id))?);
+        writeln!(writer, "#+TITLE: {}\n", doc.id)?;
+        write_section(&mut writer, doc, root_idx, 1, &codes, &ctx)?;
+    }
+    Ok(())
+}
+
+/// Writes the heading of an element, its description, the table of its children without children
+/// and the sections of the others
+fn write_section<W: Write>(
+    writer: &mut W,
+    doc: &StructureDefTreeInfo,
+    idx: usize,
+    level: usize,
+    codes: &HashMap<usize, String>,
+    ctx: &TableContext,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(element) = doc.element_tree.get(idx) else {
+        return Ok(());
+    };
+    let labels = &ctx.labels;
+    let name = get_slice_after_last_occurrence(&element.id, '.').unwrap_or(element.id.to_string());
+    writeln!(writer, "{} {}", "*".repeat(level), name)?;
+    writeln!(writer, ":PROPERTIES:\n:CUSTOM_ID: {}", element.id)?;
+    if element.id.contains('.') {
+        writeln!(writer, ":CODE: {}", codes.get(&idx).map(|c| c.as_str()).unwrap_or_default())?;
+        writeln!(writer, ":CARDINALITY: {}", labels.cardinality(&element.min, &element.max))?;
+        writeln!(writer, ":DATATYPE: {}", org_text(&reduce_datatypes(&element.datatype)))?;
+    }
+    writeln!(writer, ":END:")?;
+    if !element.short.is_empty() {
+        writeln!(writer, "{}", org_text(&element.short))?;
+    }
+
+    let children = doc
+        .element_tree
+        .children(idx)
+        .iter()
+        .copied()
+        .filter(|&child| doc.element_tree.get(child).is_some_and(|e| &*e.max != "0"))
+        .collect::<Vec<_>>();
+    let (sections, leaves): (Vec<usize>, Vec<usize>) =
+        children.iter().partition(|&&child| !doc.element_tree.children(child).is_empty());
+
+    if !leaves.is_empty() {
+        writeln!(
+            writer,
+            "\n| {} |\n|{}|",
+            [
+                labels.get("code"),
+                labels.get("element_name"),
+                labels.get("element_description"),
+                labels.get("data_type"),
+                labels.get("cardinality"),
+                labels.get("binding_requirements"),
+            ]
+            .join(" | "),
+            ["---"; 6].join("+")
+        )?;
+        for child_idx in leaves {
+            let Some(child) = doc.element_tree.get(child_idx) else {
+                continue;
+            };
+            writeln!(
+                writer,
+                "| {} | {} | {} | {} | {} | {} |",
+                codes.get(&child_idx).map(|c| c.as_str()).unwrap_or_default(),
+                org_text(&get_slice_after_last_occurrence(&child.id, '.').unwrap_or(child.id.to_string())),
+                org_text(&child.short),
+                org_text(&reduce_datatypes(&child.datatype)),
+                labels.cardinality(&child.min, &child.max),
+                org_text(&value_set_name(child).unwrap_or_default())
+            )?;
+        }
+    }
+    writeln!(writer)?;
+
+    for section_idx in sections {
+        write_section(writer, doc, section_idx, level + 1, codes, ctx)?;
+    }
+    Ok(())
+}
+
+/// Text on a single line that does not end an org table cell
+fn org_text(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ").replace('|', "\\vert{}")
+}
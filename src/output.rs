@@ -0,0 +1,208 @@
+//! Where the generated documents go. Generators create each document with `create_output_file`
+//! under the name it has when written as a file, and the target of the run decides where it is
+//! written: as that file (the default), below `--output-dir`, one after the other into
+//! `--single-file`, or to standard output with `--stdout`. `--archive` is written as files that
+//! are then collected, see `archive`, and `serve` keeps the documents in memory.
+
+use crate::diagnostics::{self, Diagnostic};
+use crate::summary;
+use std::{
+    collections::BTreeMap,
+    fs::{File, create_dir_all},
+    io::{self, Sink, Stdout, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+/// Where the documents of a run are written
+#[derive(Clone, Debug, PartialEq)]
+pub enum OutputTarget {
+    /// each document in its own file, relative to the working directory
+    Files,
+    /// each document in its own file, relative to a folder
+    Directory(PathBuf),
+    /// all documents one after the other into one file
+    SingleFile(PathBuf),
+    Stdout,
+    /// each document kept in memory by the name of its file, see `take_documents`
+    Memory,
+}
+
+static TARGET: Mutex<OutputTarget> = Mutex::new(OutputTarget::Files);
+
+/// The file of `OutputTarget::SingleFile`, created with the first document
+static SINGLE_FILE: Mutex<Option<OutputFile>> = Mutex::new(None);
+
+/// The documents of `OutputTarget::Memory` by the name of their file
+static DOCUMENTS: Mutex<BTreeMap<PathBuf, Vec<u8>>> = Mutex::new(BTreeMap::new());
+
+pub fn set_target(target: OutputTarget) {
+    *TARGET.lock().unwrap_or_else(PoisonError::into_inner) = target;
+}
+
+pub fn target() -> OutputTarget {
+    TARGET.lock().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
+/// Whether each document goes to a file of its own, not one after the other to standard output or
+/// the single file
+pub fn has_own_files() -> bool {
+    !matches!(target(), OutputTarget::Stdout | OutputTarget::SingleFile(_))
+}
+
+/// Whether the documents of a run go to standard output, whatever their names
+pub fn is_stdout() -> bool {
+    target() == OutputTarget::Stdout
+}
+
+/// Whether an output folder is given, or the documents are kept in memory, so that a generator
+/// writes its files directly into it
+pub fn has_output_dir() -> bool {
+    matches!(target(), OutputTarget::Directory(_) | OutputTarget::Memory)
+}
+
+fn documents() -> MutexGuard<'static, BTreeMap<PathBuf, Vec<u8>>> {
+    DOCUMENTS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The documents kept in memory since the last call
+pub fn take_documents() -> BTreeMap<PathBuf, Vec<u8>> {
+    std::mem::take(&mut *documents())
+}
+
+/// The path a document is written to as a file, below the output folder if one is given
+pub fn resolve(path: &Path) -> PathBuf {
+    match target() {
+        OutputTarget::Directory(dir) if path.is_relative() => dir.join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Whether existing output files are overwritten instead of skipped
+static FORCE: AtomicBool = AtomicBool::new(false);
+
+/// Input files, which are never overwritten
+static INPUTS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Files written in the run, in the order they were created
+static OUTPUTS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Records a file written in the run, also one written by another program
+pub fn output_written(path: &Path) {
+    let mut outputs = OUTPUTS.lock().unwrap_or_else(PoisonError::into_inner);
+    if !outputs.iter().any(|output| output == path) {
+        outputs.push(path.to_path_buf());
+    }
+}
+
+pub fn outputs() -> Vec<PathBuf> {
+    OUTPUTS.lock().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
+pub fn set_force(force: bool) {
+    FORCE.store(force, Ordering::Relaxed);
+}
+
+pub fn is_force() -> bool {
+    FORCE.load(Ordering::Relaxed)
+}
+
+pub fn protect_inputs(files: &[PathBuf]) {
+    let mut inputs = INPUTS.lock().unwrap_or_else(PoisonError::into_inner);
+    inputs.extend(files.iter().filter_map(|file| file.canonicalize().ok()));
+}
+
+/// A generated document, either a file, the single file of the run, standard output or a document
+/// in memory, or discarded when the file exists
+pub enum OutputFile {
+    File(File),
+    Single,
+    Stdout(Stdout),
+    Memory(PathBuf),
+    Skipped(Sink),
+}
+
+fn single_file() -> MutexGuard<'static, Option<OutputFile>> {
+    SINGLE_FILE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputFile::File(file) => file.write(buf),
+            OutputFile::Single => single_file().as_mut().map_or(Ok(buf.len()), |file| file.write(buf)),
+            OutputFile::Stdout(stdout) => stdout.write(buf),
+            OutputFile::Memory(path) => {
+                documents().entry(path.clone()).or_default().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            OutputFile::Skipped(sink) => sink.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputFile::File(file) => file.flush(),
+            OutputFile::Single => single_file().as_mut().map_or(Ok(()), |file| file.flush()),
+            OutputFile::Stdout(stdout) => stdout.flush(),
+            OutputFile::Memory(_) => Ok(()),
+            OutputFile::Skipped(sink) => sink.flush(),
+        }
+    }
+}
+
+/// Creates a document with the name of its file, or writes it to standard output if the name is
+/// "-", counting it among the documents generated in its format. Where it goes depends on the
+/// target of the run. An existing file is skipped with a warning unless `--force` is given, and an
+/// input file is never overwritten.
+pub fn create_output_file(path: impl AsRef<Path>) -> Result<OutputFile, Box<dyn std::error::Error>> {
+    let path = path.as_ref();
+    if path == Path::new("-") || is_stdout() {
+        summary::document_written("stdout");
+        return Ok(OutputFile::Stdout(io::stdout()));
+    }
+    let format = path.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+    if let OutputTarget::SingleFile(single) = target() {
+        let mut file = single_file();
+        if file.is_none() {
+            *file = Some(create_file(&single)?);
+        }
+        summary::document_written(&format);
+        return Ok(OutputFile::Single);
+    }
+    if target() == OutputTarget::Memory {
+        documents().insert(path.to_path_buf(), Vec::new());
+        summary::document_written(&format);
+        return Ok(OutputFile::Memory(path.to_path_buf()));
+    }
+    let output = create_file(&resolve(path))?;
+    if !matches!(output, OutputFile::Skipped(_)) {
+        summary::document_written(&format);
+    }
+    Ok(output)
+}
+
+/// Creates a file and the folders it is in, unless it exists without `--force` or is an input
+fn create_file(path: &Path) -> Result<OutputFile, Box<dyn std::error::Error>> {
+    if let Ok(existing) = path.canonicalize() {
+        if INPUTS.lock().unwrap_or_else(PoisonError::into_inner).contains(&existing) {
+            return Err(format!("Refusing to overwrite input file '{}'", path.display()).into());
+        }
+        if !FORCE.load(Ordering::Relaxed) {
+            diagnostics::emit(
+                Diagnostic::warning("existing-output", "Output file exists and is not overwritten, use --force to overwrite it")
+                    .in_file(path),
+            );
+            return Ok(OutputFile::Skipped(io::sink()));
+        }
+    }
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        create_dir_all(parent)?;
+    }
+    let file = File::create(path)?;
+    output_written(path);
+    Ok(OutputFile::File(file))
+}
@@ -0,0 +1,30 @@
+use crate::output::create_output_file;
+use crate::summary::status;
+use crate::utils::{escape_csv, reduce_datatypes};
+use crate::{PathsArgs, StructureDefTreeInfo};
+use std::io::{BufWriter, Write};
+
+/// Writes a CSV file with a row per element of the structure definitions, with its path, datatype
+/// and cardinality and an empty column to fill in with the source of a mapping
+pub fn generate_paths(args: &PathsArgs, docs: &[StructureDefTreeInfo]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = BufWriter::new(create_output_file(&args.output_file)?);
+    writeln!(writer, "structure definition,path,datatype,cardinality,maps to")?;
+    for doc in docs.iter() {
+        status!("processing: {}", doc.id);
+        for (_idx, element) in doc.element_tree.iter() {
+            // elements that are not allowed have nothing to map to
+            if &*element.max == "0" {
+                continue;
+            }
+            writeln!(
+                writer,
+                "{},{},{},{},",
+                escape_csv(&doc.id),
+                escape_csv(&element.id),
+                escape_csv(&reduce_datatypes(&element.datatype)),
+                escape_csv(&format!("{}..{}", element.min, element.max))
+            )?;
+        }
+    }
+    Ok(())
+}
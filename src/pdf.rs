@@ -0,0 +1,194 @@
+use crate::numbering::number_elements;
+use crate::output::{self, OutputFile, create_output_file};
+use crate::summary::status;
+use crate::utils::{file_name, get_slice_after_last_occurrence, reduce_datatypes};
+use crate::{PdfArgs, StructureDefTreeInfo, TableArgs, TableContext, default_args, value_set_name};
+use std::fs::remove_file;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Writes a Typst document per structure definition, or one for all with `--combined`, and
+/// compiles it to PDF with the typst executable. Tables repeat their header row on each page, and
+/// the rendered class diagrams of `--diagrams-dir`, e.g. from `plant-uml --split`, are embedded.
+pub fn generate_pdf(args: &PdfArgs, docs: &[StructureDefTreeInfo]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut table_args = default_args::<TableArgs>();
+    table_args.common.lang = args.common.lang.clone();
+    let ctx = TableContext::new(&table_args, docs)?;
+
+    if let Some(title) = &args.combined {
+        let source = PathBuf::from(format!("{}.typ", file_name(title)));
+        let output = create_output_file(&source)?;
+        let root = typst_root(&source, args);
+        let written = matches!(output, OutputFile::File(_));
+        let mut writer = BufWriter::new(output);
+        write_preamble(&mut writer, title)?;
+        writeln!(writer, "#align(center, text(size: 20pt, {}))\n#outline()\n#pagebreak()", typst_string(title))?;
+        for (doc_num, doc) in docs.iter().enumerate() {
+            status!("processing: {}", doc.id);
+            if doc_num > 0 {
+                writeln!(writer, "#pagebreak()")?;
+            }
+            write_profile(&mut writer, doc, doc_num, &ctx, args, &root)?;
+        }
+        drop(writer);
+        if written {
+            compile(&source, &root, args)?;
+        }
+    } else {
+        for (doc_num, doc) in docs.iter().enumerate() {
+            status!("processing: {}", doc.id);
+            let source = PathBuf::from(format!("{}.typ", doc.id));
+            let output = create_output_file(&source)?;
+            let root = typst_root(&source, args);
+            let written = matches!(output, OutputFile::File(_));
+            let mut writer = BufWriter::new(output);
+            write_preamble(&mut writer, &doc.id)?;
+            write_profile(&mut writer, doc, doc_num, &ctx, args, &root)?;
+            drop(writer);
+            if written {
+                compile(&source, &root, args)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The folder typst may read files in, which it reads no file outside of: the common ancestor of
+/// the folders of the document and of the diagrams
+fn typst_root(source: &Path, args: &PdfArgs) -> PathBuf {
+    let absolute = |path: &Path| path.canonicalize().or_else(|_| std::path::absolute(path)).unwrap_or_else(|_| path.to_path_buf());
+    let source = absolute(&output::resolve(source));
+    let mut root = source.parent().map(Path::to_path_buf).unwrap_or_default();
+    if let Some(diagrams_dir) = &args.diagrams_dir {
+        let diagrams_dir = absolute(diagrams_dir);
+        while !diagrams_dir.starts_with(&root) && root.pop() {}
+    }
+    root
+}
+
+/// Landscape pages numbered in the footer, so that wide tables fit
+fn write_preamble<W: Write>(writer: &mut W, title: &str) -> std::io::Result<()> {
+    writeln!(
+        writer,
+        "#set document(title: {})\n#set page(paper: \"a4\", flipped: true, margin: 1.5cm, numbering: \"1\")\n#set text(size: 8pt)\n#set par(justify: false)\n",
+        typst_string(title)
+    )
+}
+
+/// Writes the heading, the class diagram if rendered and the element table of a structure definition
+fn write_profile<W: Write>(
+    writer: &mut W,
+    doc: &StructureDefTreeInfo,
+    doc_num: usize,
+    ctx: &TableContext,
+    args: &PdfArgs,
+    root: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let labels = &ctx.labels;
+    writeln!(writer, "#heading(level: 1, {})\n", typst_string(&doc.id))?;
+    if let Some(diagram) = diagram_file(args, doc) {
+        // typst resolves absolute paths from its root, with '/' between the folders on any system
+        let diagram = diagram.canonicalize().unwrap_or(diagram);
+        let below_root = diagram.strip_prefix(root).unwrap_or(&diagram);
+        let path = below_root.components().map(|component| component.as_os_str().to_string_lossy()).collect::<Vec<_>>();
+        writeln!(
+            writer,
+            "#figure(image({}, width: 100%, height: 80%, fit: \"contain\"))\n#pagebreak()",
+            typst_string(&format!("/{}", path.join("/")))
+        )?;
+    }
+
+    let codes = number_elements(&doc.element_tree, &ctx.prefix_of(doc_num, doc), &ctx.args.numbering, &ctx.stable_codes);
+    let columns = [
+        labels.get("code"),
+        labels.get("element_name"),
+        labels.get("element_description"),
+        labels.get("data_type"),
+        labels.get("cardinality"),
+        labels.get("binding_requirements"),
+    ];
+    writeln!(writer, "#table(\n  columns: (auto, auto, 3fr, 1fr, auto, 1fr),\n  fill: (_, row) => if row == 0 {{ luma(230) }},")?;
+    let header = columns.iter().map(|c| format!("strong({})", typst_string(c))).collect::<Vec<_>>();
+    writeln!(writer, "  table.header(repeat: true, {}),", header.join(", "))?;
+    for (idx, element) in doc.element_tree.iter() {
+        if &*element.max == "0" {
+            continue;
+        }
+        let level = element.depth;
+        let name = if level > 0 {
+            get_slice_after_last_occurrence(&element.id, '.').unwrap_or(element.id.to_string())
+        } else {
+            element.id.to_string()
+        };
+        let description = if element.short == element.definition {
+            element.short.to_string()
+        } else {
+            format!("{}\n{}", element.short, element.definition)
+        };
+        let (datatype, cardinality) = if level > 0 {
+            (reduce_datatypes(&element.datatype), labels.cardinality(&element.min, &element.max))
+        } else {
+            (format!("{} {}", labels.get("derived_from"), doc.base), String::new())
+        };
+        writeln!(
+            writer,
+            "  {}, [#h({}em)#{}], {}, {}, {}, {},",
+            typst_string(codes.get(&idx).map(|c| c.as_str()).unwrap_or_default()),
+            level,
+            typst_string(&name),
+            typst_string(&description),
+            typst_string(&datatype),
+            typst_string(&cardinality),
+            typst_string(&value_set_name(element).unwrap_or_default())
+        )?;
+    }
+    writeln!(writer, ")")?;
+    Ok(())
+}
+
+/// The rendered class diagram of a structure definition, <id>.svg or <id>.png in the diagrams folder
+fn diagram_file(args: &PdfArgs, doc: &StructureDefTreeInfo) -> Option<PathBuf> {
+    let dir = args.diagrams_dir.as_ref()?;
+    ["svg", "png"]
+        .iter()
+        .map(|extension| dir.join(format!("{}.{}", doc.id, extension)))
+        .find(|file| file.is_file())
+}
+
+/// Compiles a Typst document to a PDF file next to it, unless only the source is wanted. The PDF
+/// is created first as any output, so that an existing one is kept without `--force`.
+fn compile(source: &Path, root: &Path, args: &PdfArgs) -> Result<(), Box<dyn std::error::Error>> {
+    if args.source_only {
+        return Ok(());
+    }
+    let pdf = source.with_extension("pdf");
+    if !matches!(create_output_file(&pdf)?, OutputFile::File(_)) {
+        return Ok(());
+    }
+    let (source, pdf) = (output::resolve(source), output::resolve(&pdf));
+    status!("compiling: {}", pdf.display());
+    let result = Command::new(&args.typst)
+        .arg("compile")
+        .arg("--root")
+        .arg(root)
+        .arg(&source)
+        .arg(&pdf)
+        .status();
+    let error = match result {
+        Ok(status) if status.success() => return Ok(()),
+        Ok(status) => format!("Error compiling '{}' to PDF: typst exited with {}", source.display(), status),
+        Err(e) => format!("Error running '{}', install typst or use --source-only: {}", args.typst.display(), e),
+    };
+    // the PDF created for typst is not left empty
+    let _ = remove_file(&pdf);
+    Err(error.into())
+}
+
+/// A Typst string literal, shown as is without markup
+fn typst_string(s: &str) -> String {
+    format!(
+        "\"{}\"",
+        s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\r', "")
+    )
+}
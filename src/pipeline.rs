@@ -0,0 +1,138 @@
+use crate::cycles;
+use crate::summary::status;
+use crate::{AllArgs, Cli, generate, load_structure_definition_files};
+use clap::Parser;
+use std::fs::read_to_string;
+use toml_edit::{DocumentMut, Item, Value};
+
+/// The keys of a config file, for the help of `all`
+pub const CONFIG_KEYS: &str = "Config file keys:
+  with = [...]             generators run when --with is not given, e.g. [\"plant-uml\", \"table\"]
+  [<generator>]            options of a generator by their long names: true for a flag, a value, or an
+                           array of values for an option given more than once
+
+Example:
+  with = [\"plant-uml\", \"table\"]
+
+  [plant-uml]
+  show-base = true
+  group-by = \"base\"
+
+  [table]
+  flags = true
+  prefix-code = \"PAT\"";
+
+/// Runs several generators on the same structure definitions, loaded and parsed once, e.g.
+///
+/// ```toml
+/// # generators run when --with is not given
+/// with = ["plant-uml", "table"]
+///
+/// [plant-uml]
+/// show-base = true
+/// group-by = "base"
+///
+/// [table]
+/// flags = true
+/// prefix-code = "PAT"
+/// ```
+///
+/// Each table of the config file gives the options of a generator by their long names: `true` for
+/// a flag, a value, or an array of values for an option given more than once. The input files and
+/// the options on them, e.g. --lang or --must-support-only, are those given to `all`, the same for
+/// all generators, and are not accepted in the config file.
+pub fn run_all(args: &AllArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let config = match &args.config {
+        Some(file) => read_to_string(file)
+            .map_err(|e| format!("Error reading config file '{}': {}", file.display(), e))?
+            .parse::<DocumentMut>()
+            .map_err(|e| format!("Error reading config file '{}': {}", file.display(), e))?,
+        None => DocumentMut::new(),
+    };
+    let generators = if args.with.is_empty() { strings(config.get("with"))? } else { args.with.clone() };
+    if generators.is_empty() {
+        return Err("No generators given, list them with --with or in the config file".into());
+    }
+
+    // all generators are parsed first, so that an error in their options stops the run before
+    // anything is generated
+    let mut commands = Vec::new();
+    for generator in generators.iter() {
+        let mut arguments = vec!["fhir-generate".to_string(), generator.clone()];
+        arguments.extend(options(config.get(generator), generator)?);
+        let mut command = Cli::try_parse_from(&arguments)
+            .map_err(|e| format!("Error in the options of '{}': {}", generator, e))?
+            .command
+            .ok_or_else(|| format!("No generator '{}'", generator))?;
+        let common = command
+            .common_mut()
+            .ok_or_else(|| format!("'{}' does not generate from the structure definitions and cannot be run by all", generator))?;
+        if let Some(option) = common.given_option() {
+            return Err(format!("Give {} to all itself, not in the options of '{}' in the config file", option, generator).into());
+        }
+        *common = args.common.clone();
+        commands.push((generator, command));
+    }
+
+    let docs = load_structure_definition_files(&args.common)?;
+    if commands.iter().any(|(_, command)| command.follows_types()) {
+        cycles::report_cycles(&docs);
+    }
+    for (generator, command) in commands {
+        status!("running: {}", generator);
+        generate(command, &docs)?;
+    }
+    Ok(())
+}
+
+/// The strings of an array in the config file, none if it is not given
+fn strings(item: Option<&Item>) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let Some(item) = item else {
+        return Ok(Vec::new());
+    };
+    let error = "'with' in the config file must be an array of generator names";
+    item.as_array()
+        .ok_or(error)?
+        .iter()
+        .map(|value| value.as_str().map(str::to_string).ok_or_else(|| error.into()))
+        .collect()
+}
+
+/// The command line options of a table of the config file, e.g. `--flags` for `flags = true`
+fn options(item: Option<&Item>, generator: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let Some(item) = item else {
+        return Ok(Vec::new());
+    };
+    let table = item
+        .as_table_like()
+        .ok_or_else(|| format!("'{}' in the config file must be a table of options", generator))?;
+    let mut arguments = Vec::<String>::new();
+    for (name, value) in table.iter() {
+        let option = format!("--{}", name);
+        let error = || format!("Option '{}' of '{}' in the config file must be a flag, a value or an array of values", name, generator);
+        let value = value.as_value().ok_or_else(error)?;
+        match value {
+            Value::Boolean(flag) => {
+                if *flag.value() {
+                    arguments.push(option);
+                }
+            }
+            Value::Array(values) => {
+                for value in values.iter() {
+                    arguments.extend([option.clone(), option_value(value).ok_or_else(error)?]);
+                }
+            }
+            value => arguments.extend([option, option_value(value).ok_or_else(error)?]),
+        }
+    }
+    Ok(arguments)
+}
+
+fn option_value(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.value().clone()),
+        Value::Integer(i) => Some(i.value().to_string()),
+        Value::Float(f) => Some(f.value().to_string()),
+        _ => None,
+    }
+}
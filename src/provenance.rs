@@ -0,0 +1,180 @@
+use crate::doc_index::DocIndex;
+use crate::{ElementInfo, StructureDefTreeInfo};
+use std::collections::{HashMap, HashSet};
+
+/// The structure definition followed by the loaded structure definitions it is derived from,
+/// e.g. national profile, EU profile, core resource
+pub fn derivation_chain<'a>(doc: &'a StructureDefTreeInfo, index: &DocIndex<'a>) -> Vec<&'a StructureDefTreeInfo> {
+    let mut chain = vec![doc];
+    let mut seen = HashSet::from([doc.id.as_str()]);
+    while let Some(base) = index.base_of(chain[chain.len() - 1]) {
+        if !seen.insert(base.id.as_str()) {
+            break;
+        }
+        chain.push(base);
+    }
+    chain
+}
+
+/// Element path below the root, which is the same in a profile or specialization and its base
+fn relative_path(element: &ElementInfo) -> &str {
+    element.id.split_once('.').map(|(_, path)| path).unwrap_or_default()
+}
+
+/// Whether two layers define an element with the same cardinality, types, binding and must-support
+fn same_constraints(a: &ElementInfo, b: &ElementInfo) -> bool {
+    a.min == b.min
+        && a.max == b.max
+        && a.datatype == b.datatype
+        && a.value_set == b.value_set
+        && a.binding_strength == b.binding_strength
+        && a.must_support == b.must_support
+}
+
+/// The structure definition of the derivation chain that introduced or last constrained each
+/// element, keyed by element id
+pub fn element_sources(doc: &StructureDefTreeInfo, index: &DocIndex) -> HashMap<String, String> {
+    let chain = derivation_chain(doc, index);
+    let layers = chain
+        .iter()
+        .map(|layer| {
+            layer
+                .element_tree
+                .iter()
+                .map(|(_idx, element)| (relative_path(element), element))
+                .collect::<HashMap<_, _>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut sources = HashMap::<String, String>::new();
+    for (_idx, element) in doc.element_tree.iter() {
+        let path = relative_path(element);
+        let mut source = &doc.id;
+        for (layer_num, layer) in chain.iter().enumerate() {
+            source = &layer.id;
+            let current = layers[layer_num].get(path);
+            let inherited = layers.get(layer_num + 1).and_then(|l| l.get(path));
+            match (current, inherited) {
+                (Some(current), Some(inherited)) if same_constraints(current, inherited) => continue,
+                _ => break,
+            }
+        }
+        sources.insert(element.id.to_string(), source.clone());
+    }
+    sources
+}
+
+/// Ids of the elements a structure definition adds or constrains compared to its loaded base,
+/// none when the base is not loaded
+pub fn changed_elements(doc: &StructureDefTreeInfo, index: &DocIndex) -> Option<HashSet<String>> {
+    if derivation_chain(doc, index).len() < 2 {
+        return None;
+    }
+    let sources = element_sources(doc, index);
+    Some(sources.into_iter().filter(|(_, source)| *source == doc.id).map(|(id, _)| id).collect())
+}
+
+/// Upper bound of a cardinality, none for "*"
+fn max_bound(max: &str) -> Option<u64> {
+    max.parse().ok()
+}
+
+/// Ranking of binding strengths, from the weakest
+fn strength_rank(strength: Option<&str>) -> usize {
+    match strength {
+        Some("example") => 1,
+        Some("preferred") => 2,
+        Some("extensible") => 3,
+        Some("required") => 4,
+        _ => 0,
+    }
+}
+
+/// Narrows the constraints of an element by those of the same element in a base: the tightest
+/// cardinality, the types allowed by both and the strongest binding
+fn constrain(element: &mut ElementInfo, base: &ElementInfo) {
+    if base.min.parse::<u64>().unwrap_or(0) > element.min.parse::<u64>().unwrap_or(0) {
+        element.min = base.min.clone();
+    }
+    if let Some(base_max) = max_bound(&base.max)
+        && max_bound(&element.max).is_none_or(|max| base_max < max)
+    {
+        element.max = base.max.clone();
+    }
+    if element.datatype.is_empty() {
+        element.datatype = base.datatype.clone();
+        element.reference = base.reference.clone();
+    } else if !base.datatype.is_empty() {
+        // profiled types, which the base does not name, are kept
+        let common = element
+            .datatype
+            .iter()
+            .filter(|datatype| base.datatype.contains(datatype))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !common.is_empty() {
+            element.reference.retain(|reference| common.contains(reference));
+            element.datatype = common;
+        }
+    }
+    if strength_rank(base.binding_strength.as_deref()) > strength_rank(element.binding_strength.as_deref()) {
+        element.binding = base.binding.clone();
+        element.binding_strength = base.binding_strength.clone();
+        element.value_set = base.value_set.clone();
+        element.value_set_title = base.value_set_title.clone();
+    }
+    element.must_support |= base.must_support;
+    element.is_modifier |= base.is_modifier;
+}
+
+/// Consolidates each derivation chain into the profile at its end: its elements get the effective
+/// constraints of all layers, and the structure definitions that other ones are derived from are
+/// left out
+pub fn flatten(docs: Vec<StructureDefTreeInfo>) -> Vec<StructureDefTreeInfo> {
+    let index = DocIndex::new(&docs);
+    let flattened = docs
+        .iter()
+        .map(|doc| {
+            let layers = derivation_chain(doc, &index)
+                .iter()
+                .skip(1)
+                .map(|layer| {
+                    layer
+                        .element_tree
+                        .iter()
+                        .map(|(_idx, element)| (relative_path(element), element))
+                        .collect::<HashMap<_, _>>()
+                })
+                .collect::<Vec<_>>();
+            doc.element_tree
+                .iter()
+                .map(|(idx, element)| {
+                    let mut flat = element.clone();
+                    for base in layers.iter().filter_map(|layer| layer.get(relative_path(element))) {
+                        constrain(&mut flat, base);
+                    }
+                    (idx, flat)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let bases = docs
+        .iter()
+        .filter(|doc| docs.iter().any(|d| d.base == doc.id && d.id != doc.id))
+        .map(|doc| doc.id.clone())
+        .collect::<HashSet<_>>();
+
+    let mut flat_docs = Vec::<StructureDefTreeInfo>::new();
+    for (mut doc, elements) in docs.into_iter().zip(flattened) {
+        if bases.contains(&doc.id) {
+            continue;
+        }
+        for (idx, element) in elements {
+            if let Some(e) = doc.element_tree.get_mut(idx) {
+                *e = element;
+            }
+        }
+        flat_docs.push(doc);
+    }
+    flat_docs
+}
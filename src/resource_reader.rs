@@ -0,0 +1,134 @@
+use serde_core::de::{DeserializeSeed, Deserializer, Error, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde_json::{Map, Value};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// The members of a JSON object that are read, the others are skipped while parsing
+enum Keep {
+    All,
+    /// all members but those listed, which are kept by their own rule; the rule applies to each
+    /// item of an array
+    Except(&'static [(&'static str, Keep)]),
+    None,
+    Same(&'static Keep),
+}
+
+/// An element definition without its comments and mappings, which no generator reads
+const ELEMENT: Keep = Keep::Except(&[("comment", Keep::None), ("mapping", Keep::None)]);
+
+/// A resource, e.g. a structure definition, a value set or a bundle of them, without its
+/// narrative, differential and mappings. Those are most of a structure definition, and only they
+/// are left out, so that what the generators read of any resource type is kept.
+static RESOURCE: Keep = Keep::Except(&[
+    ("text", Keep::None),
+    ("differential", Keep::None),
+    ("mapping", Keep::None),
+    ("snapshot", Keep::Except(&[("element", ELEMENT)])),
+    ("entry", Keep::Except(&[("resource", Keep::Same(&RESOURCE))])),
+]);
+
+/// Reads a FHIR resource from a JSON file keeping only what the generators use, so that large
+/// snapshots and bundles, e.g. of a whole package, are not held in memory in full
+pub fn read_resource(path: &Path) -> Result<Value, Box<dyn std::error::Error>> {
+    // streamed, so that the file is not held in memory next to the pruned value
+    let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(File::open(path)?));
+    let value = Pruned(&RESOURCE).deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+/// Reads a FHIR resource from JSON in memory, e.g. a file at a git revision, as `read_resource`
+pub fn read_resource_from_slice(bytes: &[u8]) -> Result<Value, Box<dyn std::error::Error>> {
+    let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+    let value = Pruned(&RESOURCE).deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+impl Keep {
+    fn member(&self, name: &str) -> Option<&Keep> {
+        match self {
+            Keep::All => Some(self),
+            Keep::Except(members) => match members.iter().find(|(member, _)| *member == name) {
+                Some((_, Keep::None)) => None,
+                Some((_, keep)) => Some(keep),
+                None => Some(&Keep::All),
+            },
+            Keep::None => None,
+            Keep::Same(keep) => keep.member(name),
+        }
+    }
+}
+
+struct Pruned<'a>(&'a Keep);
+
+impl<'de> DeserializeSeed<'de> for Pruned<'_> {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        match self.0 {
+            Keep::All => serde_core::Deserialize::deserialize(deserializer),
+            _ => deserializer.deserialize_any(self),
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for Pruned<'_> {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a JSON value")
+    }
+
+    fn visit_bool<E: Error>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E: Error>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_f64<E: Error>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn visit_string<E: Error>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_unit<E: Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element_seed(Pruned(self.0))? {
+            items.push(item);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut members = Map::new();
+        while let Some(name) = map.next_key::<String>()? {
+            match self.0.member(&name) {
+                Some(keep) => {
+                    members.insert(name, map.next_value_seed(Pruned(keep))?);
+                }
+                None => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(Value::Object(members))
+    }
+}
@@ -0,0 +1,122 @@
+use crate::output::create_output_file;
+use crate::summary::status;
+use crate::utils::{TextMode, items, sanitize_markdown, text};
+use crate::{SearchParametersArgs, load_resources};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{BufWriter, Write};
+
+/// A search parameter of a resource type, from a SearchParameter or only declared by a
+/// CapabilityStatement
+#[derive(Default)]
+struct SearchParam {
+    code: String,
+    param_type: String,
+    expression: String,
+    targets: Vec<String>,
+    modifiers: Vec<String>,
+    description: String,
+    url: Option<String>,
+    /// the capability statements supporting it
+    supported_by: Vec<String>,
+}
+
+/// Writes a markdown table of the search parameters per resource type, by default
+/// <resource>_search.md, with the code, type, FHIRPath expression, target resource types,
+/// modifiers and description of each. The CapabilityStatements among the files add the
+/// parameters they declare for each resource and a column of the statements supporting each.
+pub fn generate_search_parameters(args: &SearchParametersArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let resources = load_resources(&args.common, &["SearchParameter", "CapabilityStatement"]);
+    // resource types and codes in alphabetical order
+    let mut params = BTreeMap::<String, BTreeMap<String, SearchParam>>::new();
+    for (_file, resource) in resources.iter().filter(|(_, r)| r["resourceType"] == "SearchParameter") {
+        let bases = strings(&resource["base"]);
+        for base in bases.iter() {
+            let param = SearchParam {
+                code: text(resource, "code").to_string(),
+                param_type: text(resource, "type").to_string(),
+                expression: expression_of(text(resource, "expression"), base, bases.len()),
+                targets: strings(&resource["target"]),
+                modifiers: strings(&resource["modifier"]),
+                description: text(resource, "description").to_string(),
+                url: resource["url"].as_str().map(|url| url.to_string()),
+                supported_by: Vec::new(),
+            };
+            params.entry(base.clone()).or_default().insert(param.code.clone(), param);
+        }
+    }
+
+    let statements = resources.iter().filter(|(_, r)| r["resourceType"] == "CapabilityStatement").collect::<Vec<_>>();
+    for (_file, statement) in statements.iter() {
+        let name = statement["title"].as_str().or(statement["name"].as_str()).or(statement["id"].as_str()).unwrap_or("CapabilityStatement");
+        for resource in items(&statement["rest"]).flat_map(|rest| items(&rest["resource"])) {
+            let resource_params = params.entry(text(resource, "type").to_string()).or_default();
+            for declared in items(&resource["searchParam"]) {
+                let definition = declared["definition"].as_str();
+                // matched by the canonical of the definition, or else by name
+                let code = resource_params
+                    .values()
+                    .find(|param| definition.is_some() && param.url.as_deref() == definition)
+                    .map(|param| param.code.clone())
+                    .unwrap_or_else(|| text(declared, "name").to_string());
+                let param = resource_params.entry(code.clone()).or_insert_with(|| SearchParam {
+                    code,
+                    param_type: text(declared, "type").to_string(),
+                    description: text(declared, "documentation").to_string(),
+                    url: definition.map(|definition| definition.to_string()),
+                    ..SearchParam::default()
+                });
+                param.supported_by.push(name.to_string());
+            }
+        }
+    }
+
+    for (resource_type, resource_params) in params.iter() {
+        status!("processing: {}", resource_type);
+        let mut writer = BufWriter::new(create_output_file(args.output_pattern.replace("{resource}", resource_type))?);
+        writeln!(writer, "# {} search parameters\n", resource_type)?;
+        let mut header = "| Code | Type | Expression | Targets | Modifiers | Description |".to_string();
+        if !statements.is_empty() {
+            header += " Supported by |";
+        }
+        writeln!(writer, "{}\n{}", header, "|---".repeat(header.matches(" |").count()) + "|")?;
+        for param in resource_params.values() {
+            let code = match &param.url {
+                Some(url) => format!("[{}]({})", param.code, url),
+                None => param.code.clone(),
+            };
+            write!(
+                writer,
+                "| {} | {} | {} | {} | {} | {} |",
+                code,
+                sanitize_markdown(&param.param_type, TextMode::Escape, None),
+                if param.expression.is_empty() { String::new() } else { format!("`{}`", param.expression.replace('|', "\\|")) },
+                sanitize_markdown(&param.targets.join(", "), TextMode::Escape, None),
+                sanitize_markdown(&param.modifiers.join(", "), TextMode::Escape, None),
+                sanitize_markdown(&param.description, TextMode::Escape, None)
+            )?;
+            if !statements.is_empty() {
+                write!(writer, " {} |", sanitize_markdown(&param.supported_by.join(", "), TextMode::Escape, None))?;
+            }
+            writeln!(writer)?;
+        }
+    }
+    Ok(())
+}
+
+fn strings(value: &Value) -> Vec<String> {
+    items(value).filter_map(|item| item.as_str().map(|s| s.to_string())).collect()
+}
+
+/// The parts of the expression of a parameter on several resource types that apply to one, e.g.
+/// `Patient.name` of `Patient.name | Practitioner.name`, or the whole when none is on its own
+fn expression_of(expression: &str, base: &str, bases: usize) -> String {
+    if bases < 2 {
+        return expression.to_string();
+    }
+    let parts = expression
+        .split(" | ")
+        .filter(|part| part.trim_start_matches('(').starts_with(&format!("{}.", base)))
+        .collect::<Vec<_>>();
+    if parts.is_empty() { expression.to_string() } else { parts.join(" | ") }
+}
@@ -0,0 +1,142 @@
+use crate::output::{self, OutputTarget};
+use crate::summary::status;
+use crate::{ServeArgs, SiteArgs, default_args, fsh, load_structure_definition_files, site};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Polls the version of the pages, and reloads the page when they are generated again
+const RELOAD_SCRIPT: &str = r#"<script>
+let version = null;
+setInterval(() => fetch("/__version").then(r => r.text()).then(v => {
+  if (version !== null && v !== version) location.reload();
+  version = v;
+}).catch(() => {}), 1000);
+</script>"#;
+
+/// The generated pages, by path below the site
+struct Pages {
+    documents: BTreeMap<PathBuf, Vec<u8>>,
+    /// counts the generations, for the pages to reload when it changes
+    version: usize,
+    /// when the inputs were last modified, as of the generation
+    modified: Vec<Option<SystemTime>>,
+}
+
+/// Serves the static HTML site of the structure definitions, with a page and class diagram per
+/// structure definition, on localhost from memory. The pages are generated again when an input
+/// file changes, which the browser notices and reloads. Each connection is answered on its own
+/// thread, from the pages as they were when it was accepted, so that a slow or idle client does
+/// not hold up the others.
+pub fn serve(args: &ServeArgs) -> Result<(), Box<dyn std::error::Error>> {
+    output::set_target(OutputTarget::Memory);
+    let listener = TcpListener::bind(("127.0.0.1", args.port))?;
+    let mut pages = Arc::new(generate(args, 1)?);
+    status!("serving: http://{}/", listener.local_addr()?);
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        if modified(&fsh::watched(&args.common.files)) != pages.modified {
+            status!("regenerating: the input files changed");
+            match generate(args, pages.version + 1) {
+                Ok(generated) => pages = Arc::new(generated),
+                // the pages before are served until the inputs can be read again
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        let pages = Arc::clone(&pages);
+        // a failed request does not stop the server
+        thread::spawn(move || respond(stream, &pages));
+    }
+    Ok(())
+}
+
+fn generate(args: &ServeArgs, version: usize) -> Result<Pages, Box<dyn std::error::Error>> {
+    let modified = modified(&fsh::watched(&args.common.files));
+    let docs = load_structure_definition_files(&args.common)?;
+    let mut site_args = default_args::<SiteArgs>();
+    site_args.title = args.title.clone();
+    site_args.html = true;
+    site_args.plantuml = args.plantuml.clone();
+    site_args.plantuml_server = args.plantuml_server.clone();
+    site::generate_site(&site_args, &docs)?;
+    Ok(Pages { documents: output::take_documents(), version, modified })
+}
+
+/// When each input file was last modified, none for those that cannot be read
+fn modified(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files.iter().map(|file| file.metadata().and_then(|metadata| metadata.modified()).ok()).collect()
+}
+
+/// Answers a GET request with a page, the version of the pages for `/__version`, or 404
+fn respond(mut stream: TcpStream, pages: &Pages) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // the headers are not used
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or_default(), parts.next().unwrap_or("/"));
+    let path = target.split(['?', '#']).next().unwrap_or_default().trim_start_matches('/');
+    let path = if path.is_empty() { "index.html" } else { path };
+    let (status, content_type, body) = if method != "GET" && method != "HEAD" {
+        ("405 Method Not Allowed", "text/plain; charset=utf-8", b"Only GET is supported\n".to_vec())
+    } else if path == "__version" {
+        ("200 OK", "text/plain; charset=utf-8", pages.version.to_string().into_bytes())
+    } else if let Some(document) = page(pages, path) {
+        ("200 OK", content_type(path), document)
+    } else {
+        ("404 Not Found", "text/plain; charset=utf-8", format!("No page {}\n", path).into_bytes())
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    if method != "HEAD" {
+        stream.write_all(&body)?;
+    }
+    stream.flush()
+}
+
+/// A generated page, the HTML pages with the script reloading them
+fn page(pages: &Pages, path: &str) -> Option<Vec<u8>> {
+    let path = Path::new(path);
+    // only pages below the site
+    if !path.components().all(|component| matches!(component, Component::Normal(_))) {
+        return None;
+    }
+    let document = pages.documents.get(path)?;
+    if path.extension().is_some_and(|extension| extension == "html") {
+        let html = String::from_utf8_lossy(document);
+        let html = match html.rfind("</body>") {
+            Some(end) => format!("{}{}\n{}", &html[..end], RELOAD_SCRIPT, &html[end..]),
+            None => format!("{}{}\n", html, RELOAD_SCRIPT),
+        };
+        Some(html.into_bytes())
+    } else {
+        Some(document.clone())
+    }
+}
+
+fn content_type(path: &str) -> &'static str {
+    match Path::new(path).extension().and_then(|extension| extension.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("json") => "application/json",
+        Some("svg") => "image/svg+xml",
+        _ => "text/plain; charset=utf-8",
+    }
+}
@@ -0,0 +1,206 @@
+use crate::doc_index::DocIndex;
+use crate::output::{self, create_output_file};
+use crate::summary::status;
+use crate::utils::{escape_xml, get_slice_after_last_occurrence, reduce_datatypes};
+use crate::{
+    PlantUmlArgs, SiteArgs, StructureDefTreeInfo, TableArgs, TableContext, default_args, extension_view,
+    structure_metadata, write_plantuml_class, write_plantuml_header, write_table,
+};
+use std::{
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+};
+
+/// Generates an mdBook source tree, or plain static HTML, with one chapter per structure definition
+pub fn generate_site(args: &SiteArgs, docs: &[StructureDefTreeInfo]) -> Result<(), Box<dyn std::error::Error>> {
+    if args.html {
+        generate_html_site(args, docs)
+    } else {
+        generate_mdbook(args, docs)
+    }
+}
+
+/// PlantUML class diagram of a single structure definition and its outgoing relations
+pub fn class_diagram(
+    doc: &StructureDefTreeInfo,
+    index: &DocIndex,
+    plantuml_args: &PlantUmlArgs,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut diagram = Vec::<u8>::new();
+    write_plantuml_header(&mut diagram, plantuml_args)?;
+    let relations = write_plantuml_class(&mut diagram, doc, index, plantuml_args)?;
+    write!(diagram, "{}", relations)?;
+    writeln!(diagram, "@enduml")?;
+    Ok(String::from_utf8(diagram)?)
+}
+
+/// Folder of the site, the output folder if given, otherwise "site"
+fn site_root() -> PathBuf {
+    if output::has_output_dir() { PathBuf::new() } else { PathBuf::from("site") }
+}
+
+fn generate_mdbook(args: &SiteArgs, docs: &[StructureDefTreeInfo]) -> Result<(), Box<dyn std::error::Error>> {
+    let root = site_root();
+    let src = root.join("src");
+    let mut book = BufWriter::new(create_output_file(root.join("book.toml"))?);
+    writeln!(book, "[book]\ntitle = \"{}\"\nsrc = \"src\"", args.title.replace('"', "\\\""))?;
+
+    let mut summary = BufWriter::new(create_output_file(src.join("SUMMARY.md"))?);
+    writeln!(summary, "# Summary\n")?;
+
+    let mut table_args = default_args::<TableArgs>();
+    table_args.common.lang = args.common.lang.clone();
+    let plantuml_args = default_args::<PlantUmlArgs>();
+    let ctx = TableContext::new(&table_args, docs)?;
+    let index = DocIndex::new(docs);
+    for (doc_num, doc) in docs.iter().enumerate() {
+        status!("processing: {}", doc.id);
+        writeln!(summary, "- [{}]({}.md)", doc.id, doc.id)?;
+
+        let mut writer = BufWriter::new(create_output_file(src.join(format!("{}.md", doc.id)))?);
+        writeln!(writer, "# {}\n", doc.id)?;
+        if args.metadata {
+            for (name, value) in structure_metadata(doc) {
+                writeln!(writer, "- **{}**: {}", name, value.replace('\n', " "))?;
+            }
+            writeln!(writer)?;
+        }
+        // rendered by the mdbook-plantuml preprocessor
+        writeln!(writer, "```plantuml\n{}```\n", class_diagram(doc, &index, &plantuml_args)?)?;
+        write_table(&mut writer, doc, doc_num, &ctx)?;
+    }
+    Ok(())
+}
+
+fn generate_html_site(args: &SiteArgs, docs: &[StructureDefTreeInfo]) -> Result<(), Box<dyn std::error::Error>> {
+    let root = site_root();
+
+    let title = escape_xml(&args.title);
+    let mut index_page = BufWriter::new(create_output_file(root.join("index.html"))?);
+    writeln!(
+        index_page,
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n<body>\n<h1>{}</h1>\n<ul>",
+        title, title
+    )?;
+
+    let plantuml_args = default_args::<PlantUmlArgs>();
+    let index = DocIndex::new(docs);
+    for doc in docs.iter() {
+        status!("processing: {}", doc.id);
+        writeln!(index_page, "<li><a href=\"{}.html\">{}</a></li>", doc.id, doc.id)?;
+
+        let diagram = class_diagram(doc, &index, &plantuml_args)?;
+        let mut diagram_file = BufWriter::new(create_output_file(root.join(format!("{}.plantuml", doc.id)))?);
+        write!(diagram_file, "{}", diagram)?;
+
+        let mut writer = BufWriter::new(create_output_file(root.join(format!("{}.html", doc.id)))?);
+        writeln!(
+            writer,
+            "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n<body>\n<p><a href=\"index.html\">{}</a></p>\n<h1>{}</h1>",
+            doc.id, title, doc.id
+        )?;
+        if args.metadata {
+            writeln!(writer, "<dl>")?;
+            for (name, value) in structure_metadata(doc) {
+                writeln!(writer, "<dt>{}</dt><dd>{}</dd>", name, escape_xml(value))?;
+            }
+            writeln!(writer, "</dl>")?;
+        }
+        let image = match (&args.plantuml, &args.plantuml_server) {
+            (Some(plantuml), _) => {
+                let mut svg_file = create_output_file(root.join(format!("{}.svg", doc.id)))?;
+                svg_file.write_all(&render_svg(plantuml, &diagram)?)?;
+                Some(format!("{}.svg", doc.id))
+            }
+            // the text of the diagram in hexadecimal, which PlantUML servers take after ~h
+            (None, Some(server)) => Some(format!(
+                "{}/svg/~h{}",
+                server.trim_end_matches('/'),
+                diagram.bytes().map(|byte| format!("{:02x}", byte)).collect::<String>()
+            )),
+            (None, None) => None,
+        };
+        if let Some(image) = image {
+            writeln!(writer, "<p><img src=\"{}\" alt=\"Class diagram of {}\"></p>", escape_xml(&image), doc.id)?;
+        }
+        writeln!(
+            writer,
+            "<details><summary><a href=\"{}.plantuml\">Diagram</a></summary><pre>{}</pre></details>",
+            doc.id,
+            escape_xml(&diagram)
+        )?;
+        write_html_table(&mut writer, extension_view(doc).as_ref().unwrap_or(doc), &index)?;
+        writeln!(writer, "</body>\n</html>")?;
+    }
+
+    writeln!(index_page, "</ul>\n</body>\n</html>")?;
+    Ok(())
+}
+
+/// Renders a PlantUML diagram to SVG with the PlantUML executable, the diagram given on standard
+/// input
+fn render_svg(plantuml: &Path, diagram: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut child = Command::new(plantuml)
+        .args(["-tsvg", "-pipe"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Error running '{}', install PlantUML or leave out --plantuml: {}", plantuml.display(), e))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    // written on its own thread, for a large diagram not to block on the SVG not read yet
+    let diagram = diagram.to_string();
+    let writing = thread::spawn(move || stdin.write_all(diagram.as_bytes()));
+    let result = child.wait_with_output()?;
+    let written = writing.join().map_err(|_| "Error writing the diagram to PlantUML")?;
+    if !result.status.success() {
+        return Err(format!("Error rendering a class diagram: '{}' exited with {}", plantuml.display(), result.status).into());
+    }
+    written?;
+    Ok(result.stdout)
+}
+
+fn write_html_table<W: Write>(
+    writer: &mut W,
+    doc: &StructureDefTreeInfo,
+    index: &DocIndex,
+) -> Result<(), Box<dyn std::error::Error>> {
+    writeln!(
+        writer,
+        "<table>\n<tr><th>Element</th><th>Description</th><th>Datatype</th><th>Cardinality</th><th>Binding</th></tr>"
+    )?;
+    doc.element_tree.traverse(
+        |_idx, element, _| {
+            let hier_level = element.depth;
+            let element_part = get_slice_after_last_occurrence(&element.id, '.').unwrap_or(element.id.to_string());
+            let datatypes = element
+                .datatype
+                .iter()
+                .map(|datatype| {
+                    if let Some(target) = index.get(datatype) {
+                        format!("<a href=\"{}.html\">{}</a>", target.id, datatype)
+                    } else {
+                        escape_xml(datatype)
+                    }
+                })
+                .collect::<Vec<_>>();
+            writeln!(
+                writer,
+                "<tr><td>{}{}</td><td>{}</td><td>{}</td><td>{}..{}</td><td>{}</td></tr>",
+                "&nbsp;&nbsp;".repeat(hier_level),
+                escape_xml(&element_part),
+                escape_xml(&element.short),
+                reduce_datatypes(&datatypes),
+                element.min,
+                element.max,
+                escape_xml(element.value_set_title.as_ref().or(element.binding.as_ref()).map(|b| b.as_str()).unwrap_or_default())
+            )
+            .unwrap();
+        },
+        |_, _, _| (),
+        &mut (),
+    );
+    writeln!(writer, "</table>")?;
+    Ok(())
+}
@@ -0,0 +1,203 @@
+use crate::output::create_output_file;
+use crate::summary::status;
+use crate::utils::{TextMode, items, sanitize_markdown, text};
+use crate::{StructureMapArgs, load_resources};
+use serde_json::Value;
+use std::io::{BufWriter, Write};
+
+/// Writes a PlantUML activity diagram and a markdown listing of the rules per StructureMap, by
+/// default <id>_map.plantuml and <id>_map.md. Each group is a partition of the diagram and a
+/// section of the listing, with a step per rule: its sources, its targets with their transforms and
+/// the groups it calls, and the rules nested in it. Rules with a source condition are drawn as a
+/// decision.
+pub fn generate_structure_maps(args: &StructureMapArgs) -> Result<(), Box<dyn std::error::Error>> {
+    for (_file, map) in load_resources(&args.common, &["StructureMap"]).iter() {
+        let id = map["id"].as_str().or(map["name"].as_str()).unwrap_or("StructureMap");
+        status!("processing: {}", id);
+        let name = args.output_pattern.replace("{id}", id);
+        let title = map["title"].as_str().or(map["name"].as_str()).unwrap_or(id);
+
+        let mut writer = BufWriter::new(create_output_file(format!("{}.plantuml", name))?);
+        writeln!(writer, "@startuml\ntitle {}\n", title)?;
+        for group in items(&map["group"]) {
+            writeln!(writer, "partition \"{}\" {{\nstart", group_signature(group).replace('"', "'"))?;
+            for rule in items(&group["rule"]) {
+                write_rule_activity(&mut writer, rule)?;
+            }
+            writeln!(writer, "stop\n}}\n")?;
+        }
+        writeln!(writer, "@enduml")?;
+
+        let mut writer = BufWriter::new(create_output_file(format!("{}.md", name))?);
+        write_rule_listing(&mut writer, map, title)?;
+    }
+    Ok(())
+}
+
+/// Name and inputs of a group, e.g. `PatientGroup(source src : EHDSPatient, target tgt : Patient)`
+fn group_signature(group: &Value) -> String {
+    let inputs = items(&group["input"])
+        .map(|input| {
+            let mut signature = format!("{} {}", text(input, "mode"), text(input, "name"));
+            if let Some(datatype) = input["type"].as_str() {
+                signature = format!("{} : {}", signature, datatype);
+            }
+            signature
+        })
+        .collect::<Vec<_>>();
+    let mut signature = format!("{}({})", text(group, "name"), inputs.join(", "));
+    if let Some(extends) = group["extends"].as_str() {
+        signature = format!("{} extends {}", signature, extends);
+    }
+    signature
+}
+
+/// A source of a rule, e.g. `src.name : HumanName as n`
+fn source_text(source: &Value) -> String {
+    let mut text = text(source, "context").to_string();
+    if let Some(element) = source["element"].as_str() {
+        text = format!("{}.{}", text, element);
+    }
+    if let Some(datatype) = source["type"].as_str() {
+        text = format!("{} : {}", text, datatype);
+    }
+    if let Some(variable) = source["variable"].as_str() {
+        text = format!("{} as {}", text, variable);
+    }
+    if let Some(check) = source["check"].as_str() {
+        text = format!("{} check {}", text, check);
+    }
+    text
+}
+
+/// A target of a rule with its transform, e.g. `tgt.name = create('HumanName') as tn`
+fn target_text(target: &Value) -> String {
+    let mut text = text(target, "context").to_string();
+    if let Some(element) = target["element"].as_str() {
+        text = format!("{}.{}", text, element);
+    }
+    if let Some(transform) = target["transform"].as_str() {
+        let parameters = items(&target["parameter"]).map(parameter_text).collect::<Vec<_>>();
+        text = format!("{} = {}({})", text, transform, parameters.join(", "));
+    }
+    if let Some(variable) = target["variable"].as_str() {
+        text = format!("{} as {}", text, variable);
+    }
+    text
+}
+
+/// A parameter of a transform or a dependent rule: a variable as is and other values as literals
+fn parameter_text(parameter: &Value) -> String {
+    let Some((key, value)) = parameter.as_object().and_then(|members| members.iter().find(|(key, _)| key.starts_with("value"))) else {
+        return String::new();
+    };
+    match (key.as_str(), value) {
+        ("valueId", Value::String(variable)) => variable.clone(),
+        (_, Value::String(literal)) => format!("'{}'", literal),
+        (_, literal) => literal.to_string(),
+    }
+}
+
+/// The groups a rule calls with their arguments, e.g. `HumanName(n, tn)`, the variables listed as
+/// `variable` before R5 and as `parameter` from R5
+fn dependent_texts(rule: &Value) -> Vec<String> {
+    items(&rule["dependent"])
+        .map(|dependent| {
+            let arguments = items(&dependent["variable"])
+                .filter_map(|variable| variable.as_str().map(|v| v.to_string()))
+                .chain(items(&dependent["parameter"]).map(parameter_text))
+                .collect::<Vec<_>>();
+            format!("{}({})", text(dependent, "name"), arguments.join(", "))
+        })
+        .collect()
+}
+
+/// Writes a rule as an activity, within a decision on the conditions of its sources, followed by
+/// its nested rules
+fn write_rule_activity<W: Write>(writer: &mut W, rule: &Value) -> std::io::Result<()> {
+    let conditions = items(&rule["source"]).filter_map(|source| source["condition"].as_str()).collect::<Vec<_>>();
+    if !conditions.is_empty() {
+        writeln!(writer, "if ({}) then (yes)", conditions.join(" and "))?;
+    }
+    let mut lines = vec![format!("**{}**", text(rule, "name"))];
+    lines.extend(items(&rule["source"]).map(source_text));
+    lines.extend(items(&rule["target"]).map(|target| format!("→ {}", target_text(target))));
+    if let Some(documentation) = rule["documentation"].as_str() {
+        lines.push(format!("//{}//", documentation.trim().replace('\n', " ")));
+    }
+    // a line ending with ';' would end the activity
+    writeln!(writer, ":{};", lines.join("\n").trim_end_matches(';'))?;
+    for dependent in dependent_texts(rule) {
+        writeln!(writer, ":call {};", dependent)?;
+    }
+    for nested in items(&rule["rule"]) {
+        write_rule_activity(writer, nested)?;
+    }
+    if !conditions.is_empty() {
+        writeln!(writer, "endif")?;
+    }
+    Ok(())
+}
+
+/// Writes the structures of a map and a table of the rules per group, nested rules named by the
+/// path of rule names, e.g. `name.given`
+fn write_rule_listing<W: Write>(writer: &mut W, map: &Value, title: &str) -> std::io::Result<()> {
+    writeln!(writer, "# {}\n", title)?;
+    if let Some(url) = map["url"].as_str() {
+        writeln!(writer, "{}\n", url)?;
+    }
+    if let Some(description) = map["description"].as_str() {
+        writeln!(writer, "{}\n", description.trim())?;
+    }
+    if map["structure"].is_array() {
+        writeln!(writer, "| Structure | Mode | Alias |\n|---|---|---|")?;
+        for structure in items(&map["structure"]) {
+            writeln!(writer, "| {} | {} | {} |", text(structure, "url"), text(structure, "mode"), text(structure, "alias"))?;
+        }
+        writeln!(writer)?;
+    }
+    for group in items(&map["group"]) {
+        writeln!(writer, "## {}\n\n`{}`\n", text(group, "name"), group_signature(group))?;
+        if let Some(documentation) = group["documentation"].as_str() {
+            writeln!(writer, "{}\n", documentation.trim())?;
+        }
+        writeln!(writer, "| Rule | Sources | Targets | Calls | Documentation |\n|---|---|---|---|---|")?;
+        for rule in items(&group["rule"]) {
+            write_rule_row(writer, rule, "")?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+fn write_rule_row<W: Write>(writer: &mut W, rule: &Value, parent: &str) -> std::io::Result<()> {
+    let path = if parent.is_empty() {
+        text(rule, "name").to_string()
+    } else {
+        format!("{}.{}", parent, text(rule, "name"))
+    };
+    let cell = |texts: Vec<String>| {
+        texts
+            .iter()
+            .map(|text| format!("`{}`", text.replace('`', "'").replace('|', "\\|")))
+            .collect::<Vec<_>>()
+            .join("<br/>")
+    };
+    let mut sources = items(&rule["source"]).map(source_text).collect::<Vec<_>>();
+    for condition in items(&rule["source"]).filter_map(|source| source["condition"].as_str()) {
+        sources.push(format!("where {}", condition));
+    }
+    writeln!(
+        writer,
+        "| {} | {} | {} | {} | {} |",
+        sanitize_markdown(&path, TextMode::Escape, None),
+        cell(sources),
+        cell(items(&rule["target"]).map(target_text).collect()),
+        cell(dependent_texts(rule)),
+        sanitize_markdown(text(rule, "documentation"), TextMode::Escape, None)
+    )?;
+    for nested in items(&rule["rule"]) {
+        write_rule_row(writer, nested, &path)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::File,
+    io::{self, Write},
+    path::Path,
+    sync::{
+        Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Instant,
+};
+
+/// What a run has read, skipped and written, reported when it ends
+struct Summary {
+    files_read: usize,
+    files_skipped: Vec<(String, String)>,
+    /// number of documents written per format, i.e. file extension
+    documents: BTreeMap<String, usize>,
+    elements: usize,
+    warnings: usize,
+}
+
+static SUMMARY: Mutex<Summary> = Mutex::new(Summary {
+    files_read: 0,
+    files_skipped: Vec::new(),
+    documents: BTreeMap::new(),
+    elements: 0,
+    warnings: 0,
+});
+
+fn summary() -> MutexGuard<'static, Summary> {
+    SUMMARY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+pub fn file_read() {
+    summary().files_read += 1;
+}
+
+pub fn file_skipped(file: &Path, reason: &str) {
+    summary().files_skipped.push((file.display().to_string(), reason.to_string()));
+}
+
+pub fn elements_processed(count: usize) {
+    summary().elements += count;
+}
+
+pub fn warning() {
+    summary().warnings += 1;
+}
+
+/// Whether a document is written to standard output, so that messages on the progress of the
+/// run are printed on standard error instead
+static OUTPUT_TO_STDOUT: AtomicBool = AtomicBool::new(false);
+
+pub fn set_output_to_stdout() {
+    OUTPUT_TO_STDOUT.store(true, Ordering::Relaxed);
+}
+
+pub fn print_status(message: fmt::Arguments) {
+    if OUTPUT_TO_STDOUT.load(Ordering::Relaxed) {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+/// Prints a message on the progress of the run, like `println!` unless a document is written
+/// to standard output
+macro_rules! status {
+    ($($arg:tt)*) => {
+        $crate::summary::print_status(format_args!($($arg)*))
+    };
+}
+pub(crate) use status;
+
+/// Counts a document among those generated in its format, i.e. file extension
+pub fn document_written(format: &str) {
+    *summary().documents.entry(format.to_string()).or_default() += 1;
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Summary:")?;
+        writeln!(f, "  files read: {}", self.files_read)?;
+        writeln!(f, "  files skipped: {}", self.files_skipped.len())?;
+        for (file, reason) in self.files_skipped.iter() {
+            writeln!(f, "    {}: {}", file, reason)?;
+        }
+        let documents = self
+            .documents
+            .iter()
+            .map(|(format, count)| if format.is_empty() { count.to_string() } else { format!("{} {}", count, format) })
+            .collect::<Vec<_>>();
+        writeln!(
+            f,
+            "  documents generated: {}",
+            if documents.is_empty() { "0".to_string() } else { documents.join(", ") }
+        )?;
+        writeln!(f, "  elements processed: {}", self.elements)?;
+        writeln!(f, "  warnings: {}", self.warnings)
+    }
+}
+
+/// Whether the elapsed time is left out of the summary, so that it is the same in each run
+static OMIT_ELAPSED_TIME: AtomicBool = AtomicBool::new(false);
+
+pub fn omit_elapsed_time() {
+    OMIT_ELAPSED_TIME.store(true, Ordering::Relaxed);
+}
+
+/// Prints the summary of the run, and writes it to `summary_file` if given
+pub fn report(started: Instant, summary_file: Option<&Path>) -> io::Result<()> {
+    let mut text = summary().to_string();
+    if !OMIT_ELAPSED_TIME.load(Ordering::Relaxed) {
+        text += &format!("  elapsed: {:.2} s\n", started.elapsed().as_secs_f64());
+    }
+    status!("{}", text.trim_end());
+    if let Some(summary_file) = summary_file {
+        File::create(summary_file)?.write_all(text.as_bytes())?;
+    }
+    Ok(())
+}
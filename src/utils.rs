@@ -1,7 +1,40 @@
+use clap::ValueEnum;
 use serde_json::Value;
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::PathBuf;
+use std::rc::Rc;
+
+/// Shared copies of the strings repeated across the elements, e.g. an id, datatype, binding strength,
+/// value set URL or cardinality, so that each is held in memory once however many elements have
+/// it. One is made per load and dropped with it, so reloads in `batch`, `serve` and `browse` do
+/// not keep the strings of the earlier loads
+#[derive(Default)]
+pub struct Interner(HashSet<Rc<str>>);
+
+impl Interner {
+    pub fn intern(&mut self, s: &str) -> Rc<str> {
+        match self.0.get(s) {
+            Some(shared) => Rc::clone(shared),
+            None => {
+                let shared = Rc::<str>::from(s);
+                self.0.insert(Rc::clone(&shared));
+                shared
+            }
+        }
+    }
+}
+
+/// The items of a JSON array, none if the value is not one, e.g. a member not given
+pub fn items(value: &Value) -> impl Iterator<Item = &Value> {
+    value.as_array().into_iter().flatten()
+}
+
+/// A string member of a JSON object, empty if it is not given
+pub fn text<'a>(value: &'a Value, key: &str) -> &'a str {
+    value[key].as_str().unwrap_or_default()
+}
 
 pub fn load_json_from_file(path: &PathBuf) -> Result<Value, Box<dyn std::error::Error>> {
     let file = File::open(path)?;
@@ -20,22 +53,53 @@ pub fn get_slice_before_first_occurrence(s: &str, c: char) -> Option<String> {
         .map(|first_index| s[..first_index ].to_string())
 }
 
+/// A title as the name of a file, with the characters not allowed in file names on some system,
+/// e.g. '/' or ':', replaced by '_'
+pub fn file_name(title: &str) -> String {
+    title
+        .chars()
+        .map(|c| if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') { '_' } else { c })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+// Function to convert an element id to a stable markdown anchor, e.g. "Patient.name[x]" to "patient-name-x".
+pub fn slugify(s: &str) -> String {
+    let mut result = String::new();
+    for c in s.chars() {
+        if c.is_alphanumeric() {
+            result.extend(c.to_lowercase());
+        } else if !result.is_empty() && !result.ends_with('-') {
+            result.push('-');
+        }
+    }
+    result.trim_end_matches('-').to_string()
+}
+
 // pub fn get_slice_after_first_occurrence(s: &str, c: char) -> Option<String> {
 //     s.find(c)
 //         .map(|first_index| s[first_index..].to_string())
 // }
 
-pub fn count_char_occurrences(s: &str, c: char) -> usize {
-    s.chars().filter(|&ch| ch == c).count()
-}
-
+// Splits camel case into capitalized words. A run of capitals stays one word (an acronym), and
+// digits are separated from a preceding lowercase word, so "ODSOrganisationID" becomes
+// "ODS Organisation ID" and "address1" becomes "Address 1".
 pub fn camel_to_spaced_pascal(s: &str) -> String {
     let mut result = String::new();
-    let mut chars = s.chars().peekable();
+    let chars = s.chars().collect::<Vec<_>>();
 
-    while let Some(c) = chars.next() {
-        let next = chars.peek();
-        if c.is_uppercase() && !result.is_empty() && next.is_some_and(|x| x.is_lowercase()) {
+    for (i, &c) in chars.iter().enumerate() {
+        let prev = if i > 0 { chars.get(i - 1) } else { None };
+        let next = chars.get(i + 1);
+        let boundary = match prev {
+            Some(p) if c.is_uppercase() => {
+                p.is_lowercase() || p.is_ascii_digit() || (p.is_uppercase() && next.is_some_and(|x| x.is_lowercase()))
+            }
+            Some(p) if c.is_ascii_digit() => p.is_lowercase(),
+            _ => false,
+        };
+        if boundary {
             result.push(' ');
         }
         result.push(c);
@@ -54,14 +118,106 @@ pub fn camel_to_spaced_pascal(s: &str) -> String {
         .join(" ")
 }
 
-pub fn reduce_datatypes(datatypes: &[String]) -> String {
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum TextMode {
+    /// Escape characters that break tables or render as markdown
+    Escape,
+    /// Remove embedded markdown (emphasis, code and links) and escape the rest
+    Strip,
+}
+
+// Function to make free text safe for a markdown table cell.
+// Newlines become <br/>, and text longer than 'max_len' characters is cut with an ellipsis.
+pub fn sanitize_markdown(s: &str, mode: TextMode, max_len: Option<usize>) -> String {
+    let mut text = s.trim().to_string();
+    if mode == TextMode::Strip {
+        text = strip_markdown(&text);
+    }
+    if let Some(max_len) = max_len
+        && text.chars().count() > max_len
+    {
+        text = text.chars().take(max_len).collect::<String>().trim_end().to_string() + "…";
+    }
+    let mut result = String::new();
+    for c in text.chars() {
+        match c {
+            '|' | '*' | '`' if mode == TextMode::Escape => {
+                result.push('\\');
+                result.push(c);
+            }
+            '|' => result.push_str("\\|"),
+            '\r' => (),
+            '\n' => result.push_str("<br/>"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+fn strip_markdown(s: &str) -> String {
+    let mut result = String::new();
+    let mut chars = s.chars().peekable();
+    let mut at_line_start = true;
+    while let Some(c) = chars.next() {
+        match c {
+            '*' | '`' => (),
+            '#' if at_line_start => {
+                while chars.peek() == Some(&'#') || chars.peek() == Some(&' ') {
+                    chars.next();
+                }
+            }
+            // [text](url) becomes text
+            '[' => {
+                let mut link_text = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        closed = true;
+                        break;
+                    }
+                    link_text.push(c);
+                }
+                result.push_str(&link_text);
+                if closed && chars.peek() == Some(&'(') {
+                    for c in chars.by_ref() {
+                        if c == ')' {
+                            break;
+                        }
+                    }
+                }
+            }
+            _ => result.push(c),
+        }
+        at_line_start = c == '\n';
+    }
+    result
+}
+
+pub fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Quotes a CSV field when it contains a separator, a quote or a line break
+pub fn escape_csv(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+pub fn reduce_datatypes<S: AsRef<str>>(datatypes: &[S]) -> String {
     let mut result = String::new();
     let mut first = true;
     for d in datatypes.iter() {
         if !first {
             result.push_str(", ");
         };
-        result.push_str(d);
+        result.push_str(d.as_ref());
         first = false;
     }
     result
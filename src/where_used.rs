@@ -0,0 +1,61 @@
+use crate::output::create_output_file;
+use crate::summary::status;
+use crate::utils::get_slice_before_first_occurrence;
+use crate::{StructureDefTreeInfo, WhereUsedArgs};
+use std::collections::BTreeMap;
+use std::io::{BufWriter, Write};
+
+/// Writes a markdown report listing, for each loaded structure definition and each bound value set,
+/// the elements of the other structure definitions that use it
+pub fn generate_where_used(args: &WhereUsedArgs, docs: &[StructureDefTreeInfo]) -> Result<(), Box<dyn std::error::Error>> {
+    // value set canonical (without version) to its title, if resolved, and the binding elements
+    let mut value_sets = BTreeMap::<String, (Option<String>, Vec<String>)>::new();
+    let mut writer = BufWriter::new(create_output_file(&args.output_file)?);
+    writeln!(writer, "# Where used\n")?;
+
+    for doc in docs.iter() {
+        status!("processing: {}", doc.id);
+        let mut uses = Vec::<(String, &str)>::new();
+        for other in docs.iter().filter(|d| d.id != doc.id) {
+            for (_idx, element) in other.element_tree.iter() {
+                if element.reference.iter().any(|reference| **reference == doc.id) {
+                    uses.push((element.id.to_string(), "reference"));
+                } else if element.datatype.iter().any(|datatype| **datatype == doc.id) {
+                    uses.push((element.id.to_string(), "type"));
+                }
+            }
+        }
+        for (_idx, element) in doc.element_tree.iter() {
+            if let Some(value_set) = &element.value_set {
+                let canonical = get_slice_before_first_occurrence(value_set, '|').unwrap_or(value_set.to_string());
+                let entry = value_sets.entry(canonical).or_default();
+                if entry.0.is_none() {
+                    entry.0 = element.value_set_title.clone();
+                }
+                entry.1.push(element.id.to_string());
+            }
+        }
+
+        writeln!(writer, "## {}\n", doc.id)?;
+        if uses.is_empty() {
+            writeln!(writer, "Not used by the other structure definitions.\n")?;
+        } else {
+            for (element_id, kind) in uses.iter() {
+                writeln!(writer, "- `{}` ({})", element_id, kind)?;
+            }
+            writeln!(writer)?;
+        }
+    }
+
+    for (canonical, (title, elements)) in value_sets.iter() {
+        match title {
+            Some(title) => writeln!(writer, "## {} ({})\n", title, canonical)?,
+            None => writeln!(writer, "## {}\n", canonical)?,
+        }
+        for element_id in elements.iter() {
+            writeln!(writer, "- `{}` (binding)", element_id)?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
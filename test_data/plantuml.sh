@@ -0,0 +1,7 @@
+#!/bin/sh
+# Stands in for PlantUML in the tests: `plantuml.sh -tsvg -pipe` "renders" the diagram on standard
+# input as an SVG image with the number of its lines
+set -e
+test "$1" = -tsvg
+test "$2" = -pipe
+printf '<svg xmlns="http://www.w3.org/2000/svg"><text>%s lines</text></svg>\n' "$(wc -l)"
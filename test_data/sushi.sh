@@ -0,0 +1,8 @@
+#!/bin/sh
+# Stands in for SUSHI in the tests: `sushi.sh build <project> --out <out> --snapshot` "builds" the
+# EHDSAddress logical model of test_data/EHDSAddress.fsh
+set -e
+test -f "$2/sushi-config.yaml"
+mkdir -p "$4/fsh-generated/resources"
+cp "$(dirname "$0")/ep-ed/StructureDefinition-EHDSAddress.json" "$4/fsh-generated/resources/"
+echo "Number of StructureDefinitions: 1"
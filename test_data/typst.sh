@@ -0,0 +1,7 @@
+#!/bin/sh
+# Stands in for typst in the tests: `typst.sh compile --root <root> <source> <pdf>` writes the
+# root and the source it is given to the "PDF"
+set -e
+test "$1" = compile
+printf 'root: %s\n' "$3" > "$5"
+cat "$4" >> "$5"
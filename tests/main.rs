@@ -5,6 +5,7 @@ use std::fs;
 fn test_plantuml_generation() {
     let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
     cmd.arg("plant-uml")
+        .arg("--force")
         .arg("--output-file")
         .arg("output.plantuml")
         .arg("test_data/ps/*.json");
@@ -20,11 +21,14 @@ fn test_plantuml_generation() {
 fn test_mindmap_generation() {
     let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
     cmd.arg("mindmap")
-        .arg("test_data/StructureDefinition-EHDSAddress.json");
+        .arg("--force")
+        .arg("--output-dir")
+        .arg("target/test-mindmap")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSAddress.json");
 
     cmd.assert().success();
 
-    let output = fs::read_to_string("EHDSAddress.plantuml").unwrap();
+    let output = fs::read_to_string("target/test-mindmap/EHDSAddress_mindmap.plantuml").unwrap();
     assert!(output.contains("@startmindmap"));
     assert!(output.contains("@endmindmap"));
 }
@@ -33,10 +37,1839 @@ fn test_mindmap_generation() {
 fn test_table_generation() {
     let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
     cmd.arg("table")
-        .arg("test_data/StructureDefinition-EHDSAddress.json");
+        .arg("--force")
+        .arg("--output-dir")
+        .arg("target/test-table")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSAddress.json");
 
     cmd.assert().success();
 
-    let output = fs::read_to_string("EHDSAddress.md").unwrap();
-    assert!(output.contains("| Code | Element | Short | Definition | Datatype | Cardinality | Preferred Code System | Binding Strength |"));
+    let output = fs::read_to_string("target/test-table/EHDSAddress.md").unwrap();
+    assert!(output.contains("| Code | Level | Element Name | Element Description | Data type | Cardinality | Binding requirements |"));
+}
+
+#[test]
+fn test_plantuml_split_generation() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("plant-uml")
+        .arg("--force")
+        .arg("--split")
+        .arg("--output-dir")
+        .arg("target/test-split")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSAddress.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/test-split/EHDSAddress.plantuml").unwrap();
+    assert!(output.contains("@startuml"));
+    assert!(output.contains("class **EHDSAddress**"));
+    assert!(output.contains("@enduml"));
+}
+
+#[test]
+fn test_site_generation() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("site")
+        .arg("--force")
+        .arg("--output-dir")
+        .arg("target/test-site")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSAddress.json")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSPatient.json");
+
+    cmd.assert().success();
+
+    let summary = fs::read_to_string("target/test-site/src/SUMMARY.md").unwrap();
+    assert!(summary.contains("- [EHDSPatient](EHDSPatient.md)"));
+    let chapter = fs::read_to_string("target/test-site/src/EHDSPatient.md").unwrap();
+    assert!(chapter.contains("```plantuml"));
+    assert!(chapter.contains("[EHDSAddress](EHDSAddress.md)"));
+}
+
+#[test]
+fn test_html_site_diagrams() {
+    let site = |folder: &str, render: &[&str]| {
+        let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+        cmd.arg("site")
+            .arg("--force")
+            .arg("--html")
+            .args(render)
+            .arg("--output-dir")
+            .arg(folder)
+            .arg("test_data/ep-ed/StructureDefinition-EHDSAddress.json");
+        cmd.assert().success();
+        fs::read_to_string(format!("{}/EHDSAddress.html", folder)).unwrap()
+    };
+
+    // rendered by PlantUML next to the page
+    let page = site("target/test-site-rendered", &["--plantuml", "test_data/plantuml.sh"]);
+    assert!(page.contains("<img src=\"EHDSAddress.svg\" alt=\"Class diagram of EHDSAddress\">"));
+    let svg = fs::read_to_string("target/test-site-rendered/EHDSAddress.svg").unwrap();
+    assert!(svg.starts_with("<svg"));
+
+    // by a PlantUML server, from the text of the diagram in hexadecimal ("@startuml")
+    let page = site("target/test-site-server", &["--plantuml-server", "http://localhost:8080/plantuml/"]);
+    assert!(page.contains("<img src=\"http://localhost:8080/plantuml/svg/~h407374617274756d6c"));
+
+    // the source only, without a renderer
+    let page = site("target/test-site-source", &[]);
+    assert!(!page.contains("<img"));
+    assert!(page.contains("<pre>@startuml"));
+}
+
+#[test]
+fn test_json_export() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("json")
+        .arg("--force")
+        .arg("--output-file")
+        .arg("target/test-model.json")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSAddress.json");
+
+    cmd.assert().success();
+
+    let output: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string("target/test-model.json").unwrap()).unwrap();
+    let root = &output["structureDefinitions"][0]["roots"][0];
+    assert_eq!(root["id"], "EHDSAddress");
+    assert_eq!(root["children"][0]["id"], "EHDSAddress.use");
+}
+
+#[test]
+fn test_json_export_all_roots() {
+    // without its root element, the top-level elements have no parent
+    let address = fs::read_to_string("test_data/ep-ed/StructureDefinition-EHDSAddress.json").unwrap();
+    let mut address = serde_json::from_str::<serde_json::Value>(&address).unwrap();
+    address["snapshot"]["element"].as_array_mut().unwrap().remove(0);
+    fs::create_dir_all("target/json_roots").unwrap();
+    fs::write("target/json_roots/StructureDefinition-EHDSAddress.json", address.to_string()).unwrap();
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("json")
+        .arg("--force")
+        .arg("--output-file")
+        .arg("target/json_roots/model.json")
+        .arg("target/json_roots/StructureDefinition-EHDSAddress.json");
+
+    cmd.assert().success();
+
+    let output: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string("target/json_roots/model.json").unwrap()).unwrap();
+    let roots = output["structureDefinitions"][0]["roots"].as_array().unwrap();
+    let ids = roots.iter().map(|root| root["id"].as_str().unwrap()).collect::<Vec<_>>();
+    assert_eq!(ids.len(), 9);
+    assert_eq!(ids[0], "EHDSAddress.use");
+    assert_eq!(ids[8], "EHDSAddress.country");
+}
+
+#[test]
+fn test_compare_generation() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("compare")
+        .arg("--force")
+        .arg("--output-file")
+        .arg("target/test-comparison.md")
+        .arg("test_data/ps/StructureDefinition-EHDSAlert.json")
+        .arg("test_data/dr/StructureDefinition-EHDSAlert.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/test-comparison.md").unwrap();
+    assert!(output.starts_with("| Element | EHDSAlert | EHDSAlert |"));
+    assert!(output.contains("| header.subject | 1..1 EHDSPatient | 1..1 EHDSPatient |"));
+}
+
+#[test]
+fn test_dictionary_generation() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("dictionary")
+        .arg("--force")
+        .arg("--output-file")
+        .arg("target/test-dictionary.md")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSAddress.json")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSPatient.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/test-dictionary.md").unwrap();
+    assert!(output.contains("- [EHDSAddress](#EHDSAddress)"));
+    assert!(output.contains("## Element index"));
+}
+
+#[test]
+fn test_batch_generation() {
+    for output in ["target/test-batch.plantuml", "target/test-batch.md", "target/test-batch-address.md"] {
+        let _ = fs::remove_file(output);
+    }
+    // the outputs are next to the manifest, not in the current folder
+    fs::create_dir_all("target/batch_cwd").unwrap();
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.current_dir("target/batch_cwd").arg("batch").arg("--force").arg("../../test_data/batch.toml");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/test-batch.plantuml").unwrap();
+    assert!(output.contains("@startuml"));
+    let output = fs::read_to_string("target/test-batch.md").unwrap();
+    assert!(output.contains("## Element index"));
+    assert!(fs::metadata("target/test-batch-address.md").is_ok());
+}
+
+#[test]
+fn test_all_generation() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.current_dir("target")
+        .arg("all")
+        .arg("--force")
+        .arg("--output-dir")
+        .arg("all")
+        .arg("--config")
+        .arg("../test_data/all.toml")
+        .arg("--with")
+        .arg("plant-uml,table,mindmap")
+        .arg("../test_data/ep-ed/StructureDefinition-EHDSPatient.json")
+        .arg("../test_data/ep-ed/StructureDefinition-EHDSAddress.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/all/all.plantuml").unwrap();
+    assert!(output.contains("class **EHDSPatient**"));
+    let output = fs::read_to_string("target/all/EHDSPatient.md").unwrap();
+    assert!(output.contains("| PAT.1 | + | personalIdentifier |"));
+    assert!(fs::metadata("target/all/EHDSAddress_mindmap.plantuml").is_ok());
+}
+
+#[test]
+fn test_all_common_option_in_config() {
+    fs::create_dir_all("target/all_common").unwrap();
+    fs::write("target/all_common/all.toml", "[table]\nlang = \"sv\"\n").unwrap();
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.current_dir("target/all_common")
+        .arg("all")
+        .arg("--force")
+        .arg("--config")
+        .arg("all.toml")
+        .arg("--with")
+        .arg("table")
+        .arg("../../test_data/ep-ed/StructureDefinition-EHDSAddress.json");
+
+    let output = cmd.assert().failure().get_output().stderr.clone();
+    assert!(String::from_utf8_lossy(&output).contains("Give --lang to all itself, not in the options of 'table' in the config file"));
+    assert!(fs::metadata("target/all_common/EHDSAddress.md").is_err());
+}
+
+#[test]
+fn test_example_generation() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("example")
+        .arg("--force")
+        .arg("--output-pattern")
+        .arg("target/{id}-example.json")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSPatient.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/EHDSPatient-example.json").unwrap();
+    assert!(output.contains("\"personalIdentifier\""));
+}
+
+#[test]
+fn test_testdata_generation() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("testdata")
+        .arg("--force")
+        .arg("--count")
+        .arg("3")
+        .arg("--output-pattern")
+        .arg("target/{id}-testdata.json")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSPatient.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/EHDSPatient-testdata.json").unwrap();
+    let instances: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(instances.as_array().map(|a| a.len()), Some(3));
+}
+
+#[test]
+fn test_testdata_max_below_min() {
+    let address = fs::read_to_string("test_data/ep-ed/StructureDefinition-EHDSAddress.json").unwrap();
+    let mut address = serde_json::from_str::<serde_json::Value>(&address).unwrap();
+    for element in address["snapshot"]["element"].as_array_mut().unwrap() {
+        if element["id"] == "EHDSAddress.street" {
+            element["min"] = serde_json::json!(2);
+            element["max"] = serde_json::json!("1");
+        }
+    }
+    fs::create_dir_all("target/max_below_min").unwrap();
+    fs::write("target/max_below_min/StructureDefinition-EHDSAddress.json", address.to_string()).unwrap();
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("--diagnostics-format")
+        .arg("json")
+        .arg("testdata")
+        .arg("--force")
+        .arg("--count")
+        .arg("3")
+        .arg("--output-pattern")
+        .arg("target/max_below_min/{id}-testdata.json")
+        .arg("target/max_below_min/StructureDefinition-EHDSAddress.json");
+
+    let stderr = cmd.assert().success().get_output().stderr.clone();
+
+    // generated as required, and reported once for all instances
+    let stderr = String::from_utf8_lossy(&stderr);
+    assert_eq!(stderr.matches("\"rule\":\"max-below-min\"").count(), 1);
+    let output = fs::read_to_string("target/max_below_min/EHDSAddress-testdata.json").unwrap();
+    let instances = serde_json::from_str::<serde_json::Value>(&output).unwrap();
+    assert!(instances.as_array().unwrap().iter().all(|instance| instance["street"].is_string()));
+}
+
+#[test]
+fn test_circular_reference_only_when_following_types() {
+    let address = fs::read_to_string("test_data/ep-ed/StructureDefinition-EHDSAddress.json").unwrap();
+    let mut address = serde_json::from_str::<serde_json::Value>(&address).unwrap();
+    let url = address["url"].clone();
+    for element in address["snapshot"]["element"].as_array_mut().unwrap() {
+        if element["id"] == "EHDSAddress.text" {
+            element["type"] = serde_json::json!([{ "code": url }]);
+        }
+    }
+    fs::create_dir_all("target/circular").unwrap();
+    fs::write("target/circular/StructureDefinition-EHDSAddress.json", address.to_string()).unwrap();
+    let circular = |generator: &str| {
+        let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+        cmd.arg("--diagnostics-format")
+            .arg("json")
+            .arg(generator)
+            .arg("--force")
+            .arg("--output-dir")
+            .arg(format!("target/circular/{}", generator))
+            .arg("target/circular/StructureDefinition-EHDSAddress.json");
+        let stderr = cmd.assert().success().get_output().stderr.clone();
+        String::from_utf8_lossy(&stderr).matches("\"rule\":\"circular-reference\"").count()
+    };
+
+    assert_eq!(circular("example"), 1);
+    assert_eq!(circular("table"), 0);
+}
+
+#[test]
+fn test_summary_of_failed_run() {
+    let _ = fs::remove_file("target/failed_summary.txt");
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("--summary-file")
+        .arg("target/failed_summary.txt")
+        .arg("table")
+        .arg("--codes-file")
+        .arg("target/missing_codes.csv")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSAddress.json");
+
+    let stdout = cmd.assert().failure().get_output().stdout.clone();
+    assert!(String::from_utf8_lossy(&stdout).contains("Summary:\n  files read: 1\n"));
+    let summary = fs::read_to_string("target/failed_summary.txt").unwrap();
+    assert!(summary.starts_with("Summary:\n  files read: 1\n"));
+}
+
+#[test]
+fn test_mindmap_plantuml_options_refused() {
+    for (format, option) in [("freemind", &["--link"][..]), ("opml", &["--depth-colors", "Orange"][..]), ("freemind", &["--box-level", "2"][..])] {
+        let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+        cmd.arg("mindmap")
+            .arg("--force")
+            .arg("--format")
+            .arg(format)
+            .args(option)
+            .arg("--output-dir")
+            .arg("target/mindmap_refused")
+            .arg("test_data/ep-ed/StructureDefinition-EHDSAddress.json");
+
+        let stderr = cmd.assert().failure().get_output().stderr.clone();
+        let expected = format!("{} applies to PlantUML mind maps only, not to --format {}", option[0], format);
+        assert!(String::from_utf8_lossy(&stderr).contains(&expected));
+    }
+    assert!(!std::path::Path::new("target/mindmap_refused").exists());
+}
+
+#[test]
+fn test_table_short_lang() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("table")
+        .arg("--force")
+        .arg("-l")
+        .arg("sv")
+        .arg("--output-dir")
+        .arg("target/short_lang")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSAddress.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/short_lang/EHDSAddress.md").unwrap();
+    assert!(output.contains("| Kod | Nivå | Elementnamn |"));
+}
+
+#[test]
+fn test_metrics_formats_on_stdout() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("--stdout")
+        .arg("metrics")
+        .arg("--json-file")
+        .arg("target/metrics_stdout.json")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSAddress.json");
+
+    let output = cmd.assert().failure().get_output().clone();
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot both be written to standard output"));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("# Metrics"));
+
+    // one of them on standard output
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("metrics")
+        .arg("--force")
+        .arg("--output-file")
+        .arg("target/metrics_stdout.md")
+        .arg("--json-file")
+        .arg("-")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSAddress.json");
+
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+    let metrics = serde_json::from_slice::<serde_json::Value>(&stdout).unwrap();
+    assert_eq!(metrics[0]["id"], "EHDSAddress");
+}
+
+#[test]
+fn test_fhirpath_generation() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("fhirpath")
+        .arg("--force")
+        .arg("--output-file")
+        .arg("target/selectors.txt")
+        .arg("test_data/ia/StructureDefinition-EHDSMedicationDispenseObligations.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/selectors.txt").unwrap();
+    assert!(output.contains("EHDSMedicationDispense.header.statusReason.ofType(CodeableConcept)"));
+}
+
+#[test]
+fn test_paths_generation() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("paths")
+        .arg("--force")
+        .arg("--output-file")
+        .arg("target/paths.csv")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSPatient.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/paths.csv").unwrap();
+    assert!(output.starts_with("structure definition,path,datatype,cardinality,maps to\n"));
+}
+
+#[test]
+fn test_adl_generation() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("adl")
+        .arg("--force")
+        .arg("--output-pattern")
+        .arg("target/{id}.adl")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSPatient.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/EHDSPatient.adl").unwrap();
+    assert!(output.contains("openEHR-EHR-CLUSTER.ehds_patient.v0.0.1"));
+}
+
+#[test]
+fn test_pdf_source_generation() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("pdf")
+        .arg("--force")
+        .arg("--source-only")
+        .arg("--output-dir")
+        .arg("target")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSPatient.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/EHDSPatient.typ").unwrap();
+    assert!(output.contains("table.header(repeat: true"));
+}
+
+#[test]
+fn test_pdf_compilation() {
+    let dir = std::path::Path::new("target/pdf_typst");
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir.join("diagrams")).unwrap();
+    fs::write(dir.join("diagrams/EHDSPatient.svg"), "<svg/>").unwrap();
+    let pdf = |force: bool| {
+        let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+        cmd.current_dir(dir);
+        if force {
+            cmd.arg("--force");
+        }
+        cmd.arg("pdf")
+            .arg("--typst")
+            .arg("../../test_data/typst.sh")
+            .arg("--output-dir")
+            .arg("out")
+            .arg("--diagrams-dir")
+            .arg("diagrams")
+            .arg("--combined")
+            .arg("EHDS: Patient/Summary")
+            .arg("../../test_data/ep-ed/StructureDefinition-EHDSPatient.json");
+        cmd.assert().success();
+    };
+
+    pdf(true);
+    // compiled with the folder of the document and the diagrams as root
+    let output = fs::read_to_string(dir.join("out/EHDS_ Patient_Summary.pdf")).unwrap();
+    let root = dir.canonicalize().unwrap();
+    assert!(output.starts_with(&format!("root: {}\n", root.display())));
+    assert!(output.contains("image(\"/diagrams/EHDSPatient.svg\""));
+
+    // an existing PDF is kept without --force
+    fs::remove_file(dir.join("out/EHDS_ Patient_Summary.typ")).unwrap();
+    fs::write(dir.join("out/EHDS_ Patient_Summary.pdf"), "existing").unwrap();
+    pdf(false);
+    assert_eq!(fs::read_to_string(dir.join("out/EHDS_ Patient_Summary.pdf")).unwrap(), "existing");
+}
+
+#[test]
+fn test_org_generation() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("org")
+        .arg("--force")
+        .arg("--output-pattern")
+        .arg("target/{id}.org")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSMedicationDispense.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/EHDSMedicationDispense.org").unwrap();
+    assert!(output.contains("\n** header\n"));
+}
+
+#[test]
+fn test_metrics_generation() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("metrics")
+        .arg("--force")
+        .arg("--output-file")
+        .arg("target/metrics.md")
+        .arg("--json-file")
+        .arg("target/metrics.json")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSPatient.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/metrics.json").unwrap();
+    let metrics: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(metrics[0]["id"], "EHDSPatient");
+}
+
+#[test]
+fn test_archive_generation() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("paths")
+        .arg("--force")
+        .arg("--archive")
+        .arg("target/archive.zip")
+        .arg("--output-file")
+        .arg("target/archived-paths.csv")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSPatient.json");
+
+    cmd.assert().success();
+
+    let archive = fs::read("target/archive.zip").unwrap();
+    assert!(archive.starts_with(b"PK\x03\x04"));
+    assert_eq!(archive_names(&archive), ["target/archived-paths.csv", "manifest.json"]);
+    assert!(!std::path::Path::new("target/archived-paths.csv").exists());
+}
+
+/// The names of the entries of a zip archive, from their local headers
+fn archive_names(archive: &[u8]) -> Vec<String> {
+    let field = |at: usize, len: usize| archive[at..at + len].iter().rev().fold(0usize, |value, byte| value << 8 | *byte as usize);
+    let mut names = Vec::new();
+    let mut at = 0;
+    while archive[at..].starts_with(b"PK\x03\x04") {
+        let (size, name_len, extra_len) = (field(at + 18, 4), field(at + 26, 2), field(at + 28, 2));
+        names.push(String::from_utf8_lossy(&archive[at + 30..at + 30 + name_len]).to_string());
+        at += 30 + name_len + extra_len + size;
+    }
+    names
+}
+
+#[test]
+fn test_archive_names() {
+    let dir = std::path::Path::new("target/archive_names");
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    let archive = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+        cmd.current_dir(dir.join("sub")).arg("paths").args(args).arg("../../../test_data/ep-ed/StructureDefinition-EHDSPatient.json");
+        cmd.assert()
+    };
+
+    // below the working directory, with the .. of a path resolved
+    archive(&["--archive", "paths.zip", "--output-file", "out/nested/../paths.csv"]).success();
+    let names = archive_names(&fs::read(dir.join("sub/paths.zip")).unwrap());
+    assert_eq!(names, ["out/paths.csv", "manifest.json"]);
+
+    // a file outside the working directory or taking the name of the manifest is refused, and left in place
+    let outside = archive(&["--archive", "outside.zip", "--output-file", "../outside.csv"]).failure();
+    assert!(String::from_utf8_lossy(&outside.get_output().stderr).contains("cannot be archived"));
+    assert!(dir.join("outside.csv").exists());
+    let manifest = archive(&["--archive", "manifest.zip", "--output-file", "manifest.json"]).failure();
+    assert!(String::from_utf8_lossy(&manifest.get_output().stderr).contains("would both be archived as 'manifest.json'"));
+    assert!(dir.join("sub/manifest.json").exists());
+}
+
+#[test]
+fn test_archive_exists() {
+    let _ = fs::remove_file("target/archive-twice.zip");
+    let _ = fs::remove_file("target/archive-twice.csv");
+    let archive = |cmd: &mut Command| {
+        cmd.arg("paths")
+            .arg("--archive")
+            .arg("target/archive-twice.zip")
+            .arg("--output-file")
+            .arg("target/archive-twice.csv")
+            .arg("test_data/ep-ed/StructureDefinition-EHDSPatient.json");
+    };
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    archive(&mut cmd);
+    cmd.assert().success();
+    let first = fs::read("target/archive-twice.zip").unwrap();
+
+    // without --force the archive is kept, and so are the files generated again
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    archive(&mut cmd);
+    cmd.assert().failure();
+    assert_eq!(fs::read("target/archive-twice.zip").unwrap(), first);
+    assert!(std::path::Path::new("target/archive-twice.csv").exists());
+}
+
+#[test]
+fn test_plantuml_relation_by_url() {
+    // a structure definition whose id differs from the last segment of its URL
+    let source = fs::read_to_string("test_data/ep-ed/StructureDefinition-EHDSAddress.json").unwrap();
+    let mut address: serde_json::Value = serde_json::from_str(&source).unwrap();
+    address["id"] = "ehds-address".into();
+    fs::create_dir_all("target").unwrap();
+    fs::write("target/ehds-address.json", address.to_string()).unwrap();
+
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("plant-uml")
+        .arg("--force")
+        .arg("--output-file")
+        .arg("target/by-url.plantuml")
+        .arg("target/ehds-address.json")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSOrganization.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/by-url.plantuml").unwrap();
+    assert!(output.contains("\"**EHDSOrganization**\" *-- \"0..*\" \"**ehds-address**\" : address >"));
+}
+
+#[test]
+fn test_single_file_output() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("table")
+        .arg("--force")
+        .arg("--single-file")
+        .arg("target/tables.md")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSAddress.json")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSPatient.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/tables.md").unwrap();
+    assert!(output.contains("EHDSAddress"));
+    assert!(output.contains("EHDSPatient"));
+}
+
+#[test]
+fn test_slice_in_element_tree() {
+    // a slice is an element below the one it slices, and its elements below it
+    let source = fs::read_to_string("test_data/ep-ed/StructureDefinition-EHDSPatient.json").unwrap();
+    let mut patient: serde_json::Value = serde_json::from_str(&source).unwrap();
+    let elements = patient["snapshot"]["element"].as_array_mut().unwrap();
+    let mut slice = elements[1].clone();
+    slice["id"] = "EHDSPatient.personalIdentifier:national".into();
+    slice["short"] = "National identifier".into();
+    let mut value = elements[3].clone();
+    value["id"] = "EHDSPatient.personalIdentifier:national.value".into();
+    elements.insert(2, slice);
+    elements.insert(3, value);
+    patient["id"] = "sliced-patient".into();
+    fs::create_dir_all("target").unwrap();
+    fs::write("target/sliced-patient.json", patient.to_string()).unwrap();
+
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.current_dir("target").arg("table").arg("--force").arg("sliced-patient.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/sliced-patient.md").unwrap();
+    assert!(output.contains("| A.1.1 | ++ | personalIdentifier:national |"));
+    assert!(output.contains("| A.1.1.1 | +++ | value |"));
+}
+
+#[test]
+fn test_plantuml_legend() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("plant-uml")
+        .arg("--force")
+        .arg("--legend")
+        .arg("--bold-must-support")
+        .arg("--output-file")
+        .arg("target/legend.plantuml")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSPatient.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/legend.plantuml").unwrap();
+    assert!(output.contains("legend right\n"));
+    assert!(output.contains("| **element** | must support |"));
+    assert!(output.contains("endlegend\n@enduml"));
+}
+
+#[test]
+fn test_mindmap_short_line() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.current_dir("target")
+        .arg("mindmap")
+        .arg("--force")
+        .arg("--short-line")
+        .arg("../test_data/ep-ed/StructureDefinition-EHDSAddress.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/EHDSAddress_mindmap.plantuml").unwrap();
+    assert!(output.contains("**:Use\n<size:10>Purpose of the address</size>;\n"));
+}
+
+#[test]
+fn test_mindmap_labels() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.current_dir("target")
+        .arg("mindmap")
+        .arg("--force")
+        .arg("--output-dir")
+        .arg("labels")
+        .arg("--lang")
+        .arg("sv")
+        .arg("--labels")
+        .arg("../test_data/labels.yaml")
+        .arg("../test_data/ep-ed/StructureDefinition-EHDSAddress.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/labels/EHDSAddress_mindmap.plantuml").unwrap();
+    assert!(output.contains("** Adressens användning\n"));
+    assert!(output.contains("** Town\n"));
+}
+
+#[test]
+fn test_mindmap_csv_labels() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.current_dir("target")
+        .arg("mindmap")
+        .arg("--force")
+        .arg("--output-dir")
+        .arg("csv_labels")
+        .arg("--lang")
+        .arg("sv")
+        .arg("--labels")
+        .arg("../test_data/labels.csv")
+        .arg("../test_data/ep-ed/StructureDefinition-EHDSAddress.json");
+
+    cmd.assert().success();
+
+    // the row of a label on two lines is read whole
+    let output = fs::read_to_string("target/csv_labels/EHDSAddress_mindmap.plantuml").unwrap();
+    assert!(output.contains("** Adressens användning\n"));
+    assert!(output.contains("** Stad, \"town\"\n"));
+}
+
+#[test]
+fn test_table_metadata() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.current_dir("target")
+        .arg("table")
+        .arg("--force")
+        .arg("--output-dir")
+        .arg("metadata")
+        .arg("--metadata")
+        .arg("../test_data/ep-ed/StructureDefinition-EHDSPatient.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/metadata/EHDSPatient.md").unwrap();
+    assert!(output.starts_with("---\nurl: \"https://www.xt-ehr.eu/specifications/fhir/StructureDefinition/EHDSPatient\"\n"));
+    assert!(output.contains("version: \"0.1.0\"\nstatus: \"draft\"\n"));
+    assert!(output.contains("---\n## EHDSPatient\n"));
+}
+
+#[test]
+fn test_table_metadata_single_file() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("table")
+        .arg("--force")
+        .arg("--single-file")
+        .arg("target/metadata_single.md")
+        .arg("--metadata")
+        .arg("--redact")
+        .arg("definitions")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSPatient.json")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSAddress.json");
+
+    cmd.assert().success();
+
+    // no front matter in the middle of the file
+    let output = fs::read_to_string("target/metadata_single.md").unwrap();
+    assert!(!output.contains("---\n"));
+    assert!(output.contains("## EHDSPatient\n- **url**: https://www.xt-ehr.eu/specifications/fhir/StructureDefinition/EHDSPatient\n"));
+    assert!(output.contains("## EHDSAddress\n- **url**: https://www.xt-ehr.eu/specifications/fhir/StructureDefinition/EHDSAddress\n"));
+    assert!(!output.contains("- **description**"));
+}
+
+#[test]
+fn test_plantuml_choice_composition() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("plant-uml")
+        .arg("--force")
+        .arg("--output-file")
+        .arg("target/test-choice.plantuml")
+        .arg("test_data/dr/StructureDefinition-EHDSAdvanceDirective.json")
+        .arg("test_data/dr/StructureDefinition-EHDSDevice.json");
+
+    cmd.assert().success();
+
+    // a choice of contained datatypes is a composition, as an element of a single one
+    let output = fs::read_to_string("target/test-choice.plantuml").unwrap();
+    assert!(output.contains("EHDSAdvanceDirectiveauthor *-- \"**EHDSDevice**\" : author >"));
+}
+
+#[test]
+fn test_plantuml_metadata() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("plant-uml")
+        .arg("--force")
+        .arg("--metadata")
+        .arg("--output-file")
+        .arg("target/test-metadata.plantuml")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSPatient.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/test-metadata.plantuml").unwrap();
+    assert!(output.contains("note top of \"**EHDSPatient**\"\n  url: https://www.xt-ehr.eu/specifications/fhir/StructureDefinition/EHDSPatient\n  version: 0.1.0\n"));
+}
+
+#[test]
+fn test_archimate_generation() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("archimate")
+        .arg("--force")
+        .arg("--output-file")
+        .arg("target/test-model.xml")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSPatient.json")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSAddress.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/test-model.xml").unwrap();
+    assert!(output.contains("<element identifier=\"id-EHDSPatient\" xsi:type=\"BusinessObject\">"));
+    assert!(output.contains(
+        "source=\"id-EHDSPatient\" target=\"id-EHDSAddress\" xsi:type=\"Association\">\n      <name xml:lang=\"en\">address</name>"
+    ));
+}
+
+#[test]
+fn test_extension_table() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.current_dir("target")
+        .arg("table")
+        .arg("--force")
+        .arg("--output-dir")
+        .arg("extension")
+        .arg("../test_data/StructureDefinition-EHDSNationality.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/extension/EHDSNationality.md").unwrap();
+    assert!(output.contains("**Context of use**: `Patient`, `RelatedPerson`"));
+    assert!(output.contains("| code | Nationality code<br/>Code representing a nationality of the patient. | CodeableConcept | 1..1 |"));
+    assert!(output.contains("| period |"));
+    assert!(!output.contains("extension:code"));
+}
+
+#[test]
+fn test_extension_diagrams() {
+    for generator in [&["plant-uml", "--output-file", "EHDSNationality.plantuml"][..], &["mindmap"], &["html-dictionary"]] {
+        let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+        cmd.current_dir("target")
+            .args(generator)
+            .arg("--force")
+            .arg("--output-dir")
+            .arg("extension_diagrams")
+            .arg("../test_data/StructureDefinition-EHDSNationality.json");
+        cmd.assert().success();
+    }
+
+    // the sub-extensions typed by their values, not the generic elements of an extension
+    let output = fs::read_to_string("target/extension_diagrams/EHDSNationality.plantuml").unwrap();
+    assert!(output.contains("class **EHDSNationality** <<Extension>> {\n  |_ code : CodeableConcept [1..1]\n  |_ period : Period [0..1]\n}"));
+    let output = fs::read_to_string("target/extension_diagrams/EHDSNationality_mindmap.plantuml").unwrap();
+    assert!(output.contains("* **EHDSNationality**\n** Code\n** Period\n"));
+    let output = fs::read_to_string("target/extension_diagrams/dictionary.html").unwrap();
+    assert!(output.contains("\"datatype\":[\"Period\"]"));
+    assert!(!output.contains("\"name\":\"url\""));
+}
+
+#[test]
+fn test_mindmap_direction() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.current_dir("target")
+        .arg("mindmap")
+        .arg("--force")
+        .arg("--direction")
+        .arg("both")
+        .arg("--left-from")
+        .arg("2")
+        .arg("--combined")
+        .arg("Sides")
+        .arg("../test_data/ep-ed/StructureDefinition-EHDSAddress.json")
+        .arg("../test_data/ep-ed/StructureDefinition-EHDSPatient.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/Sides_mindmap.plantuml").unwrap();
+    let left_side = output.find("left side\n").unwrap();
+    assert!(output.find("** **EHDSAddress**").unwrap() < left_side);
+    assert!(output.find("** **EHDSPatient**").unwrap() > left_side);
+}
+
+#[test]
+fn test_attributes_generation() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("attributes")
+        .arg("--force")
+        .arg("--output-file")
+        .arg("target/attributes.ndjson")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSAddress.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/attributes.ndjson").unwrap();
+    let rows = output.lines().map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()).collect::<Vec<_>>();
+    assert!(rows.iter().any(|row| row["element"] == "EHDSAddress.use"
+        && row["attribute"] == "binding.strength"
+        && row["value"] == "preferred"));
+}
+
+#[test]
+fn test_redact() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("attributes")
+        .arg("--force")
+        .arg("--redact")
+        .arg("definitions,value-sets")
+        .arg("--output-file")
+        .arg("target/attributes_redacted.csv")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSAddress.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/attributes_redacted.csv").unwrap();
+    assert!(output.contains("EHDSAddress,EHDSAddress.use,definition,Purpose of the address"));
+    assert!(output.contains("EHDSAddress,EHDSAddress.use,binding.strength,preferred"));
+    assert!(!output.contains("binding.valueSet"));
+    assert!(output.contains("EHDSAddress,EHDSAddress.use,max,1"));
+}
+
+#[test]
+fn test_redact_all_outputs() {
+    fn contents(dir: &std::path::Path) -> String {
+        let mut text = String::new();
+        for entry in fs::read_dir(dir).unwrap().flatten() {
+            let path = entry.path();
+            text += &if path.is_dir() { contents(&path) } else { String::from_utf8_lossy(&fs::read(&path).unwrap()).to_string() };
+        }
+        text
+    }
+    let generators: &[&[&str]] = &[
+        &["table"],
+        &["table", "--metadata"],
+        &["plant-uml", "--metadata"],
+        &["mindmap", "--format", "freemind", "--short-line"],
+        &["obligations"],
+        &["site"],
+        &["site", "--html", "--metadata"],
+        &["html-dictionary"],
+        &["json"],
+        &["dictionary"],
+        &["example"],
+        &["fhirpath"],
+        &["paths"],
+        &["attributes"],
+        &["adl"],
+        &["pdf", "--source-only"],
+        &["org"],
+        &["archimate"],
+        &["concept-map"],
+    ];
+    for (generator_num, generator) in generators.iter().enumerate() {
+        let dir = format!("target/redact/{}", generator_num);
+        let _ = fs::remove_dir_all(&dir);
+        let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+        cmd.args(*generator)
+            .arg("--force")
+            .arg("--output-dir")
+            .arg(&dir)
+            .arg("--redact")
+            .arg("definitions,mappings")
+            .arg("test_data/ep-ed/StructureDefinition-EHDSPatient.json")
+            .arg("test_data/ConceptMap-EHDSAddressUse.json");
+
+        cmd.assert().success();
+
+        let output = contents(std::path::Path::new(&dir));
+        assert!(!output.is_empty(), "{:?} generated nothing", generator);
+        // a definition, the description of the structure definition and that of the concept map
+        for redacted in ["unique within a defined scope", "EHDS refined base model", "national registry"] {
+            assert!(!output.contains(redacted), "{:?} contains {:?}", generator, redacted);
+        }
+        assert!(!output.contains("Holiday addresses are temporary"), "{:?} contains a mapping", generator);
+    }
+}
+
+#[test]
+fn test_fsh_input() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("attributes")
+        .arg("--force")
+        .arg("--sushi")
+        .arg("test_data/sushi.sh")
+        .arg("--output-file")
+        .arg("target/attributes_fsh.csv")
+        .arg("test_data/EHDSAddress.fsh");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/attributes_fsh.csv").unwrap();
+    assert!(output.contains("EHDSAddress,EHDSAddress.use,binding.strength,preferred"));
+}
+
+#[test]
+fn test_fsh_source() {
+    let project = std::path::Path::new("target/fsh_source_project");
+    let _ = fs::remove_dir_all(project);
+    fs::create_dir_all(project.join("input/fsh")).unwrap();
+    fs::write(project.join("sushi-config.yaml"), "FSHOnly: true\n").unwrap();
+    fs::copy("test_data/EHDSAddress.fsh", project.join("input/fsh/EHDSAddress.fsh")).unwrap();
+    let source = |input: &str| {
+        let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+        cmd.arg("json").arg("--force").arg("--sushi").arg("test_data/sushi.sh").arg("--output-file").arg("-").arg(input);
+        let assert = cmd.assert().success();
+        let stderr = String::from_utf8_lossy(&assert.get_output().stderr).to_string();
+        assert!(!stderr.contains("fhir-generate-"), "{:?} shows the temporary folder", stderr);
+        let output = serde_json::from_slice::<serde_json::Value>(&assert.get_output().stdout).unwrap();
+        output["structureDefinitions"][0]["source"].as_str().unwrap().to_string()
+    };
+
+    // the FSH file declaring the resource, not the resource built in the temporary folder
+    assert_eq!(source("test_data/EHDSAddress.fsh"), "test_data/EHDSAddress.fsh");
+    assert_eq!(source("target/fsh_source_project"), "target/fsh_source_project/input/fsh/EHDSAddress.fsh");
+}
+
+#[test]
+fn test_subtree() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("attributes")
+        .arg("--force")
+        .arg("--subtree")
+        .arg("EHDSAddress.use")
+        .arg("--output-file")
+        .arg("target/attributes_subtree.csv")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSAddress.json")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSDevice.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/attributes_subtree.csv").unwrap();
+    assert!(output.contains("EHDSAddress,EHDSAddress.use,min,0"));
+    assert!(!output.contains("EHDSAddress.city"));
+    assert!(!output.contains("EHDSDevice"));
+}
+
+/// The element paths of EHDSMedication written by `paths` with an element filter, the summary
+/// flag set on item.ingredient.substance and must support on batch.lotNumber
+fn filtered_paths(filter: &[&str], name: &str) -> Vec<String> {
+    let medication = fs::read_to_string("test_data/ps/StructureDefinition-EHDSMedication.json").unwrap();
+    let mut medication = serde_json::from_str::<serde_json::Value>(&medication).unwrap();
+    for element in medication["snapshot"]["element"].as_array_mut().unwrap() {
+        match element["id"].as_str().unwrap() {
+            "EHDSMedication.item.ingredient.substance" => element["isSummary"] = serde_json::json!(true),
+            "EHDSMedication.batch.lotNumber" => element["mustSupport"] = serde_json::json!(true),
+            _ => {}
+        }
+    }
+    let folder = format!("target/{}", name);
+    fs::create_dir_all(&folder).unwrap();
+    fs::write(format!("{}/StructureDefinition-EHDSMedication.json", folder), medication.to_string()).unwrap();
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("paths")
+        .arg("--force")
+        .args(filter)
+        .arg("--output-file")
+        .arg(format!("{}/paths.csv", folder))
+        .arg(format!("{}/StructureDefinition-EHDSMedication.json", folder));
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string(format!("{}/paths.csv", folder)).unwrap();
+    output.lines().skip(1).map(|line| line.split(',').nth(1).unwrap().to_string()).collect()
+}
+
+#[test]
+fn test_summary_only() {
+    let paths = filtered_paths(&["--summary-only"], "summary_only");
+    assert_eq!(
+        paths,
+        [
+            "EHDSMedication",
+            "EHDSMedication.item",
+            "EHDSMedication.item.ingredient",
+            "EHDSMedication.item.ingredient.substance"
+        ]
+    );
+}
+
+#[test]
+fn test_must_support_only() {
+    let paths = filtered_paths(&["--must-support-only"], "must_support_only");
+    assert_eq!(paths, ["EHDSMedication", "EHDSMedication.batch", "EHDSMedication.batch.lotNumber"]);
+}
+
+#[test]
+fn test_subtree_ancestors() {
+    let paths = filtered_paths(&["--subtree", "EHDSMedication.item.ingredient"], "subtree_ancestors");
+    assert_eq!(
+        paths,
+        [
+            "EHDSMedication",
+            "EHDSMedication.item",
+            "EHDSMedication.item.ingredient",
+            "EHDSMedication.item.ingredient.isActive",
+            "EHDSMedication.item.ingredient.substance",
+            "EHDSMedication.item.ingredient.strengthInfo",
+            "EHDSMedication.item.ingredient.strengthInfo.strength",
+            "EHDSMedication.item.ingredient.strengthInfo.basisOfStrengthSubstance"
+        ]
+    );
+}
+
+/// The cell of a markdown table in the column with this header, in the row of this element name
+fn table_cell(table: &str, element: &str, header: &str) -> Option<String> {
+    let cells = |line: &str| line.trim().trim_matches('|').split('|').map(|cell| cell.trim().to_string()).collect::<Vec<_>>();
+    let mut rows = table.lines().filter(|line| line.starts_with('|')).map(cells);
+    let headers = rows.next()?;
+    let column = headers.iter().position(|h| h == header)?;
+    let name = headers.iter().position(|h| h == "Element Name")?;
+    rows.find(|row| row[name] == element).map(|row| row[column].clone())
+}
+
+/// The codes of the table of a logical model with a slice, a child of the slice and an element
+/// after it two levels up, in the order of the rows
+fn numbered_codes(name: &str, args: &[&str]) -> Vec<String> {
+    let element = |id: &str, datatype: &str, max: &str| {
+        serde_json::json!({
+            "id": id,
+            "path": id.replace(":national", ""),
+            "short": id,
+            "definition": id,
+            "min": 0,
+            "max": max,
+            "type": [{ "code": datatype }]
+        })
+    };
+    let mut root = element("Numbered", "Base", "*");
+    root.as_object_mut().unwrap().remove("type");
+    let numbered = serde_json::json!({
+        "resourceType": "StructureDefinition",
+        "id": "Numbered",
+        "url": "http://example.org/StructureDefinition/Numbered",
+        "name": "Numbered",
+        "status": "draft",
+        "kind": "logical",
+        "abstract": false,
+        "type": "Numbered",
+        "baseDefinition": "http://hl7.org/fhir/StructureDefinition/Base",
+        "derivation": "specialization",
+        "snapshot": { "element": [
+            root,
+            element("Numbered.identifier", "Identifier", "*"),
+            element("Numbered.identifier:national", "Identifier", "1"),
+            element("Numbered.identifier:national.system", "uri", "1"),
+            element("Numbered.name", "string", "1"),
+            element("Numbered.birthDate", "date", "1")
+        ] }
+    });
+    let file = format!("target/{}/StructureDefinition-Numbered.json", name);
+    fs::create_dir_all(format!("target/{}", name)).unwrap();
+    fs::write(&file, numbered.to_string()).unwrap();
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("table").arg("--stdout").args(args).arg(&file);
+
+    let stdout = String::from_utf8_lossy(&cmd.assert().success().get_output().stdout).to_string();
+    stdout
+        .lines()
+        .filter(|line| line.starts_with("| ") && !line.starts_with("| Code"))
+        .map(|line| line.trim_matches('|').split('|').next().unwrap().trim().to_string())
+        .collect()
+}
+
+#[test]
+fn test_numbering_slices() {
+    // the slice is numbered below the element it slices, and the element after its child one level
+    // below the root, however far the hierarchy jumps back
+    assert_eq!(numbered_codes("numbering_slices", &[]), ["A", "A.1", "A.1.1", "A.1.1.1", "A.2", "A.3"]);
+}
+
+#[test]
+fn test_numbering_alphanumeric() {
+    assert_eq!(numbered_codes("numbering_alphanumeric", &["--numbering", "alphanumeric"]), ["A", "A.1", "A.1.a", "A.1.a.1", "A.2", "A.3"]);
+}
+
+#[test]
+fn test_numbering_stable_codes() {
+    // an earlier release had Numbered.gender as A.3, since removed, and had no Numbered.birthDate
+    fs::create_dir_all("target/numbering_stable").unwrap();
+    let codes = serde_json::json!({
+        "Numbered": "A",
+        "Numbered.identifier": "A.1",
+        "Numbered.identifier:national": "A.1.1",
+        "Numbered.identifier:national.system": "A.1.1.1",
+        "Numbered.name": "A.2",
+        "Numbered.gender": "A.3"
+    });
+    fs::write("target/numbering_stable/codes.json", codes.to_string()).unwrap();
+
+    // the code of the removed element is not reused, the new element is numbered after the others
+    let codes = numbered_codes("numbering_stable", &["--codes-file", "target/numbering_stable/codes.json"]);
+    assert_eq!(codes, ["A", "A.1", "A.1.1", "A.1.1.1", "A.2", "A.4"]);
+}
+
+#[test]
+fn test_flatten_cells() {
+    // EHDSMedicationDispense, its obligations profile and a profile on that, each constraining
+    // statusReason[x] in one way
+    let read = |file: &str| serde_json::from_str::<serde_json::Value>(&fs::read_to_string(file).unwrap()).unwrap();
+    let mut base = read("test_data/ep-ed/StructureDefinition-EHDSMedicationDispense.json");
+    let mut middle = read("test_data/ia/StructureDefinition-EHDSMedicationDispenseObligations.json");
+    let mut top = middle.clone();
+    top["id"] = serde_json::json!("EHDSMedicationDispenseFlat");
+    top["url"] = serde_json::json!(format!("{}Flat", middle["url"].as_str().unwrap()));
+    top["baseDefinition"] = middle["url"].clone();
+    fn status_reason(doc: &mut serde_json::Value) -> &mut serde_json::Value {
+        let elements = doc["snapshot"]["element"].as_array_mut().unwrap();
+        elements.iter_mut().find(|e| e["id"] == "EHDSMedicationDispense.header.statusReason[x]").unwrap()
+    }
+    status_reason(&mut base)["max"] = serde_json::json!("3");
+    status_reason(&mut base)["type"] = serde_json::json!([{ "code": "CodeableConcept" }]);
+    status_reason(&mut middle)["min"] = serde_json::json!(1);
+    status_reason(&mut middle)["max"] = serde_json::json!("*");
+    status_reason(&mut middle)["binding"] = serde_json::json!({ "strength": "required", "description": "Required reasons" });
+    status_reason(&mut top)["max"] = serde_json::json!("*");
+    status_reason(&mut top)["binding"] = serde_json::json!({ "strength": "example", "description": "Example reasons" });
+    let dir = "target/flatten";
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).unwrap();
+    for (name, doc) in [("base", &base), ("middle", &middle), ("top", &top)] {
+        fs::write(format!("{}/{}.json", dir, name), doc.to_string()).unwrap();
+    }
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("table")
+        .arg("--force")
+        .arg("--flatten")
+        .arg("--output-dir")
+        .arg(format!("{}/out", dir))
+        .arg(format!("{}/base.json", dir))
+        .arg(format!("{}/middle.json", dir))
+        .arg(format!("{}/top.json", dir));
+
+    cmd.assert().success();
+
+    // only the end of the chain, with the tightest cardinality, the common types and the strongest binding
+    assert_eq!(fs::read_dir(format!("{}/out", dir)).unwrap().count(), 1);
+    let output = fs::read_to_string(format!("{}/out/EHDSMedicationDispenseFlat.md", dir)).unwrap();
+    assert_eq!(table_cell(&output, "statusReason[x]", "Cardinality").unwrap(), "1..3");
+    assert_eq!(table_cell(&output, "statusReason[x]", "Data type").unwrap(), "CodeableConcept");
+    assert_eq!(table_cell(&output, "statusReason[x]", "Binding requirements").unwrap(), "Required reasons");
+}
+
+#[test]
+fn test_obligation_cells() {
+    let table = |actor: &[&str], dir: &str| {
+        let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+        cmd.arg("table")
+            .arg("--force")
+            .arg("--obligations")
+            .args(actor)
+            .arg("--output-dir")
+            .arg(dir)
+            .arg("test_data/ia/StructureDefinition-EHDSMedicationPrescriptionObligations.json");
+
+        cmd.assert().success();
+
+        fs::read_to_string(format!("{}/EHDSMedicationPrescriptionObligations.md", dir)).unwrap()
+    };
+
+    let output = table(&[], "target/obligations_all");
+    assert_eq!(
+        table_cell(&output, "subject", "Obligations").unwrap(),
+        "actor-prescribing-system: SHALL:able-to-populate<br/>actor-dispensing-system: SHALL:process"
+    );
+    assert_eq!(table_cell(&output, "presentedForm", "Obligations").unwrap(), "actor-prescribing-system: SHALL:able-to-populate");
+
+    // only the obligations of the actor, and the elements having them
+    let output = table(&["--actor", "actor-dispensing-system"], "target/obligations_actor");
+    assert_eq!(table_cell(&output, "subject", "Obligations").unwrap(), "actor-dispensing-system: SHALL:process");
+    assert_eq!(table_cell(&output, "presentedForm", "Obligations"), None);
+}
+
+#[test]
+fn test_support_level_cells() {
+    let obligations = fs::read_to_string("test_data/ia/StructureDefinition-EHDSMedicationDispenseObligations.json").unwrap();
+    let obligations = obligations.replace(
+        "https://www.xt-ehr.eu/specifications/fhir/actor-dispensing-system",
+        "https://ehds.eu/specifications/fhir/actor-full",
+    );
+    fs::create_dir_all("target/support_levels").unwrap();
+    fs::write("target/support_levels/StructureDefinition-EHDSMedicationDispenseObligations.json", obligations).unwrap();
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("table")
+        .arg("--force")
+        .arg("--output-dir")
+        .arg("target/support_levels/out")
+        .arg("target/support_levels/StructureDefinition-EHDSMedicationDispenseObligations.json");
+
+    cmd.assert().success();
+
+    // a support level without an obligation is a single empty cell, as many as the header has
+    let output = fs::read_to_string("target/support_levels/out/EHDSMedicationDispenseObligations.md").unwrap();
+    let columns = |line: &str| line.trim().trim_matches('|').split('|').count();
+    let mut rows = output.lines().filter(|line| line.starts_with('|'));
+    let header = columns(rows.next().unwrap());
+    assert!(rows.all(|row| columns(row) == header));
+    assert_eq!(table_cell(&output, "subject", "Relevance for support level \"full\"").unwrap(), "SHALL");
+    assert_eq!(table_cell(&output, "subject", "Relevance for support level \"basic\"").unwrap(), "");
+    assert_eq!(table_cell(&output, "lastUpdate", "Relevance for support level \"full\"").unwrap(), "");
+}
+
+/// EHDSAddress with use and type bound to local value sets of two and three codes, required, and
+/// country bound by a description only
+fn address_with_value_sets(dir: &str) -> Vec<String> {
+    let address = fs::read_to_string("test_data/ep-ed/StructureDefinition-EHDSAddress.json").unwrap();
+    let mut address = serde_json::from_str::<serde_json::Value>(&address).unwrap();
+    for element in address["snapshot"]["element"].as_array_mut().unwrap() {
+        match element["id"].as_str().unwrap() {
+            "EHDSAddress.use" => {
+                element["binding"] = serde_json::json!({ "strength": "required", "valueSet": "http://example.org/ValueSet/address-use|1.0" })
+            }
+            "EHDSAddress.type" => {
+                element["binding"] = serde_json::json!({ "strength": "required", "valueSet": "http://example.org/ValueSet/address-type" })
+            }
+            _ => {}
+        }
+    }
+    let value_set = |id: &str, title: &str, codes: &[(&str, &str)]| {
+        let concepts = codes.iter().map(|(code, display)| serde_json::json!({ "code": code, "display": display })).collect::<Vec<_>>();
+        serde_json::json!({
+            "resourceType": "ValueSet",
+            "url": format!("http://example.org/ValueSet/{}", id),
+            "version": "1.0",
+            "title": title,
+            "compose": { "include": [{ "system": format!("http://example.org/CodeSystem/{}", id), "concept": concepts }] }
+        })
+    };
+    let files = [
+        ("StructureDefinition-EHDSAddress.json", address),
+        ("ValueSet-address-use.json", value_set("address-use", "Address use", &[("home", "Home"), ("work", "Work")])),
+        (
+            "ValueSet-address-type.json",
+            value_set("address-type", "Address type", &[("postal", "Postal"), ("physical", "Physical"), ("both", "Postal & Physical")]),
+        ),
+    ];
+    fs::create_dir_all(dir).unwrap();
+    files
+        .iter()
+        .map(|(name, resource)| {
+            let file = format!("{}/{}", dir, name);
+            fs::write(&file, resource.to_string()).unwrap();
+            file
+        })
+        .collect()
+}
+
+#[test]
+fn test_binding_footnotes() {
+    let files = address_with_value_sets("target/binding_footnotes");
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("table")
+        .arg("--force")
+        .arg("--binding-footnotes")
+        .arg("--output-dir")
+        .arg("target/binding_footnotes/out")
+        .args(&files);
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/binding_footnotes/out/EHDSAddress.md").unwrap();
+    assert_eq!(table_cell(&output, "use", "Binding requirements").unwrap(), "[1](#ehdsaddress-binding-1)");
+    assert_eq!(table_cell(&output, "type", "Binding requirements").unwrap(), "[2](#ehdsaddress-binding-2)");
+    assert_eq!(table_cell(&output, "country", "Binding requirements").unwrap(), "[3](#ehdsaddress-binding-3)");
+    assert_eq!(table_cell(&output, "city", "Binding requirements").unwrap(), "");
+    assert!(output.ends_with(
+        "\n1. <a id=\"ehdsaddress-binding-1\"></a>**Address use**, [http://example.org/ValueSet/address-use](http://example.org/ValueSet/address-use), version 1.0, required\n\
+         2. <a id=\"ehdsaddress-binding-2\"></a>**Address type**, [http://example.org/ValueSet/address-type](http://example.org/ValueSet/address-type), required\n\
+         3. <a id=\"ehdsaddress-binding-3\"></a>**ISO 3166-1 alpha-2**, preferred\n"
+    ));
+}
+
+#[test]
+fn test_inline_codes() {
+    let files = address_with_value_sets("target/inline_codes");
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("table")
+        .arg("--force")
+        .arg("--inline-codes")
+        .arg("2")
+        .arg("--output-dir")
+        .arg("target/inline_codes/out")
+        .args(&files);
+
+    cmd.assert().success();
+
+    // the value set of three codes is over the limit, and a binding without one has none to list
+    let output = fs::read_to_string("target/inline_codes/out/EHDSAddress.md").unwrap();
+    assert_eq!(table_cell(&output, "use", "Binding requirements").unwrap(), "Address use<br/>home — Home<br/>work — Work");
+    assert_eq!(table_cell(&output, "type", "Binding requirements").unwrap(), "Address type");
+    assert_eq!(table_cell(&output, "country", "Binding requirements").unwrap(), "ISO 3166-1 alpha-2");
+}
+
+/// A profile of EHDSMedication constraining batch.lotNumber to 1..1 and adding batch.expiry,
+/// written to the folder with the paths of `paths --changed-only` when given the files
+fn changed_paths(folder: &str, with_base: bool) -> (Vec<String>, String) {
+    let base = fs::read_to_string("test_data/ps/StructureDefinition-EHDSMedication.json").unwrap();
+    let mut profile = serde_json::from_str::<serde_json::Value>(&base).unwrap();
+    profile["id"] = serde_json::json!("EHDSMedicationProfile");
+    profile["name"] = serde_json::json!("EHDSMedicationProfile");
+    profile["baseDefinition"] = profile["url"].clone();
+    profile["url"] = serde_json::json!("https://example.org/StructureDefinition/EHDSMedicationProfile");
+    profile["derivation"] = serde_json::json!("constraint");
+    let elements = profile["snapshot"]["element"].as_array_mut().unwrap();
+    for element in elements.iter_mut() {
+        for key in ["id", "path"] {
+            let renamed = element[key].as_str().unwrap().replacen("EHDSMedication", "EHDSMedicationProfile", 1);
+            element[key] = serde_json::json!(renamed);
+        }
+        if element["id"] == "EHDSMedicationProfile.batch.lotNumber" {
+            element["min"] = serde_json::json!(1);
+        }
+    }
+    let lot_number = elements.iter().position(|element| element["id"] == "EHDSMedicationProfile.batch.lotNumber").unwrap();
+    elements.insert(
+        lot_number + 1,
+        serde_json::json!({
+            "id": "EHDSMedicationProfile.batch.expiry",
+            "path": "EHDSMedicationProfile.batch.expiry",
+            "short": "Expiry date of the batch",
+            "definition": "Expiry date of the batch",
+            "min": 0,
+            "max": "1",
+            "type": [{ "code": "date" }]
+        }),
+    );
+    fs::create_dir_all(folder).unwrap();
+    let profile_file = format!("{}/StructureDefinition-EHDSMedicationProfile.json", folder);
+    fs::write(&profile_file, profile.to_string()).unwrap();
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("--diagnostics-format")
+        .arg("json")
+        .arg("paths")
+        .arg("--force")
+        .arg("--changed-only")
+        .arg("--output-file")
+        .arg(format!("{}/paths.csv", folder))
+        .arg(&profile_file);
+    if with_base {
+        cmd.arg("test_data/ps/StructureDefinition-EHDSMedication.json");
+    }
+
+    let stderr = String::from_utf8_lossy(&cmd.assert().success().get_output().stderr).to_string();
+    let output = fs::read_to_string(format!("{}/paths.csv", folder)).unwrap();
+    let paths = output
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').nth(1).unwrap().to_string())
+        .filter(|path| path.starts_with("EHDSMedicationProfile"))
+        .collect();
+    (paths, stderr)
+}
+
+#[test]
+fn test_changed_only() {
+    let (paths, stderr) = changed_paths("target/changed_only", true);
+
+    // the unchanged inherited elements are hidden, the constrained and added ones kept with their ancestors
+    assert_eq!(
+        paths,
+        [
+            "EHDSMedicationProfile",
+            "EHDSMedicationProfile.batch",
+            "EHDSMedicationProfile.batch.lotNumber",
+            "EHDSMedicationProfile.batch.expiry"
+        ]
+    );
+    // only the base of EHDSMedication itself is not loaded
+    assert!(!stderr.lines().any(|line| line.contains("unresolved-base") && line.contains("EHDSMedicationProfile")));
+}
+
+#[test]
+fn test_changed_only_unresolved_base() {
+    let (paths, stderr) = changed_paths("target/changed_only_unresolved", false);
+
+    assert!(stderr.lines().any(|line| line.contains("\"rule\":\"unresolved-base\"") && line.contains("EHDSMedicationProfile")));
+    assert!(paths.contains(&"EHDSMedicationProfile.item.ingredient.substance".to_string()));
+}
+
+#[test]
+fn test_help_all_config_keys() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("--help-all");
+
+    let stdout = String::from_utf8_lossy(&cmd.assert().success().get_output().stdout).to_string();
+    assert!(stdout.contains("Manifest keys:"));
+    assert!(stdout.contains("    prefix-code = \"...\""));
+    assert!(stdout.contains("Config file keys:"));
+    assert!(stdout.contains("  [plant-uml]\n  show-base = true"));
+}
+
+#[test]
+fn test_browse_needs_terminal() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("browse").arg("test_data/ep-ed/StructureDefinition-EHDSAddress.json");
+
+    let output = cmd.assert().failure().get_output().stderr.clone();
+    assert!(String::from_utf8_lossy(&output).contains("browse needs a terminal"));
+}
+
+#[test]
+fn test_pruned_members() {
+    let patient = fs::read_to_string("test_data/ep-ed/StructureDefinition-EHDSPatient.json").unwrap();
+    let mut heavy = serde_json::from_str::<serde_json::Value>(&patient).unwrap();
+    let marker = serde_json::json!("pruned while parsing");
+    heavy["text"] = serde_json::json!({ "status": "generated", "div": marker });
+    heavy["differential"] = heavy["snapshot"].clone();
+    heavy["mapping"] = serde_json::json!([{ "identity": marker }]);
+    for element in heavy["snapshot"]["element"].as_array_mut().unwrap() {
+        element["comment"] = marker.clone();
+        element["mapping"] = serde_json::json!([{ "identity": "ehds", "map": marker }]);
+    }
+    fs::create_dir_all("target/pruned").unwrap();
+    fs::write("target/pruned/StructureDefinition-EHDSPatient.json", heavy.to_string()).unwrap();
+
+    let generate = |input: &str, output: &str| {
+        let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+        cmd.arg("attributes").arg("--force").arg("--output-file").arg(output).arg(input);
+        cmd.assert().success();
+        fs::read_to_string(output).unwrap()
+    };
+    let pruned = generate("target/pruned/StructureDefinition-EHDSPatient.json", "target/pruned/heavy.csv");
+    let original = generate("test_data/ep-ed/StructureDefinition-EHDSPatient.json", "target/pruned/original.csv");
+
+    assert_eq!(pruned, original);
+    for attribute in ["short", "definition", "min", "max", "type", "binding.strength", "binding.description", "isSummary"] {
+        assert!(pruned.contains(&format!(",{},", attribute)), "{} is read", attribute);
+    }
+}
+
+#[test]
+fn test_base_by_url() {
+    // a base whose id is not the last segment of its canonical URL
+    let address = fs::read_to_string("test_data/ep-ed/StructureDefinition-EHDSAddress.json").unwrap();
+    let mut base = serde_json::from_str::<serde_json::Value>(&address).unwrap();
+    base["id"] = serde_json::json!("ehds-address");
+    let mut derived = base.clone();
+    derived["id"] = serde_json::json!("NationalAddress");
+    derived["name"] = serde_json::json!("NationalAddress");
+    derived["baseDefinition"] = base["url"].clone();
+    derived["url"] = serde_json::json!("http://example.org/StructureDefinition/NationalAddress");
+    derived["derivation"] = serde_json::json!("constraint");
+    let dir = std::path::Path::new("target/base_by_url");
+    fs::create_dir_all(dir).unwrap();
+    fs::write(dir.join("base.json"), base.to_string()).unwrap();
+    fs::write(dir.join("derived.json"), derived.to_string()).unwrap();
+
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.current_dir(dir).arg("metrics").arg("--force").arg("base.json").arg("derived.json");
+    cmd.assert().success();
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.current_dir(dir)
+        .arg("mindmap")
+        .arg("--force")
+        .arg("--by-base")
+        .arg("--combined")
+        .arg("Addresses")
+        .arg("base.json")
+        .arg("derived.json");
+    cmd.assert().success();
+
+    // the profile constrains nothing its base does not
+    let metrics = fs::read_to_string(dir.join("metrics.md")).unwrap();
+    assert!(metrics.contains("| NationalAddress | 9 | 1 | 0 | 0 | 0 | 0.00 | 0 |"));
+    let mindmap = fs::read_to_string(dir.join("Addresses_mindmap.plantuml")).unwrap();
+    // below all elements of its base, in a branch of its own
+    assert!(mindmap.contains("\n*** Country\n*** //Derived profiles//\n**** **NationalAddress**\n***** Use\n"));
+}
+
+#[test]
+fn test_serve() {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let mut server = std::process::Command::new(env!("CARGO_BIN_EXE_fhir-generate"))
+        .arg("serve")
+        .arg("--port")
+        .arg("0")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSAddress.json")
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stdout = BufReader::new(server.stdout.take().unwrap());
+    let address = stdout
+        .lines()
+        .find_map(|line| line.unwrap().strip_prefix("serving: http://").map(|address| address.trim_end_matches('/').to_string()))
+        .unwrap();
+
+    // a connection sending nothing does not hold up the others
+    let _idle = std::net::TcpStream::connect(&address).unwrap();
+    let mut stream = std::net::TcpStream::connect(&address).unwrap();
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(2))).unwrap();
+    write!(stream, "GET /EHDSAddress.html HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    let read = stream.read_to_string(&mut response);
+    server.kill().unwrap();
+    server.wait().unwrap();
+
+    read.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains("<h1>EHDSAddress</h1>"));
+    assert!(response.contains("fetch(\"/__version\")"));
+}
+
+#[test]
+fn test_serve_sushi_project() {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let project = std::path::Path::new("target/serve_project");
+    let _ = fs::remove_dir_all(project);
+    fs::create_dir_all(project.join("input/fsh")).unwrap();
+    fs::write(project.join("sushi-config.yaml"), "FSHOnly: true\n").unwrap();
+    let fsh = project.join("input/fsh/EHDSAddress.fsh");
+    fs::copy("test_data/EHDSAddress.fsh", &fsh).unwrap();
+    let mut server = std::process::Command::new(env!("CARGO_BIN_EXE_fhir-generate"))
+        .arg("serve")
+        .arg("--port")
+        .arg("0")
+        .arg("--sushi")
+        .arg("test_data/sushi.sh")
+        .arg(project)
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    // kept open, for the server to report the regeneration
+    let mut stdout = BufReader::new(server.stdout.take().unwrap()).lines();
+    let address = stdout
+        .by_ref()
+        .find_map(|line| line.unwrap().strip_prefix("serving: http://").map(|address| address.trim_end_matches('/').to_string()))
+        .unwrap();
+    let version = || {
+        let mut stream = std::net::TcpStream::connect(&address).unwrap();
+        write!(stream, "GET /__version HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response.rsplit("\r\n").next().unwrap().to_string()
+    };
+
+    let before = version();
+    // a FSH file of the project changes, which is not among the input files itself
+    fs::OpenOptions::new()
+        .write(true)
+        .open(&fsh)
+        .unwrap()
+        .set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(10))
+        .unwrap();
+    let after = version();
+    assert!(stdout.next().unwrap().unwrap().starts_with("regenerating:"));
+    server.kill().unwrap();
+    server.wait().unwrap();
+
+    assert_eq!(before, "1");
+    assert_eq!(after, "2");
+}
+
+#[test]
+fn test_git_diff() {
+    let repo = std::path::Path::new("target/diff_repo");
+    let _ = fs::remove_dir_all(repo);
+    fs::create_dir_all(repo).unwrap();
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.org"])
+            .args(args)
+            .current_dir(repo)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    let address = fs::read_to_string("test_data/ep-ed/StructureDefinition-EHDSAddress.json").unwrap();
+    fs::write(repo.join("StructureDefinition-EHDSAddress.json"), &address).unwrap();
+    git(&["init", "--quiet"]);
+    git(&["add", "."]);
+    git(&["commit", "--quiet", "-m", "release"]);
+    git(&["tag", "v1"]);
+    let mut changed = serde_json::from_str::<serde_json::Value>(&address).unwrap();
+    for element in changed["snapshot"]["element"].as_array_mut().unwrap() {
+        if element["id"] == "EHDSAddress.use" {
+            element["min"] = serde_json::json!(1);
+        }
+    }
+    fs::write(repo.join("StructureDefinition-EHDSAddress.json"), changed.to_string()).unwrap();
+
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.current_dir(repo)
+        .arg("diff")
+        .arg("--force")
+        .arg("--git")
+        .arg("v1")
+        .arg("StructureDefinition-EHDSAddress.json");
+
+    cmd.assert().success();
+
+    let report = fs::read_to_string(repo.join("changes.md")).unwrap();
+    assert!(report.contains("| EHDSAddress.use | cardinality | 0..1 | 1..1 |"));
+}
+
+#[test]
+fn test_git_diff_fsh() {
+    let repo = std::path::Path::new("target/diff_fsh_repo");
+    let _ = fs::remove_dir_all(repo);
+    fs::create_dir_all(repo).unwrap();
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.org"])
+            .args(args)
+            .current_dir(repo)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    fs::copy("test_data/EHDSAddress.fsh", repo.join("EHDSAddress.fsh")).unwrap();
+    git(&["init", "--quiet"]);
+    git(&["add", "."]);
+    git(&["commit", "--quiet", "-m", "release"]);
+    git(&["tag", "v1"]);
+
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.current_dir(repo)
+        .arg("diff")
+        .arg("--force")
+        .arg("--sushi")
+        .arg("../../test_data/sushi.sh")
+        .arg("--git")
+        .arg("v1")
+        .arg("EHDSAddress.fsh");
+
+    cmd.assert().success();
+
+    // built at the revision too, so that the structure definition is not reported as added
+    let report = fs::read_to_string(repo.join("changes.md")).unwrap();
+    assert!(report.contains("No changes in the model."), "{}", report);
+}
+
+#[test]
+fn test_structure_map_generation() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.current_dir("target")
+        .arg("structure-map")
+        .arg("--force")
+        .arg("../test_data/StructureMap-EHDSPatientToPatient.json");
+
+    cmd.assert().success();
+
+    let diagram = fs::read_to_string("target/EHDSPatientToPatient_map.plantuml").unwrap();
+    assert!(diagram.contains("partition \"PatientGroup(source src : EHDSPatient, target tgt : Patient)\" {"));
+    assert!(diagram.contains(":call NameGroup(n, tn);"));
+    assert!(diagram.contains("if (d.exists()) then (yes)"));
+    let listing = fs::read_to_string("target/EHDSPatientToPatient_map.md").unwrap();
+    assert!(listing.contains("| given.initials | `g as i check i.length() > 0` | `tgt._given = evaluate(i, 'substring(0, 1)')` |"));
+}
+
+#[test]
+fn test_concept_map_generation() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.current_dir("target")
+        .arg("concept-map")
+        .arg("--force")
+        .arg("--format")
+        .arg("csv")
+        .arg("../test_data/ConceptMap-EHDSAddressUse.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/EHDSAddressUse_conceptmap.csv").unwrap();
+    assert!(output.contains("http://example.org/CodeSystem/local-address-use,T,Temporary or holiday address,source-is-narrower-than-target,http://hl7.org/fhir/address-use,temp,Temporary,Holiday addresses are temporary\n"));
+    assert!(output.contains(",X,Unknown,not mapped,"));
+}
+
+#[test]
+fn test_search_parameters_generation() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.current_dir("target")
+        .arg("search-parameters")
+        .arg("--force")
+        .arg("../test_data/Bundle-EHDSSearchParameters.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/Patient_search.md").unwrap();
+    assert!(output.contains("| [identifier](http://example.org/SearchParameter/ehds-patient-identifier) | token | `Patient.identifier` |  | text, not, of-type | A personal identifier of the patient | EHDS server |"));
+    // declared by the capability statement only
+    assert!(output.contains("| [birthdate](http://hl7.org/fhir/SearchParameter/individual-birthdate) | date |"));
+    let output = fs::read_to_string("target/PractitionerRole_search.md").unwrap();
+    assert!(output.contains("`PractitionerRole.organization` | Organization |"));
+}
+
+#[test]
+fn test_instance_diagram_generation() {
+    let mut cmd = Command::cargo_bin("fhir-generate").unwrap();
+    cmd.arg("instance-diagram")
+        .arg("--force")
+        .arg("--all-fields")
+        .arg("--instance")
+        .arg("test_data/Bundle-EHDSPatientExample.json")
+        .arg("--output-file")
+        .arg("target/instances.plantuml")
+        .arg("test_data/ep-ed/StructureDefinition-EHDSPatient.json");
+
+    cmd.assert().success();
+
+    let output = fs::read_to_string("target/instances.plantuml").unwrap();
+    assert!(output.contains("object \"EHDSPatient/anna\" as o1 <<EHDSPatient>> {\n  personalIdentifier = 191212121212\n  name = Anna Andersson\n"));
+    assert!(output.contains("o1 --> o2 : generalPractitioner\n"));
+    // a reference to an instance that is not given
+    assert!(output.contains("object \"Organization/region\" as ref1 #line.dashed\n"));
 }